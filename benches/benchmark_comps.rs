@@ -23,7 +23,7 @@ pub fn bench_vect3(c: &mut Criterion) {
 pub fn bench_quat(c: &mut Criterion) {
     let quat_test_1 = Quat::new(1.0, Vect3::new(0.0, 1.0, 0.0));
 
-    c.bench_function("identity", |b| b.iter(|| Quat::identity()));
+    c.bench_function("identity", |b| b.iter(Quat::identity));
     c.bench_function("from_axis_angle", |b| {
         b.iter(|| Quat::from_axis_angle(Vect3::UP, 1.0))
     });
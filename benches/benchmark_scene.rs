@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use criterion::{criterion_group, criterion_main, Criterion};
 use rbpt::mods::{
     color::ColorRBG,
@@ -8,6 +10,7 @@ use rbpt::mods::{
     position::{Angle, Quat, Vect3},
     random::LCG,
     ray::Ray,
+    report::QuietReporter,
 };
 
 pub fn bench_mat(c: &mut Criterion) {
@@ -55,7 +58,7 @@ pub fn bench_image(c: &mut Criterion) {
 
 pub fn bench_scene(c: &mut Criterion) {
     let mut parser = Parser::build("benches/bench_scene.rtp").unwrap();
-    let mut scene = parser.parse_scene();
+    let mut scene = parser.parse_scene().unwrap();
 
     let ray = Ray::new(Vect3::new(0.0, 0.0, 20.0), Vect3::FORWARD);
     c.bench_function("scene_trace", |b| {
@@ -66,11 +69,29 @@ pub fn bench_scene(c: &mut Criterion) {
     });
 }
 
+/// Tile size 1 schedules one task per pixel, like the renderer did before tiling
+pub fn bench_tiles(c: &mut Criterion) {
+    let mut parser = Parser::build("benches/bench_scene.rtp").unwrap();
+    let mut group = c.benchmark_group("scene_render_tiles");
+    group.sample_size(10);
+
+    for tile_size in [1, 16, 32] {
+        let mut scene = parser.parse_scene().unwrap();
+        scene.set_reporter(Arc::new(QuietReporter));
+        scene.set_tile_size(tile_size);
+        group.bench_function(format!("tile_{tile_size}"), |b| {
+            b.iter(|| scene.render(5, 5, (100, 100)))
+        });
+    }
+    group.finish();
+}
+
 criterion_group!(
     benches_scene,
     bench_mat,
     bench_camera,
     bench_image,
-    bench_scene
+    bench_scene,
+    bench_tiles
 );
 criterion_main!(benches_scene);
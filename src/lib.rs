@@ -1,11 +1,24 @@
 #![feature(portable_simd)]
 pub mod mods;
-use std::error::Error;
+use std::{error::Error, fs, ops::RangeInclusive, path::Path, sync::Arc, time::Instant};
 
-use mods::parser::Parser;
+use mods::{
+    animation::frame_filename,
+    buffer::AccBuffer,
+    check::check_scene_file,
+    checkpoint::{load_checkpoint, Checkpoint, CheckpointInfo, DEFAULT_CHECKPOINT_EVERY},
+    config::{RenderOptions, StatsFormat},
+    image::{ppm_filename, ImageRGB},
+    network::render_on_workers,
+    parser::Parser,
+    render::{Progressive, Scene, SEED},
+    report::{QuietReporter, Reporter, Stage},
+    stats::SceneStats,
+    writer::{scene_to_json, scene_to_rtp},
+};
 
 /// Render scene
-///    scene_file: .rtp file describing the scene
+///    scene_file: .rtp, .json or .toml file describing the scene
 ///    image_file: file to save image to
 ///    render_iterations: number of render iterations to average
 ///    max_bounces: number of max ray bounces
@@ -17,11 +30,607 @@ pub fn render_scene(
     max_bounces: u32,
     resolution: (u32, u32),
 ) -> Result<(), Box<dyn Error>> {
-    let mut parser = Parser::build(scene_file)?;
-    let mut scene = parser.parse_scene();
+    render_scene_with_options(
+        scene_file,
+        image_file,
+        render_iterations,
+        max_bounces,
+        resolution,
+        &RenderOptions::default(),
+    )
+}
 
+/// Render a scene built with the library (see SceneBuilder), in memory:
+/// nothing is printed and no file is written
+///    scene: scene to render
+///    render_iterations: number of render iterations to average
+///    max_bounces: number of max ray bounces
+///    resolution: (width, height) of the rendered image
+pub fn render(
+    scene: &mut Scene,
+    render_iterations: usize,
+    max_bounces: u32,
+    resolution: (u32, u32),
+) -> ImageRGB {
+    scene.set_reporter(Arc::new(QuietReporter));
     scene.render(render_iterations, max_bounces, resolution);
+    scene.camera.image.clone()
+}
+
+/// Render scene with optional render settings
+///    scene_file: .rtp, .json or .toml file describing the scene
+///    image_file: file to save image to
+///    render_iterations: number of render iterations to average
+///    max_bounces: number of max ray bounces
+///    resolution: (width, height) of the rendered image
+///    options: optional render settings (progressive output, stopping criteria...)
+pub fn render_scene_with_options(
+    scene_file: &str,
+    image_file: &str,
+    render_iterations: usize,
+    max_bounces: u32,
+    resolution: (u32, u32),
+    options: &RenderOptions,
+) -> Result<(), Box<dyn Error>> {
+    render_scene_with_reporter(
+        scene_file,
+        image_file,
+        render_iterations,
+        max_bounces,
+        resolution,
+        options,
+        options.output.reporter(),
+    )
+}
+
+/// Render scene, telling a reporter about the render instead of printing it
+///    scene_file: .rtp, .json or .toml file describing the scene
+///    image_file: file to save image to
+///    render_iterations: number of render iterations to average
+///    max_bounces: number of max ray bounces
+///    resolution: (width, height) of the rendered image
+///    options: optional render settings (its output mode is ignored)
+///    reporter: receives the render's progress
+pub fn render_scene_with_reporter(
+    scene_file: &str,
+    image_file: &str,
+    render_iterations: usize,
+    max_bounces: u32,
+    resolution: (u32, u32),
+    options: &RenderOptions,
+    reporter: Arc<dyn Reporter>,
+) -> Result<(), Box<dyn Error>> {
+    let mut parser = build_parser(scene_file, options, &reporter)?;
+    let mut scene = parse_scene_file(&mut parser, scene_file, &reporter)?;
+
+    let mut render_iterations = render_iterations;
+    if let Some(range) = &options.iterations_range {
+        scene.set_first_iteration(range.start);
+        render_iterations = range.end;
+    }
+    apply_options(&mut scene, image_file, options);
+    apply_checkpoint_options(
+        &mut scene,
+        image_file,
+        parser.get_source_hash(),
+        max_bounces,
+        resolution,
+        options,
+    )?;
+    scene.render(render_iterations, max_bounces, resolution);
+    scene.save_image(image_file)?;
+    save_sample_heatmap(&scene, options, &reporter)?;
+
+    Ok(())
+}
+
+/// Render scene on other machines running `rbpt serve`
+///    scene_file: .rtp, .json or .toml file describing the scene (meshes must be found at the same path by the workers)
+///    image_file: file to save image to
+///    render_iterations: number of render iterations to average
+///    max_bounces: number of max ray bounces
+///    resolution: (width, height) of the rendered image
+///    workers: "host:port" addresses of the workers
+///    options: optional render settings (only the output mode and lenient parsing are supported,
+///             the others being refused)
+pub fn render_scene_on_workers(
+    scene_file: &str,
+    image_file: &str,
+    render_iterations: usize,
+    max_bounces: u32,
+    resolution: (u32, u32),
+    workers: &[String],
+    options: &RenderOptions,
+) -> Result<(), Box<dyn Error>> {
+    let unsupported = [
+        ("--progressive", options.progressive.is_some()),
+        ("--time-limit", options.time_limit.is_some()),
+        ("--target-noise", options.target_noise.is_some()),
+        ("--adaptive", options.adaptive.is_some()),
+        ("--sample-heatmap", options.sample_heatmap.is_some()),
+        ("--checkpoint", options.checkpoint.is_some()),
+        ("--checkpoint-every", options.checkpoint_every.is_some()),
+        ("--resume", options.resume.is_some()),
+        ("--iterations-range", options.iterations_range.is_some()),
+        ("--tile-size", options.tile_size.is_some()),
+    ];
+    if let Some((name, _)) = unsupported.iter().find(|(_, used)| *used) {
+        return Err(format!("{} is not supported with --workers", name).into());
+    }
+    let reporter = options.output.reporter();
+    let mut parser = build_parser(scene_file, options, &reporter)?;
+    let mut scene = parse_scene_file(&mut parser, scene_file, &reporter)?;
+    // Workers read a single .rtp source, so scenes are sent flattened: converted from JSON
+    // or TOML, and without includes
+    let source = scene_to_rtp(&scene)?;
+    let mut rtp_parser = Parser::from_source(&source);
+    rtp_parser.set_reporter(Arc::new(QuietReporter));
+    rtp_parser.set_lenient(options.lenient);
+    rtp_parser.parse_scene()?;
+    let scene_hash = rtp_parser.get_source_hash();
+
+    let start = Instant::now();
+    reporter.stage_started(Stage::Rendering, scene_file);
+    reporter.info(&format!(
+        "Rendering {} iterations on {} workers",
+        render_iterations,
+        workers.len()
+    ));
+    let acc_buffer = render_on_workers(
+        &source,
+        scene_hash,
+        render_iterations,
+        max_bounces,
+        resolution,
+        workers,
+        reporter.as_ref(),
+    )?;
+    reporter.stage_finished(Stage::Rendering, start.elapsed());
+
+    scene.camera.image = acc_buffer.to_image();
     scene.save_image(image_file)?;
+    Ok(())
+}
+
+/// Render an image sequence of an animated scene
+///    scene_file: .rtp, .json or .toml file describing the scene
+///    image_file: base name of the images, "out" giving out_0001.ppm, out_0002.ppm...
+///    frames: frames to render (end included)
+///    render_iterations: number of render iterations to average
+///    max_bounces: number of max ray bounces
+///    resolution: (width, height) of the rendered images
+pub fn render_animation(
+    scene_file: &str,
+    image_file: &str,
+    frames: RangeInclusive<u32>,
+    render_iterations: usize,
+    max_bounces: u32,
+    resolution: (u32, u32),
+) -> Result<(), Box<dyn Error>> {
+    render_animation_with_options(
+        scene_file,
+        image_file,
+        frames,
+        render_iterations,
+        max_bounces,
+        resolution,
+        &RenderOptions::default(),
+    )
+}
+
+/// Render an image sequence of an animated scene with optional render settings
+///    scene_file: .rtp, .json or .toml file describing the scene
+///    image_file: base name of the images, "out" giving out_0001.ppm, out_0002.ppm...
+///    frames: frames to render (end included)
+///    render_iterations: number of render iterations to average
+///    max_bounces: number of max ray bounces
+///    resolution: (width, height) of the rendered images
+///    options: optional render settings (progressive output...)
+pub fn render_animation_with_options(
+    scene_file: &str,
+    image_file: &str,
+    frames: RangeInclusive<u32>,
+    render_iterations: usize,
+    max_bounces: u32,
+    resolution: (u32, u32),
+    options: &RenderOptions,
+) -> Result<(), Box<dyn Error>> {
+    if options.checkpoint.is_some()
+        || options.resume.is_some()
+        || options.iterations_range.is_some()
+    {
+        return Err(
+            "checkpoints and iteration ranges are not supported for image sequences".into(),
+        );
+    }
+    let reporter = options.output.reporter();
+    let mut parser = build_parser(scene_file, options, &reporter)?;
+    let frame_count = (frames.end() - frames.start() + 1) as usize;
+
+    for (i, frame) in frames.enumerate() {
+        reporter.frame_started(frame, i, frame_count);
+        parser.set_frame(frame as f64);
+        let mut scene = parse_scene_file(&mut parser, scene_file, &reporter)?;
+        let frame_file = frame_filename(image_file, frame);
+
+        apply_options(&mut scene, &frame_file, options);
+        scene.render(render_iterations, max_bounces, resolution);
+        scene.save_image(&frame_file)?;
+    }
+
+    Ok(())
+}
+
+/// Builds the parser of a scene file, reporting to the given reporter
+fn build_parser(
+    scene_file: &str,
+    options: &RenderOptions,
+    reporter: &Arc<dyn Reporter>,
+) -> Result<Parser, Box<dyn Error>> {
+    let mut parser = Parser::build(scene_file)?;
+    parser.set_reporter(reporter.clone());
+    parser.set_lenient(options.lenient);
+    Ok(parser)
+}
+
+/// Parses the scene of a parser, reporting the parsing stage
+fn parse_scene_file(
+    parser: &mut Parser,
+    scene_file: &str,
+    reporter: &Arc<dyn Reporter>,
+) -> Result<Scene, Box<dyn Error>> {
+    let start = Instant::now();
+    reporter.stage_started(Stage::Parsing, scene_file);
+    let scene = parser.parse_scene()?;
+    reporter.stage_finished(Stage::Parsing, start.elapsed());
+    Ok(scene)
+}
+
+/// Applies optional render settings to a scene
+fn apply_options(scene: &mut Scene, image_file: &str, options: &RenderOptions) {
+    scene.set_progressive(
+        options
+            .progressive
+            .map(|mode| Progressive::new(image_file, mode)),
+    );
+    scene.set_time_limit(options.time_limit);
+    scene.set_target_noise(options.target_noise);
+    scene.set_adaptive(options.adaptive);
+    if let Some(tile_size) = options.tile_size {
+        scene.set_tile_size(tile_size);
+    }
+}
+
+/// Sets up checkpoints and resumes from a checkpoint, if asked for
+///
+/// Resuming is refused if the scene, the resolution or the max bounces changed
+fn apply_checkpoint_options(
+    scene: &mut Scene,
+    image_file: &str,
+    scene_hash: u64,
+    max_bounces: u32,
+    resolution: (u32, u32),
+    options: &RenderOptions,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(resume) = &options.resume {
+        let (info, acc_buffer) = load_checkpoint(resume)?;
+        if info.scene_hash != scene_hash {
+            return Err(format!("{} was saved for another scene file", resume).into());
+        }
+        if (acc_buffer.get_width(), acc_buffer.get_height())
+            != (resolution.0 as usize, resolution.1 as usize)
+        {
+            return Err(format!(
+                "{} was saved at a {}x{} resolution",
+                resume,
+                acc_buffer.get_width(),
+                acc_buffer.get_height()
+            )
+            .into());
+        }
+        if info.max_bounces != max_bounces {
+            return Err(
+                format!("{} was saved with {} max bounces", resume, info.max_bounces).into(),
+            );
+        }
+        if info.seed != SEED {
+            return Err(format!("{} was saved with another seed", resume).into());
+        }
+        scene.resume_from(info, acc_buffer);
+    }
+
+    // A resumed render keeps saving to its checkpoint file,
+    // and a partial render saves its samples to be merged
+    let filename = match (&options.checkpoint, &options.resume) {
+        (Some(filename), _) | (None, Some(filename)) => Some(filename.clone()),
+        (None, None) => options
+            .iterations_range
+            .as_ref()
+            .map(|_| acc_filename(image_file)),
+    };
+    scene.set_checkpoint(filename.map(|filename| {
+        Checkpoint::new(
+            &filename,
+            options.checkpoint_every.unwrap_or(DEFAULT_CHECKPOINT_EVERY),
+            scene_hash,
+        )
+    }));
+    Ok(())
+}
+
+/// Print statistics of a scene and an estimated render time, without rendering it
+///    scene_file: .rtp, .json or .toml file describing the scene
+///    render_iterations: number of render iterations to average
+///    max_bounces: number of max ray bounces
+///    resolution: (width, height) of the rendered image
+///    options: optional render settings (only lenient parsing is used)
+///    format: human readable text or JSON
+pub fn print_scene_stats(
+    scene_file: &str,
+    render_iterations: usize,
+    max_bounces: u32,
+    resolution: (u32, u32),
+    options: &RenderOptions,
+    format: StatsFormat,
+) -> Result<(), Box<dyn Error>> {
+    // The statistics are the output, the parsing is not reported
+    let mut parser = Parser::build(scene_file)?;
+    parser.set_reporter(Arc::new(QuietReporter));
+    parser.set_lenient(options.lenient);
+    let mut scene = parser.parse_scene()?;
+
+    let mut stats = SceneStats::new(
+        &scene,
+        parser.get_meshes(),
+        render_iterations,
+        max_bounces,
+        resolution,
+    );
+    stats.estimate_time(&mut scene);
+    // The report is the output of the command, whatever the progress output mode
+    println!("{}", stats.report(format));
+    Ok(())
+}
 
+/// Write a scene file back as a canonical .rtp file or as JSON, chosen by the output extension
+///    scene_file: .rtp, .json or .toml file describing the scene
+///    output_file: .rtp or .json file to write
+pub fn convert_scene(scene_file: &str, output_file: &str) -> Result<(), Box<dyn Error>> {
+    convert_scene_with_options(scene_file, output_file, &RenderOptions::default())
+}
+
+/// Write a scene file back as a canonical .rtp file or as JSON, with optional settings
+///    scene_file: .rtp, .json or .toml file describing the scene
+///    output_file: .rtp or .json file to write
+///    options: optional render settings (only the output mode is used)
+pub fn convert_scene_with_options(
+    scene_file: &str,
+    output_file: &str,
+    options: &RenderOptions,
+) -> Result<(), Box<dyn Error>> {
+    let extension = Path::new(output_file)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("");
+    if extension != "rtp" && extension != "json" {
+        return Err(format!(
+            "unknown scene format `{}` (expected .rtp or .json)",
+            output_file
+        )
+        .into());
+    }
+    let mut parser = Parser::build(scene_file)?;
+    parser.set_reporter(options.output.reporter());
+    let scene = parser.parse_scene()?;
+    let text = match extension {
+        "rtp" => scene_to_rtp(&scene)?,
+        _ => serde_json::to_string_pretty(&scene_to_json(&scene)?)? + "\n",
+    };
+    fs::write(output_file, text)?;
     Ok(())
 }
+
+/// Check scene files without rendering them, printing the problems found
+///    scene_files: .rtp files to check
+/// Returns the exit code: 0 without problems, 1 with warnings only, 2 with errors
+pub fn check_scenes(scene_files: &[String]) -> i32 {
+    check_scenes_with_options(scene_files, &RenderOptions::default())
+}
+
+/// Check scene files without rendering them, reporting the problems found
+///    scene_files: .rtp files to check
+///    options: optional render settings (only the output mode is used)
+/// Returns the exit code: 0 without problems, 1 with warnings only, 2 with errors
+pub fn check_scenes_with_options(scene_files: &[String], options: &RenderOptions) -> i32 {
+    let reporter = options.output.reporter();
+    let mut exit_code = 0;
+    for scene_file in scene_files {
+        let report = check_scene_file(scene_file);
+        for error in &report.errors {
+            reporter.error(&error.to_string());
+        }
+        for warning in &report.warnings {
+            reporter.warning(&warning.to_string());
+        }
+        let status = match report.exit_code() {
+            0 => "ok",
+            1 => "warnings",
+            _ => "failed",
+        };
+        reporter.info(&format!(
+            "{} {} : {} errors, {} warnings",
+            status,
+            scene_file,
+            report.errors.len(),
+            report.warnings.len()
+        ));
+        exit_code = exit_code.max(report.exit_code());
+    }
+    exit_code
+}
+
+/// Merge partial renders of the same scene
+///    acc_files: accumulation files written by renders with disjoint iteration ranges
+///    image_file: file to save the merged image to
+pub fn merge_renders(acc_files: &[String], image_file: &str) -> Result<(), Box<dyn Error>> {
+    merge_renders_with_options(acc_files, image_file, &RenderOptions::default())
+}
+
+/// Merge partial renders of the same scene, with optional settings
+///    acc_files: accumulation files written by renders with disjoint iteration ranges
+///    image_file: file to save the merged image to
+///    options: optional render settings (only the output mode is used)
+pub fn merge_renders_with_options(
+    acc_files: &[String],
+    image_file: &str,
+    options: &RenderOptions,
+) -> Result<(), Box<dyn Error>> {
+    let reporter = options.output.reporter();
+    reporter.info(&format!("Merging {} partial renders", acc_files.len()));
+    let mut merged: Option<(CheckpointInfo, AccBuffer)> = None;
+    let mut ranges: Vec<CheckpointInfo> = vec![];
+
+    for acc_file in acc_files {
+        let (info, acc_buffer) = load_checkpoint(acc_file)?;
+        reporter.info(&format!(
+            "  - {} : iterations {}..{}",
+            acc_file, info.first_iteration, info.next_iteration
+        ));
+        if let Some(other) = ranges.iter().find(|other| other.overlaps(&info)) {
+            return Err(format!(
+                "{} overlaps iterations {}..{} of another file",
+                acc_file, other.first_iteration, other.next_iteration
+            )
+            .into());
+        }
+        ranges.push(info);
+
+        match &mut merged {
+            None => merged = Some((info, acc_buffer)),
+            Some((first, merged_buffer)) => {
+                if (first.scene_hash, first.seed, first.max_bounces)
+                    != (info.scene_hash, info.seed, info.max_bounces)
+                {
+                    return Err(format!(
+                        "{} was rendered with another scene or settings",
+                        acc_file
+                    )
+                    .into());
+                }
+                if (merged_buffer.get_width(), merged_buffer.get_height())
+                    != (acc_buffer.get_width(), acc_buffer.get_height())
+                {
+                    return Err(format!("{} was rendered at another resolution", acc_file).into());
+                }
+                merged_buffer.merge(&acc_buffer);
+            }
+        }
+    }
+
+    let (_, acc_buffer) = merged.ok_or("no file to merge")?;
+    let filename = ppm_filename(image_file);
+    let start = Instant::now();
+    reporter.stage_started(Stage::Saving, &filename);
+    acc_buffer.to_image().write_ppm_atomic(&filename)?;
+    reporter.stage_finished(Stage::Saving, start.elapsed());
+    Ok(())
+}
+
+/// Builds the accumulation file name of a partial render ("out.ppm" -> "out.acc")
+fn acc_filename(image_file: &str) -> String {
+    let stem = image_file.strip_suffix(".ppm").unwrap_or(image_file);
+    format!("{}.acc", stem)
+}
+
+/// Saves the sample count heatmap of the last render, if asked for
+fn save_sample_heatmap(
+    scene: &Scene,
+    options: &RenderOptions,
+    reporter: &Arc<dyn Reporter>,
+) -> Result<(), Box<dyn Error>> {
+    if let (Some(filename), Some(acc_buffer)) = (&options.sample_heatmap, scene.get_acc_buffer()) {
+        let filename = ppm_filename(filename);
+        acc_buffer.sample_heatmap().write_ppm_atomic(&filename)?;
+        reporter.info(&format!("Saved sample heatmap to : {}", filename));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests_lib {
+    use std::{fs, net::TcpListener, thread, time::Duration};
+
+    use super::render_scene_on_workers;
+    use crate::mods::{
+        config::{OutputMode, RenderOptions},
+        network::handle_connection,
+        report::QuietReporter,
+    };
+
+    #[test]
+    fn include_on_workers() {
+        let dir = std::env::temp_dir().join("rbpt_tests_workers_include");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        fs::write(
+            dir.join("scene.rtp"),
+            "camera {\n    position: 0.0 0.0 5.0\n    rotation: 1.0 0.0 0.0 0.0\n    focal_length: 5.0\n    fov: 40.0\n}\ninclude \"lib/lamps.rtp\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("lib/lamps.rtp"),
+            "material {\n    name: light\n    color: 1.0 1.0 1.0\n    emission_color: 1.0 0.5 0.5\n    specular_color: 1.0 1.0 1.0\n    emission_strength: 1.0\n    smoothness: 0.5\n    specular_prob: 0.5\n}\nsphere {\n    position: 0.0 0.0 0.0\n    radius: 1.0\n    mat: light\n}\n",
+        )
+        .unwrap();
+
+        // The worker does not run in the scene's directory, it cannot read the included file
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let worker = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            for stream in listener.incoming().take(2) {
+                let _ = handle_connection(stream.unwrap(), None, &QuietReporter);
+            }
+        });
+        let options = RenderOptions {
+            output: OutputMode::Quiet,
+            ..RenderOptions::default()
+        };
+        let image_file = dir.join("out.ppm");
+        render_scene_on_workers(
+            dir.join("scene.rtp").to_str().unwrap(),
+            image_file.to_str().unwrap(),
+            2,
+            3,
+            (8, 6),
+            &[worker],
+            &options,
+        )
+        .unwrap();
+
+        assert!(image_file.exists());
+    }
+
+    #[test]
+    fn unsupported_worker_options() {
+        let options = RenderOptions {
+            output: OutputMode::Quiet,
+            time_limit: Some(Duration::from_secs(1)),
+            ..RenderOptions::default()
+        };
+        let error = render_scene_on_workers(
+            "scenes/demo.rtp",
+            "out.ppm",
+            2,
+            3,
+            (8, 6),
+            &[String::from("127.0.0.1:1")],
+            &options,
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "--time-limit is not supported with --workers"
+        );
+    }
+}
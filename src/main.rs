@@ -1,8 +1,18 @@
-use clap::{Arg, Command};
+use clap::{Arg, ArgAction, ArgMatches, Command};
+
+use std::{error::Error, path::PathBuf};
 
 use rbpt::{
-    mods::config::{parse_quality, parse_resolution},
-    render_scene,
+    check_scenes_with_options, convert_scene_with_options, merge_renders_with_options,
+    mods::{
+        config::{
+            parse_frames, parse_options, parse_output, parse_quality, parse_resolution,
+            parse_workers, OutputMode, RenderOptions,
+        },
+        network::serve,
+    },
+    print_scene_stats, render_animation_with_options, render_scene_on_workers,
+    render_scene_with_options,
 };
 
 fn main() {
@@ -10,44 +20,315 @@ fn main() {
         .version("1.0")
         .author("Timothee FAGET")
         .about("Basic Ray tracer written in Rust")
-        .arg(
-            Arg::new("scene_file")
-                .help(".rtp file describing the scene")
-                .required(true)
-                .index(1),
+        .args(render_args())
+        .args(output_args())
+        .subcommand(
+            Command::new("render")
+                .about("Render a scene (same as without a command)")
+                .args(render_args())
+                .args(output_args()),
+        )
+        .subcommand(
+            Command::new("serve")
+                .about("Render jobs sent by other machines (see --workers)")
+                .arg(
+                    Arg::new("bind")
+                        .long("bind")
+                        .value_name("ADDRESS")
+                        .help("address to listen on (0.0.0.0 to accept jobs from other machines)")
+                        .default_value("127.0.0.1"),
+                )
+                .arg(
+                    Arg::new("port")
+                        .short('p')
+                        .long("port")
+                        .value_name("PORT")
+                        .help("port to listen on")
+                        .value_parser(clap::value_parser!(u16))
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("mesh_dir")
+                        .long("mesh-dir")
+                        .value_name("DIR")
+                        .help("directory the mesh files of the jobs are read in (none without it)"),
+                )
+                .args(output_args()),
         )
-        .arg(
-            Arg::new("image_file")
-                .help("file to save image to (with '.ppm' extension or not)")
-                .required(true)
-                .index(2),
+        .subcommand(
+            Command::new("check")
+                .about("Check scene files without rendering them (exit code 1 on warnings, 2 on errors)")
+                .arg(
+                    Arg::new("scene_files")
+                        .help(".rtp, .json or .toml scene files to check")
+                        .required(true)
+                        .num_args(1..),
+                )
+                .args(output_args()),
         )
-        .arg(
-            Arg::new("quality")
-                .short('q')
-                .long("quality")
-                .value_name("QUALITY")
-                .help("Quality: (H, h, m, l, L), 1 or 2 numbers")
-                .num_args(1..=2)
-                .required(false),
+        .subcommand(
+            Command::new("convert")
+                .about("Write a scene back as a canonical .rtp file or as JSON")
+                .arg(
+                    Arg::new("scene_file")
+                        .help(".rtp, .json or .toml file describing the scene")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("output")
+                        .help(".rtp or .json file to write")
+                        .required(true),
+                )
+                .args(output_args()),
         )
-        .arg(
-            Arg::new("resolution")
-                .short('r')
-                .long("resolution")
-                .value_name("Resolution")
-                .help("Resolution: (H, h, m, l, L) or 2 numbers")
-                .num_args(1..=2)
-                .required(false),
+        .subcommand(
+            Command::new("merge")
+                .about("Merge partial renders of the same scene (see --iterations-range)")
+                .arg(
+                    Arg::new("acc_files")
+                        .help(".acc files written by partial renders")
+                        .required(true)
+                        .num_args(1..),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("IMAGE_FILE")
+                        .help("file to save the merged image to")
+                        .required(true),
+                )
+                .args(output_args()),
         )
+        .subcommand_negates_reqs(true)
+        .args_conflicts_with_subcommands(true)
         .get_matches();
 
+    let matches = match matches.subcommand() {
+        Some(("merge", merge)) => {
+            let acc_files: Vec<String> = merge
+                .get_many::<String>("acc_files")
+                .unwrap()
+                .cloned()
+                .collect();
+            let output = merge.get_one::<String>("output").unwrap();
+            let options = output_options(merge);
+            if let Err(e) = merge_renders_with_options(&acc_files, output, &options) {
+                print_error(&options, e);
+            }
+            return;
+        }
+        Some(("check", check)) => {
+            let scene_files: Vec<String> = check
+                .get_many::<String>("scene_files")
+                .unwrap()
+                .cloned()
+                .collect();
+            std::process::exit(check_scenes_with_options(
+                &scene_files,
+                &output_options(check),
+            ));
+        }
+        Some(("convert", convert)) => {
+            let options = output_options(convert);
+            if let Err(e) = convert_scene_with_options(
+                convert.get_one::<String>("scene_file").unwrap(),
+                convert.get_one::<String>("output").unwrap(),
+                &options,
+            ) {
+                print_error(&options, e);
+            }
+            return;
+        }
+        Some(("serve", serve_matches)) => {
+            let options = output_options(serve_matches);
+            if let Err(e) = serve(
+                serve_matches.get_one::<String>("bind").unwrap(),
+                *serve_matches.get_one::<u16>("port").unwrap(),
+                serve_matches
+                    .get_one::<String>("mesh_dir")
+                    .map(PathBuf::from),
+                options.output.reporter(),
+            ) {
+                print_error(&options, e);
+            }
+            return;
+        }
+        Some(("render", render_matches)) => render_matches.clone(),
+        _ => matches,
+    };
+
     let scene_file = matches.get_one::<String>("scene_file").unwrap();
     let image_file = matches.get_one::<String>("image_file").unwrap();
     let quality = parse_quality(&matches);
     let resolution = parse_resolution(&matches);
+    let options = parse_options(&matches);
+
+    if let Some(format) = options.stats {
+        if let Err(e) = print_scene_stats(
+            scene_file, quality.0, quality.1, resolution, &options, format,
+        ) {
+            print_error(&options, e);
+        }
+        return;
+    }
+
+    let result = match (parse_frames(&matches), parse_workers(&matches)) {
+        (Some(_), Some(_)) => Err("--frames is not supported with --workers".into()),
+        (Some(frames), None) => render_animation_with_options(
+            scene_file, image_file, frames, quality.0, quality.1, resolution, &options,
+        ),
+        (None, Some(workers)) => render_scene_on_workers(
+            scene_file, image_file, quality.0, quality.1, resolution, &workers, &options,
+        ),
+        (None, None) => render_scene_with_options(
+            scene_file, image_file, quality.0, quality.1, resolution, &options,
+        ),
+    };
+    if let Err(e) = result {
+        print_error(&options, e);
+    }
+}
 
-    if let Err(e) = render_scene(scene_file, image_file, quality.0, quality.1, resolution) {
-        println!("Erreur : {e}");
+/// Prints an error, as a JSON event with --json-progress
+fn print_error(options: &RenderOptions, error: Box<dyn Error>) {
+    match options.output {
+        OutputMode::Json => options.output.reporter().error(&error.to_string()),
+        _ => println!("Erreur : {error}"),
     }
 }
+
+/// Options of a command that only has an output mode
+fn output_options(matches: &ArgMatches) -> RenderOptions {
+    RenderOptions {
+        output: parse_output(matches),
+        ..RenderOptions::default()
+    }
+}
+
+/// Arguments choosing how a command reports what it does
+fn output_args() -> Vec<Arg> {
+    vec![
+        Arg::new("quiet")
+            .long("quiet")
+            .help("Print nothing but errors")
+            .action(ArgAction::SetTrue),
+        Arg::new("json_progress")
+            .long("json-progress")
+            .help("Print the progress as JSON, one event per line")
+            .action(ArgAction::SetTrue)
+            .conflicts_with("quiet"),
+    ]
+}
+
+/// Arguments of a render (without command or with the render command)
+fn render_args() -> Vec<Arg> {
+    vec![
+        Arg::new("scene_file")
+            .help(".rtp, .json or .toml file describing the scene")
+            .required(true)
+            .index(1),
+        Arg::new("image_file")
+            .help("file to save image to (with '.ppm' extension or not)")
+            .required(true)
+            .index(2),
+        Arg::new("quality")
+            .short('q')
+            .long("quality")
+            .value_name("QUALITY")
+            .help("Quality: (H, h, m, l, L), 1 or 2 numbers")
+            .num_args(1..=2)
+            .required(false),
+        Arg::new("resolution")
+            .short('r')
+            .long("resolution")
+            .value_name("Resolution")
+            .help("Resolution: (H, h, m, l, L) or 2 numbers")
+            .num_args(1..=2)
+            .required(false),
+        Arg::new("frames")
+            .short('f')
+            .long("frames")
+            .value_name("START..END")
+            .help("Render an image sequence of the frames from START to END (included)")
+            .required(false),
+        Arg::new("progressive")
+            .short('p')
+            .long("progressive")
+            .value_name("INTERVAL")
+            .help(
+                    "Write the current image after each iteration, or every INTERVAL (30s, 5m...)",
+                )
+            .num_args(0..=1)
+            .required(false),
+        Arg::new("time_limit")
+            .long("time-limit")
+            .value_name("DURATION")
+            .help("Stop rendering once DURATION (30s, 10m, 1h30m...) is used up")
+            .required(false),
+        Arg::new("target_noise")
+            .long("target-noise")
+            .value_name("NOISE")
+            .help("Stop rendering once the estimated noise is below NOISE (0.01...)")
+            .required(false),
+        Arg::new("adaptive")
+            .short('a')
+            .long("adaptive")
+            .value_name("THRESHOLD")
+            .help("After the warm-up, only sample pixels whose relative error is above THRESHOLD (default 0.05)")
+            .num_args(0..=1)
+            .required(false),
+        Arg::new("adaptive_warmup")
+            .long("adaptive-warmup")
+            .value_name("ITERATIONS")
+            .help("Iterations on every pixel before adaptive sampling starts (default 16)")
+            .required(false),
+        Arg::new("sample_heatmap")
+            .long("sample-heatmap")
+            .value_name("FILE")
+            .help("Save a heatmap of the number of samples of each pixel")
+            .required(false),
+        Arg::new("stats")
+            .long("stats")
+            .value_name("FORMAT")
+            .help("Print scene statistics and an estimated render time instead of rendering (text or json)")
+            .num_args(0..=1)
+            .default_missing_value("text")
+            .required(false),
+        Arg::new("lenient")
+            .long("lenient")
+            .help("Warn about undefined materials instead of failing")
+            .action(ArgAction::SetTrue),
+        Arg::new("tile_size")
+            .long("tile-size")
+            .value_name("PIXELS")
+            .help("Side of the square tiles rendered by each task (default 32)")
+            .required(false),
+        Arg::new("checkpoint")
+            .long("checkpoint")
+            .value_name("FILE")
+            .help("Save the render state to FILE periodically, to be resumed with --resume")
+            .required(false),
+        Arg::new("checkpoint_every")
+            .long("checkpoint-every")
+            .value_name("ITERATIONS")
+            .help("Iterations between two checkpoints (default 10)")
+            .required(false),
+        Arg::new("resume")
+            .long("resume")
+            .value_name("FILE")
+            .help("Continue an interrupted render from a checkpoint file")
+            .required(false),
+        Arg::new("iterations_range")
+            .long("iterations-range")
+            .value_name("START..END")
+            .help("Only render the iterations from START to END (excluded), saving the samples to be merged")
+            .required(false),
+        Arg::new("workers")
+            .long("workers")
+            .value_name("HOST:PORT,...")
+            .help("Render on other machines running 'rbpt serve'")
+            .value_delimiter(',')
+            .required(false),
+    ]
+}
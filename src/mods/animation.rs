@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+
+use super::{
+    color::{lerp_color, ColorRBG},
+    position::{lerp, Quat, Vect3},
+};
+
+/// Interpolation used from a keyframe to the next one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    Linear,
+    Bezier,
+}
+
+impl Interpolation {
+    /// Get interpolation from its name
+    pub fn from_name(name: &str) -> Option<Interpolation> {
+        match name {
+            "linear" => Some(Interpolation::Linear),
+            "bezier" => Some(Interpolation::Bezier),
+            _ => None,
+        }
+    }
+
+    /// Remaps segment progress (0.0 to 1.0)
+    ///
+    /// Bezier uses the cubic curve with flat handles on both keys (ease in and out)
+    #[inline]
+    pub fn ease(&self, t: f64) -> f64 {
+        match self {
+            Interpolation::Linear => t,
+            Interpolation::Bezier => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// Values that can be interpolated between keyframes
+pub trait Animatable: Copy {
+    fn interpolate(start: Self, end: Self, t: f64) -> Self;
+}
+
+impl Animatable for f64 {
+    fn interpolate(start: Self, end: Self, t: f64) -> Self {
+        start + (end - start) * t
+    }
+}
+
+impl Animatable for Vect3 {
+    fn interpolate(start: Self, end: Self, t: f64) -> Self {
+        lerp(start, end, t)
+    }
+}
+
+impl Animatable for Quat {
+    fn interpolate(start: Self, end: Self, t: f64) -> Self {
+        start.slerp(end, t)
+    }
+}
+
+impl Animatable for ColorRBG {
+    fn interpolate(start: Self, end: Self, t: f64) -> Self {
+        lerp_color(start, end, t)
+    }
+}
+
+/// Keyframe implementation
+#[derive(Debug, Clone, Copy)]
+pub struct Keyframe<T> {
+    pub frame: f64,
+    pub value: T,
+    pub interpolation: Interpolation,
+}
+
+/// Track implementation: keyframes sorted by frame
+#[derive(Debug, Clone)]
+pub struct Track<T> {
+    keys: Vec<Keyframe<T>>,
+}
+
+impl<T: Animatable> Track<T> {
+    /// New empty Track constructor
+    pub fn new() -> Track<T> {
+        Track { keys: vec![] }
+    }
+
+    /// Adds a keyframe, replacing any key on the same frame
+    pub fn add_key(&mut self, frame: f64, value: T, interpolation: Interpolation) {
+        let key = Keyframe {
+            frame,
+            value,
+            interpolation,
+        };
+        match self.keys.iter().position(|k| k.frame >= frame) {
+            Some(i) if self.keys[i].frame == frame => self.keys[i] = key,
+            Some(i) => self.keys.insert(i, key),
+            None => self.keys.push(key),
+        }
+    }
+
+    /// Is the track empty
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Get value at a given frame, holding the first and last keys outside of the track
+    pub fn sample(&self, frame: f64) -> Option<T> {
+        let first = self.keys.first()?;
+        let last = self.keys.last()?;
+        if frame <= first.frame {
+            return Some(first.value);
+        }
+        if frame >= last.frame {
+            return Some(last.value);
+        }
+
+        let next = self.keys.iter().position(|k| k.frame > frame)?;
+        let (start, end) = (&self.keys[next - 1], &self.keys[next]);
+        let t = (frame - start.frame) / (end.frame - start.frame);
+        Some(T::interpolate(
+            start.value,
+            end.value,
+            start.interpolation.ease(t),
+        ))
+    }
+}
+
+impl<T: Animatable> Default for Track<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Value of an animated block property
+#[derive(Debug, Clone, Copy)]
+pub enum AnimatedValue {
+    Number(f64),
+    Vect3(Vect3),
+    Quat(Quat),
+    Color(ColorRBG),
+}
+
+impl Animatable for AnimatedValue {
+    fn interpolate(start: Self, end: Self, t: f64) -> Self {
+        match (start, end) {
+            (AnimatedValue::Number(a), AnimatedValue::Number(b)) => {
+                AnimatedValue::Number(f64::interpolate(a, b, t))
+            }
+            (AnimatedValue::Vect3(a), AnimatedValue::Vect3(b)) => {
+                AnimatedValue::Vect3(Vect3::interpolate(a, b, t))
+            }
+            (AnimatedValue::Quat(a), AnimatedValue::Quat(b)) => {
+                AnimatedValue::Quat(Quat::interpolate(a, b, t))
+            }
+            (AnimatedValue::Color(a), AnimatedValue::Color(b)) => {
+                AnimatedValue::Color(ColorRBG::interpolate(a, b, t))
+            }
+            // A property always keeps the same kind of value
+            _ => start,
+        }
+    }
+}
+
+/// Tracks of a scene block, by property name
+#[derive(Debug, Clone, Default)]
+pub struct Tracks {
+    tracks: HashMap<String, Track<AnimatedValue>>,
+}
+
+impl Tracks {
+    /// New empty Tracks constructor
+    pub fn new() -> Tracks {
+        Tracks::default()
+    }
+
+    /// Adds a keyframe to a property track
+    pub fn add_key(
+        &mut self,
+        property: &str,
+        frame: f64,
+        value: AnimatedValue,
+        interpolation: Interpolation,
+    ) {
+        self.tracks
+            .entry(property.to_string())
+            .or_default()
+            .add_key(frame, value, interpolation);
+    }
+
+    /// Is the property animated
+    pub fn has(&self, property: &str) -> bool {
+        self.tracks.get(property).is_some_and(|t| !t.is_empty())
+    }
+
+    /// Get animated number at a given frame
+    pub fn number(&self, property: &str, frame: f64) -> Option<f64> {
+        match self.tracks.get(property)?.sample(frame)? {
+            AnimatedValue::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// Get animated Vect3 at a given frame
+    pub fn vect3(&self, property: &str, frame: f64) -> Option<Vect3> {
+        match self.tracks.get(property)?.sample(frame)? {
+            AnimatedValue::Vect3(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Get animated Quat at a given frame
+    pub fn quat(&self, property: &str, frame: f64) -> Option<Quat> {
+        match self.tracks.get(property)?.sample(frame)? {
+            AnimatedValue::Quat(q) => Some(q),
+            _ => None,
+        }
+    }
+
+    /// Get animated ColorRBG at a given frame
+    pub fn color(&self, property: &str, frame: f64) -> Option<ColorRBG> {
+        match self.tracks.get(property)?.sample(frame)? {
+            AnimatedValue::Color(c) => Some(c),
+            _ => None,
+        }
+    }
+}
+
+/// Builds the file name of a frame of an image sequence ("out" -> "out_0001.ppm")
+pub fn frame_filename(image_file: &str, frame: u32) -> String {
+    let stem = image_file.strip_suffix(".ppm").unwrap_or(image_file);
+    format!("{}_{:04}.ppm", stem, frame)
+}
+
+#[cfg(test)]
+mod tests_animation {
+    use approx::assert_abs_diff_eq;
+
+    use crate::mods::position::Vect3;
+
+    use super::{frame_filename, AnimatedValue, Interpolation, Track, Tracks};
+
+    #[test]
+    fn linear() {
+        let mut track = Track::new();
+        track.add_key(10.0, 2.0, Interpolation::Linear);
+        track.add_key(0.0, 0.0, Interpolation::Linear);
+
+        assert_eq!(track.sample(-5.0), Some(0.0));
+        assert_eq!(track.sample(5.0), Some(1.0));
+        assert_eq!(track.sample(20.0), Some(2.0));
+    }
+
+    #[test]
+    fn bezier() {
+        let mut track = Track::new();
+        track.add_key(0.0, 0.0, Interpolation::Bezier);
+        track.add_key(10.0, 1.0, Interpolation::Linear);
+
+        assert_eq!(track.sample(5.0), Some(0.5));
+        assert!(track.sample(2.0).unwrap() < 0.2);
+        assert!(track.sample(8.0).unwrap() > 0.8);
+    }
+
+    #[test]
+    fn replace_key() {
+        let mut track = Track::new();
+        track.add_key(0.0, 0.0, Interpolation::Linear);
+        track.add_key(0.0, 1.0, Interpolation::Linear);
+
+        assert_eq!(track.sample(0.0), Some(1.0));
+    }
+
+    #[test]
+    fn tracks() {
+        let mut tracks = Tracks::new();
+        tracks.add_key(
+            "position",
+            0.0,
+            AnimatedValue::Vect3(Vect3::ZERO),
+            Interpolation::Linear,
+        );
+        tracks.add_key(
+            "position",
+            4.0,
+            AnimatedValue::Vect3(Vect3::new(4.0, 0.0, 0.0)),
+            Interpolation::Linear,
+        );
+
+        assert!(tracks.has("position"));
+        assert!(!tracks.has("rotation"));
+        assert_abs_diff_eq!(
+            tracks.vect3("position", 1.0).unwrap(),
+            Vect3::new(1.0, 0.0, 0.0)
+        );
+        assert_eq!(tracks.number("position", 1.0), None);
+    }
+
+    #[test]
+    fn filenames() {
+        assert_eq!(frame_filename("out", 1), "out_0001.ppm");
+        assert_eq!(
+            frame_filename("renders/out.ppm", 42),
+            "renders/out_0042.ppm"
+        );
+    }
+}
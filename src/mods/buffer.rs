@@ -0,0 +1,244 @@
+use super::{
+    color::{lerp_color, ColorRBG, ColorRBGOF},
+    image::ImageRGB,
+};
+
+/// Accumulated samples of a pixel
+#[derive(Debug, Clone, Copy)]
+pub struct PixelStats {
+    pub color: ColorRBGOF,
+    pub lum: f64,
+    pub lum_sq: f64,
+    pub samples: u32,
+}
+
+impl PixelStats {
+    /// Empty PixelStats
+    pub const EMPTY: Self = Self {
+        color: ColorRBGOF::BLACK,
+        lum: 0.0,
+        lum_sq: 0.0,
+        samples: 0,
+    };
+
+    /// Adds a sample
+    #[inline]
+    pub fn add(&mut self, color: ColorRBG) {
+        let lum = color.luminance();
+        self.color = self.color + color;
+        self.lum += lum;
+        self.lum_sq += lum * lum;
+        self.samples += 1;
+    }
+
+    /// Adds the samples of another PixelStats
+    #[inline]
+    pub fn merge(&mut self, other: &PixelStats) {
+        let (r, g, b) = other.color.get_value();
+        let (sr, sg, sb) = self.color.get_value();
+        self.color = ColorRBGOF::new(sr + r, sg + g, sb + b);
+        self.lum += other.lum;
+        self.lum_sq += other.lum_sq;
+        self.samples += other.samples;
+    }
+
+    /// Get average color
+    #[inline]
+    pub fn mean(&self) -> ColorRBG {
+        if self.samples == 0 {
+            return ColorRBG::BLACK;
+        }
+        ((1.0 / self.samples as f64) * self.color).to_rgb()
+    }
+
+    /// Get the variance of the average luminance (sample variance / samples)
+    #[inline]
+    pub fn mean_variance(&self) -> f64 {
+        if self.samples < 2 {
+            return f64::INFINITY;
+        }
+        let n = self.samples as f64;
+        let variance = (self.lum_sq - self.lum * self.lum / n) / (n - 1.0);
+        variance.max(0.0) / n
+    }
+
+    /// Get the relative standard error of the average luminance
+    #[inline]
+    pub fn relative_error(&self) -> f64 {
+        if self.samples < 2 {
+            return f64::INFINITY;
+        }
+        let mean = self.lum / self.samples as f64;
+        self.mean_variance().sqrt() / mean.max(0.01)
+    }
+}
+
+/// Accumulation buffer implementation: running sums of samples for each pixel
+#[derive(Debug, Clone)]
+pub struct AccBuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<PixelStats>,
+}
+
+impl AccBuffer {
+    /// New empty AccBuffer constructor
+    pub fn new(width: usize, height: usize) -> AccBuffer {
+        AccBuffer {
+            width,
+            height,
+            pixels: vec![PixelStats::EMPTY; width * height],
+        }
+    }
+
+    /// Build AccBuffer from row-major pixel stats
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<PixelStats>) -> Option<AccBuffer> {
+        if pixels.len() != width * height {
+            return None;
+        }
+        Some(AccBuffer {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Get pixel stats, row by row
+    pub fn pixels(&self) -> &[PixelStats] {
+        &self.pixels
+    }
+
+    /// Adds the samples of another buffer of the same size
+    pub fn merge(&mut self, other: &AccBuffer) {
+        assert_eq!((self.width, self.height), (other.width, other.height));
+        for (pixel, other) in self.pixels.iter_mut().zip(other.pixels.iter()) {
+            pixel.merge(other);
+        }
+    }
+
+    /// Get width
+    #[inline]
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+
+    /// Get height
+    #[inline]
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    /// Get a pixel's stats
+    #[inline]
+    pub fn get(&self, x: usize, y: usize) -> &PixelStats {
+        &self.pixels[y * self.width + x]
+    }
+
+    /// Adds a sample to a pixel
+    #[inline]
+    pub fn add_sample(&mut self, x: usize, y: usize, color: ColorRBG) {
+        self.pixels[y * self.width + x].add(color);
+    }
+
+    /// Estimated noise: root mean square of the pixels' standard error (luminance)
+    pub fn noise(&self) -> f64 {
+        let sum: f64 = self.pixels.iter().map(|p| p.mean_variance()).sum();
+        (sum / self.pixels.len() as f64).sqrt()
+    }
+
+    /// Averages accumulated colors into an image
+    pub fn to_image(&self) -> ImageRGB {
+        let mut image = ImageRGB::new(self.width as u32, self.height as u32);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                image.set_pixel(x, y, self.get(x, y).mean().rgb());
+            }
+        }
+        image
+    }
+
+    /// Heatmap of the number of samples of each pixel (blue: fewest, red: most)
+    pub fn sample_heatmap(&self) -> ImageRGB {
+        let max = self.pixels.iter().map(|p| p.samples).max().unwrap_or(0);
+        let min = self.pixels.iter().map(|p| p.samples).min().unwrap_or(0);
+        let range = (max - min).max(1) as f64;
+
+        let mut image = ImageRGB::new(self.width as u32, self.height as u32);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let t = (self.get(x, y).samples - min) as f64 / range;
+                let color = if t < 0.5 {
+                    lerp_color(ColorRBG::BLUE, ColorRBG::GREEN, 2.0 * t)
+                } else {
+                    lerp_color(ColorRBG::GREEN, ColorRBG::RED, 2.0 * t - 1.0)
+                };
+                image.set_pixel(x, y, color.rgb());
+            }
+        }
+        image
+    }
+}
+
+#[cfg(test)]
+mod tests_buffer {
+    use crate::mods::color::ColorRBG;
+
+    use super::AccBuffer;
+
+    #[test]
+    fn average() {
+        let mut buffer = AccBuffer::new(2, 1);
+        buffer.add_sample(1, 0, ColorRBG::WHITE);
+        buffer.add_sample(1, 0, ColorRBG::BLACK);
+
+        assert_eq!(buffer.get(1, 0).samples, 2);
+        assert_eq!(buffer.get(1, 0).mean(), ColorRBG::new(0.5, 0.5, 0.5));
+        assert_eq!(buffer.get(0, 0).mean(), ColorRBG::BLACK);
+    }
+
+    #[test]
+    fn noise() {
+        let mut flat = AccBuffer::new(1, 1);
+        let mut noisy = AccBuffer::new(1, 1);
+        for i in 0..10 {
+            flat.add_sample(0, 0, ColorRBG::WHITE);
+            let color = if i % 2 == 0 {
+                ColorRBG::WHITE
+            } else {
+                ColorRBG::BLACK
+            };
+            noisy.add_sample(0, 0, color);
+        }
+
+        assert!(flat.noise() < 1e-6);
+        assert!(noisy.noise() > 0.1);
+        assert!(AccBuffer::new(1, 1).noise().is_infinite());
+        assert!(flat.get(0, 0).relative_error() < 1e-6);
+        assert!(noisy.get(0, 0).relative_error() > 0.1);
+    }
+
+    #[test]
+    fn merge() {
+        let mut buffer_1 = AccBuffer::new(1, 1);
+        let mut buffer_2 = AccBuffer::new(1, 1);
+        buffer_1.add_sample(0, 0, ColorRBG::WHITE);
+        buffer_2.add_sample(0, 0, ColorRBG::BLACK);
+        buffer_1.merge(&buffer_2);
+
+        assert_eq!(buffer_1.get(0, 0).samples, 2);
+        assert_eq!(buffer_1.get(0, 0).mean(), ColorRBG::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn heatmap() {
+        let mut buffer = AccBuffer::new(2, 1);
+        buffer.add_sample(0, 0, ColorRBG::WHITE);
+        for _ in 0..3 {
+            buffer.add_sample(1, 0, ColorRBG::WHITE);
+        }
+        let heatmap = buffer.sample_heatmap();
+
+        assert_eq!(heatmap.get_pixel(0, 0), ColorRBG::BLUE.rgb());
+        assert_eq!(heatmap.get_pixel(1, 0), ColorRBG::RED.rgb());
+    }
+}
@@ -0,0 +1,418 @@
+use std::{
+    error::Error,
+    fs,
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use super::{
+    color::ColorRBG,
+    image::ImageRGB,
+    material::Material,
+    mesh::{read_obj, MeshFormat},
+    objs::{create_cube_triangles, Camera, Plane, Sphere, Triangle, TriangleBlock, TriangleGroup},
+    position::{Angle, Quat, Vect3},
+    render::Scene,
+};
+
+/// Builder of a Material, starting from the default material
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MaterialBuilder {
+    material: Material,
+}
+
+impl MaterialBuilder {
+    /// New MaterialBuilder constructor
+    pub fn new() -> MaterialBuilder {
+        MaterialBuilder::default()
+    }
+
+    /// Set diffuse color
+    pub fn color(mut self, color: ColorRBG) -> Self {
+        self.material.color = color;
+        self
+    }
+
+    /// Set emitted light color and strength
+    pub fn emission(mut self, color: ColorRBG, strength: f64) -> Self {
+        self.material.emission_color = color;
+        self.material.emission_strengh = strength;
+        self
+    }
+
+    /// Set specular color and probability of a ray bouncing specularly
+    pub fn specular(mut self, color: ColorRBG, probability: f64) -> Self {
+        self.material.specular_color = color;
+        self.material.specular_prob = probability;
+        self
+    }
+
+    /// Set smoothness of specular bounces (0.0: diffuse, 1.0: mirror)
+    pub fn smoothness(mut self, smoothness: f64) -> Self {
+        self.material.smoothness = smoothness;
+        self
+    }
+
+    /// Build Material
+    pub fn build(self) -> Material {
+        self.material
+    }
+}
+
+/// Handle of a material added to a SceneBuilder
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaterialHandle {
+    builder: usize,
+    index: usize,
+}
+
+/// Handle of a sphere added to a SceneBuilder
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SphereHandle {
+    builder: usize,
+    index: usize,
+}
+
+/// Handle of a plane added to a SceneBuilder
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlaneHandle {
+    builder: usize,
+    index: usize,
+}
+
+/// Handle of triangles (triangle, cube or mesh) added to a SceneBuilder
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrianglesHandle {
+    builder: usize,
+    triangles: Range<usize>,
+}
+
+/// Identifier of the next SceneBuilder, so that handles are only used with their builder
+static NEXT_BUILDER: AtomicUsize = AtomicUsize::new(0);
+
+/// Builder of a Scene, without .rtp file
+pub struct SceneBuilder {
+    id: usize,
+    camera: Camera,
+    materials: Vec<Material>,
+    spheres: Vec<Sphere>,
+    planes: Vec<Plane>,
+    triangles: Vec<Triangle>,
+    triangle_groups: Vec<TriangleGroup>,
+}
+
+impl SceneBuilder {
+    /// New SceneBuilder constructor, with a camera at the origin (focal length 5, 30° fov)
+    pub fn new() -> SceneBuilder {
+        SceneBuilder {
+            id: NEXT_BUILDER.fetch_add(1, Ordering::Relaxed),
+            camera: Camera::new(
+                Vect3::ZERO,
+                Quat::identity(),
+                5.0,
+                Angle::from_deg(30.0),
+                ImageRGB::new(1, 1),
+            ),
+            materials: vec![],
+            spheres: vec![],
+            planes: vec![],
+            triangles: vec![],
+            triangle_groups: vec![],
+        }
+    }
+
+    /// Set camera (its resolution is given when rendering)
+    pub fn camera(&mut self, position: Vect3, rotation: Quat, focal: f64, fov: Angle) -> &mut Self {
+        self.camera = Camera::new(position, rotation, focal, fov, ImageRGB::new(1, 1));
+        self
+    }
+
+    /// Get camera, to set its motion or shutter
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    /// Checks that a handle comes from this builder, and that its objects (up to end) exist
+    fn check(
+        &self,
+        builder: usize,
+        end: usize,
+        count: usize,
+        kind: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        if builder != self.id || end > count {
+            return Err(format!("{} handle from another SceneBuilder", kind).into());
+        }
+        Ok(())
+    }
+
+    /// Adds a material
+    pub fn add_material(&mut self, material: Material) -> MaterialHandle {
+        self.materials.push(material);
+        MaterialHandle {
+            builder: self.id,
+            index: self.materials.len() - 1,
+        }
+    }
+
+    /// Get a material
+    pub fn material(&self, handle: MaterialHandle) -> Result<&Material, Box<dyn Error>> {
+        self.check(
+            handle.builder,
+            handle.index + 1,
+            self.materials.len(),
+            "material",
+        )?;
+        Ok(&self.materials[handle.index])
+    }
+
+    /// Adds a sphere
+    pub fn add_sphere(
+        &mut self,
+        position: Vect3,
+        radius: f64,
+        material: MaterialHandle,
+    ) -> Result<SphereHandle, Box<dyn Error>> {
+        let material = *self.material(material)?;
+        self.spheres.push(Sphere::new(position, radius, material));
+        Ok(SphereHandle {
+            builder: self.id,
+            index: self.spheres.len() - 1,
+        })
+    }
+
+    /// Get a sphere, to move it or change its material
+    pub fn sphere_mut(&mut self, handle: SphereHandle) -> Result<&mut Sphere, Box<dyn Error>> {
+        self.check(
+            handle.builder,
+            handle.index + 1,
+            self.spheres.len(),
+            "sphere",
+        )?;
+        Ok(&mut self.spheres[handle.index])
+    }
+
+    /// Adds an infinite plane
+    pub fn add_plane(
+        &mut self,
+        point: Vect3,
+        normal: Vect3,
+        material: MaterialHandle,
+    ) -> Result<PlaneHandle, Box<dyn Error>> {
+        let material = *self.material(material)?;
+        self.planes.push(Plane::new(point, normal, material));
+        Ok(PlaneHandle {
+            builder: self.id,
+            index: self.planes.len() - 1,
+        })
+    }
+
+    /// Get a plane, to change its material
+    pub fn plane_mut(&mut self, handle: PlaneHandle) -> Result<&mut Plane, Box<dyn Error>> {
+        self.check(handle.builder, handle.index + 1, self.planes.len(), "plane")?;
+        Ok(&mut self.planes[handle.index])
+    }
+
+    /// Adds a triangle
+    pub fn add_triangle(
+        &mut self,
+        points: (Vect3, Vect3, Vect3),
+        material: MaterialHandle,
+    ) -> Result<TrianglesHandle, Box<dyn Error>> {
+        let material = *self.material(material)?;
+        Ok(self.add_triangles(vec![Triangle::new(points.0, points.1, points.2, material)]))
+    }
+
+    /// Adds a cube
+    pub fn add_cube(
+        &mut self,
+        position: Vect3,
+        rotation: Quat,
+        size: f64,
+        material: MaterialHandle,
+    ) -> Result<TrianglesHandle, Box<dyn Error>> {
+        let material = *self.material(material)?;
+        let handle = self.add_triangles(create_cube_triangles(position, rotation, size, material));
+        self.add_group(
+            TriangleBlock::Cube {
+                position,
+                rotation,
+                size,
+            },
+            material,
+            &handle,
+        );
+        Ok(handle)
+    }
+
+    /// Adds the triangles of an .obj file, moved and rotated like an .rtp mesh
+    pub fn add_obj_file(
+        &mut self,
+        obj_file: &str,
+        position: Vect3,
+        rotation: Quat,
+        material: MaterialHandle,
+    ) -> Result<TrianglesHandle, Box<dyn Error>> {
+        let material = *self.material(material)?;
+        let mut triangles = read_obj(&fs::read_to_string(obj_file)?, obj_file)?.triangles(&[], &[]);
+        for triangle in triangles.iter_mut() {
+            triangle.rotate(rotation, position);
+            triangle.set_material(material);
+        }
+        let handle = self.add_triangles(triangles);
+        self.add_group(
+            TriangleBlock::Mesh {
+                file: obj_file.to_string(),
+                format: MeshFormat::Obj,
+                scale: 1.0,
+                weld: None,
+                objects: vec![],
+                groups: vec![],
+                position,
+                rotation,
+                motion: None,
+            },
+            material,
+            &handle,
+        );
+        Ok(handle)
+    }
+
+    /// Adds triangles
+    pub fn add_triangles(&mut self, triangles: Vec<Triangle>) -> TrianglesHandle {
+        let start = self.triangles.len();
+        self.triangles.extend(triangles);
+        TrianglesHandle {
+            builder: self.id,
+            triangles: start..self.triangles.len(),
+        }
+    }
+
+    /// Records the block that created triangles, so that the scene can be written back
+    fn add_group(&mut self, block: TriangleBlock, material: Material, handle: &TrianglesHandle) {
+        self.triangle_groups.push(TriangleGroup {
+            block,
+            material: Some(material),
+            triangles: handle.triangles.clone(),
+        });
+    }
+
+    /// Get triangles, to change their material or motion
+    pub fn triangles_mut(
+        &mut self,
+        handle: &TrianglesHandle,
+    ) -> Result<&mut [Triangle], Box<dyn Error>> {
+        let end = handle.triangles.end;
+        self.check(handle.builder, end, self.triangles.len(), "triangles")?;
+        Ok(&mut self.triangles[handle.triangles.clone()])
+    }
+
+    /// Build Scene
+    pub fn build(self) -> Scene {
+        let mut scene = Scene::new(self.camera, self.spheres, self.planes, self.triangles);
+        scene.triangle_groups = self.triangle_groups;
+        scene
+    }
+}
+
+impl Default for SceneBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests_builder {
+    use crate::mods::{
+        color::ColorRBG,
+        position::{Quat, Vect3},
+    };
+
+    use super::{MaterialBuilder, SceneBuilder};
+
+    #[test]
+    fn material() {
+        let material = MaterialBuilder::new()
+            .color(ColorRBG::RED)
+            .emission(ColorRBG::WHITE, 2.0)
+            .smoothness(1.0)
+            .build();
+
+        assert_eq!(material.color, ColorRBG::RED);
+        assert_eq!(material.emission_strengh, 2.0);
+        assert_eq!(material.smoothness, 1.0);
+        assert_eq!(material.specular_prob, 0.5);
+    }
+
+    #[test]
+    fn handles() {
+        let mut builder = SceneBuilder::new();
+        let red = builder.add_material(MaterialBuilder::new().color(ColorRBG::RED).build());
+        let blue = builder.add_material(MaterialBuilder::new().color(ColorRBG::BLUE).build());
+        let sphere = builder.add_sphere(Vect3::ZERO, 1.0, red).unwrap();
+        let cube = builder
+            .add_cube(Vect3::ZERO, Quat::identity(), 1.0, red)
+            .unwrap();
+        let triangle = builder
+            .add_triangle((Vect3::ZERO, Vect3::UP, Vect3::RIGHT), red)
+            .unwrap();
+
+        builder.sphere_mut(sphere).unwrap().radius = 2.0;
+        let material = *builder.material(blue).unwrap();
+        builder.sphere_mut(sphere).unwrap().set_material(material);
+        assert_eq!(builder.triangles_mut(&cube).unwrap().len(), 12);
+        assert_eq!(builder.triangles_mut(&triangle).unwrap().len(), 1);
+
+        let scene = builder.build();
+        assert_eq!(scene.spheres[0].radius, 2.0);
+        assert_eq!(scene.spheres[0].get_mat().color, ColorRBG::BLUE);
+        assert_eq!(scene.triangles.len(), 13);
+    }
+
+    #[test]
+    fn foreign_handles() {
+        let mut builder = SceneBuilder::new();
+        let mut other = SceneBuilder::new();
+        let red = builder.add_material(MaterialBuilder::new().color(ColorRBG::RED).build());
+        let other_red = other.add_material(MaterialBuilder::new().color(ColorRBG::RED).build());
+        let other_sphere = other.add_sphere(Vect3::ZERO, 1.0, other_red).unwrap();
+        builder.add_sphere(Vect3::ZERO, 1.0, red).unwrap();
+        let triangles = builder
+            .add_triangle((Vect3::ZERO, Vect3::UP, Vect3::RIGHT), red)
+            .unwrap();
+
+        assert_eq!(
+            builder
+                .add_sphere(Vect3::ZERO, 1.0, other_red)
+                .unwrap_err()
+                .to_string(),
+            "material handle from another SceneBuilder"
+        );
+        assert_eq!(
+            builder.sphere_mut(other_sphere).err().unwrap().to_string(),
+            "sphere handle from another SceneBuilder"
+        );
+        assert!(other.triangles_mut(&triangles).is_err());
+        assert_eq!(builder.build().spheres.len(), 1);
+    }
+
+    #[test]
+    fn render_in_memory() {
+        let mut builder = SceneBuilder::new();
+        let light = builder.add_material(
+            MaterialBuilder::new()
+                .emission(ColorRBG::WHITE, 1.0)
+                .build(),
+        );
+        builder
+            .add_sphere(10.0 * Vect3::FORWARD, 3.0, light)
+            .unwrap();
+        let mut scene = builder.build();
+        let image = crate::render(&mut scene, 2, 2, (9, 9));
+
+        assert_eq!(image.get_width(), 9);
+        assert_eq!(image.get_height(), 9);
+        assert_ne!(image.get_pixel(4, 4), (0, 0, 0));
+        assert_eq!(image.get_pixel(0, 0), (0, 0, 0));
+    }
+}
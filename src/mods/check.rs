@@ -0,0 +1,229 @@
+use std::{fs, sync::Arc};
+
+use super::{
+    color::ColorRBG,
+    objs::{Sphere, Triangle},
+    parse_error::ParseError,
+    parser::Parser,
+    position::Vect3,
+    ray::Ray,
+    render::Scene,
+    report::QuietReporter,
+};
+
+/// Directions of the rays cast from the camera to find if it is inside a closed mesh
+const INSIDE_RAYS: [(f64, f64, f64); 3] = [
+    (0.577, 0.613, 0.539),
+    (-0.421, 0.248, -0.872),
+    (0.302, -0.941, 0.152),
+];
+
+/// Problems found in a scene file
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckReport {
+    pub errors: Vec<ParseError>,
+    pub warnings: Vec<ParseError>,
+}
+
+impl CheckReport {
+    /// Exit code of the check: 0 without problems, 1 with warnings only, 2 with errors
+    pub fn exit_code(&self) -> i32 {
+        if !self.errors.is_empty() {
+            2
+        } else if !self.warnings.is_empty() {
+            1
+        } else {
+            0
+        }
+    }
+}
+
+/// Checks a scene file without rendering it
+pub fn check_scene_file(scene_file: &str) -> CheckReport {
+    match fs::read_to_string(scene_file) {
+        Ok(source) => check_scene_source(&source, scene_file),
+        Err(e) => CheckReport {
+            errors: vec![ParseError::new(scene_file, 0, 0, e.to_string())],
+            warnings: vec![],
+        },
+    }
+}
+
+/// Checks a scene, named scene_file in the problems found
+pub fn check_scene_source(source: &str, scene_file: &str) -> CheckReport {
+    let mut parser = Parser::new(source, scene_file);
+    parser.set_reporter(Arc::new(QuietReporter));
+    parser.set_lint(true);
+
+    match parser.parse_scene() {
+        Ok(scene) => {
+            let mut warnings = parser.get_warnings().to_vec();
+            warnings.extend(
+                check_scene(&scene)
+                    .into_iter()
+                    .map(|message| ParseError::new(scene_file, 0, 0, message)),
+            );
+            CheckReport {
+                errors: vec![],
+                warnings,
+            }
+        }
+        Err(errors) => CheckReport {
+            errors: errors.0,
+            warnings: parser.get_warnings().to_vec(),
+        },
+    }
+}
+
+/// Problems of a parsed scene as a whole
+pub fn check_scene(scene: &Scene) -> Vec<String> {
+    let mut problems = vec![];
+    let camera = scene.camera.transform.get_pos();
+
+    if let Some(sphere) = scene.spheres.iter().find(|s| inside_sphere(camera, s)) {
+        let center = sphere.transform.get_pos();
+        problems.push(format!(
+            "camera is inside the sphere at {} {} {}",
+            center.x(),
+            center.y(),
+            center.z()
+        ));
+    }
+    if inside_triangles(camera, &scene.triangles) {
+        problems.push(String::from("camera is inside a cube or a closed mesh"));
+    }
+
+    let emissive = scene
+        .spheres
+        .iter()
+        .map(|s| s.get_mat())
+        .chain(scene.planes.iter().map(|p| p.get_mat()))
+        .chain(scene.triangles.iter().map(|t| t.get_mat()))
+        .any(|m| m.get_emited_light() != ColorRBG::BLACK);
+    if !emissive {
+        problems.push(String::from("no emissive surface, the image will be black"));
+    }
+    problems
+}
+
+/// Whether a point is inside a sphere (at the start of the shutter)
+fn inside_sphere(point: Vect3, sphere: &Sphere) -> bool {
+    (point - sphere.position_at(0.0)).norm() < sphere.radius
+}
+
+/// Whether a point is inside closed triangle meshes: rays cast from it
+/// then cross their triangles an odd number of times
+fn inside_triangles(point: Vect3, triangles: &[Triangle]) -> bool {
+    INSIDE_RAYS.iter().all(|&(x, y, z)| {
+        let ray = Ray::new(point, Vect3::new(x, y, z).normalize());
+        let crossings = triangles
+            .iter()
+            .filter(|t| t.intersect(&ray, f64::INFINITY).is_some())
+            .count();
+        crossings % 2 == 1
+    })
+}
+
+#[cfg(test)]
+mod tests_check {
+    use super::{check_scene_file, check_scene_source, CheckReport};
+
+    static CAMERA: &str = "camera {
+    position: 0.0 0.0 10.0
+    rotation: 1.0 0.0 0.0 0.0
+    focal_length: 5.0
+    fov: 40.0
+}
+material {
+    name: light
+    emission_color: 1.0 1.0 1.0
+    emission_strength: 1.0
+}
+sphere {
+    position: 0.0 5.0 0.0
+    radius: 1.0
+    mat: light
+}
+";
+
+    fn check(source: &str) -> CheckReport {
+        check_scene_source(&format!("{}{}", CAMERA, source), "scene.rtp")
+    }
+
+    fn warnings(report: &CheckReport) -> Vec<String> {
+        report.warnings.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn clean_scene() {
+        let report = check("plane {\n    normal: 0.0 1.0 0.0\n}\n");
+
+        assert_eq!(report.exit_code(), 0);
+    }
+
+    #[test]
+    fn objects() {
+        let report = check(
+            "sphere {\n    radius: 0.0\n}\nplane {\n    normal: 0.0 0.0 0.0\n}\ntriangle {\n    point_2: 1.0 1.0 1.0\n    point_3: 2.0 2.0 2.0\n}\ncube {\n    rotation: 0.5 0.0 0.0 0.0\n    size: 1.0\n}\n",
+        );
+
+        assert_eq!(report.exit_code(), 1);
+        assert_eq!(
+            warnings(&report),
+            vec![
+                "scene.rtp:17:1: sphere radius is 0",
+                "scene.rtp:20:1: plane normal has a zero length",
+                "scene.rtp:23:1: degenerate triangle (its points are aligned)",
+                "scene.rtp:28:15: rotation has a norm of 0.500 instead of 1, so it also scales",
+            ]
+        );
+    }
+
+    #[test]
+    fn camera_inside() {
+        let report = check(
+            "sphere {\n    position: 0.0 0.0 9.0\n    radius: 2.0\n}\ncube {\n    position: -1.0 -1.0 9.0\n    size: 2.0\n}\n",
+        );
+
+        assert_eq!(
+            warnings(&report),
+            vec![
+                "scene.rtp: camera is inside the sphere at 0 0 9",
+                "scene.rtp: camera is inside a cube or a closed mesh",
+            ]
+        );
+    }
+
+    #[test]
+    fn camera_outside_cube() {
+        let report = check("cube {\n    position: -1.0 -1.0 -1.0\n    size: 2.0\n}\n");
+
+        assert_eq!(report.exit_code(), 0);
+    }
+
+    #[test]
+    fn no_light() {
+        let report = check_scene_source(
+            "camera {\n    fov: 40.0\n}\nsphere {\n    position: 0.0 0.0 -5.0\n    radius: 1.0\n}\n",
+            "dark.rtp",
+        );
+
+        assert_eq!(
+            warnings(&report),
+            vec!["dark.rtp: no emissive surface, the image will be black"]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let report = check("mesh {\n    obj_file: nowhere.obj\n}\n");
+        assert_eq!(report.exit_code(), 2);
+        assert!(report.errors[0]
+            .message
+            .starts_with("cannot read mesh file"));
+
+        let report = check_scene_file("nowhere.rtp");
+        assert_eq!(report.exit_code(), 2);
+        assert_eq!(report.errors[0].line, 0);
+    }
+}
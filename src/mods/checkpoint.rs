@@ -0,0 +1,265 @@
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{prelude::*, BufReader, BufWriter},
+};
+
+use super::{
+    buffer::{AccBuffer, PixelStats},
+    color::ColorRBGOF,
+};
+
+/// Checkpoint file signature (format version 2: iteration range)
+static MAGIC: &[u8; 8] = b"RBPTCKP2";
+
+/// Size of a pixel in a checkpoint (color, luminance, squared luminance and sample count)
+const PIXEL_SIZE: usize = 5 * 8 + 4;
+
+/// Default number of iterations between two checkpoints
+pub static DEFAULT_CHECKPOINT_EVERY: usize = 10;
+
+/// Render state saved with the accumulation buffer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CheckpointInfo {
+    pub scene_hash: u64,
+    pub seed: u64,
+    pub max_bounces: u32,
+    pub first_iteration: usize,
+    pub next_iteration: usize,
+}
+
+impl CheckpointInfo {
+    /// Do the iterations of two renders overlap (giving the same samples)
+    pub fn overlaps(&self, other: &CheckpointInfo) -> bool {
+        self.first_iteration < other.next_iteration && other.first_iteration < self.next_iteration
+    }
+}
+
+/// Periodic checkpoint settings of a render
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub filename: String,
+    pub every: usize,
+    pub scene_hash: u64,
+}
+
+impl Checkpoint {
+    /// New Checkpoint constructor
+    pub fn new(filename: &str, every: usize, scene_hash: u64) -> Checkpoint {
+        Checkpoint {
+            filename: filename.to_string(),
+            every: every.max(1),
+            scene_hash,
+        }
+    }
+}
+
+/// Saves render state to a checkpoint file (written to a temporary file then renamed)
+pub fn save_checkpoint(
+    filename: &str,
+    info: &CheckpointInfo,
+    acc_buffer: &AccBuffer,
+) -> Result<(), Box<dyn Error>> {
+    let tmp_filename = format!("{}.tmp", filename);
+    let file = File::create(&tmp_filename)?;
+    let mut writer = BufWriter::new(file);
+    write_checkpoint(&mut writer, info, acc_buffer)?;
+
+    writer.into_inner()?.sync_all()?;
+    fs::rename(tmp_filename, filename)?;
+    Ok(())
+}
+
+/// Writes render state in the checkpoint format
+pub fn write_checkpoint(
+    writer: &mut impl Write,
+    info: &CheckpointInfo,
+    acc_buffer: &AccBuffer,
+) -> Result<(), Box<dyn Error>> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&info.scene_hash.to_le_bytes())?;
+    writer.write_all(&info.seed.to_le_bytes())?;
+    writer.write_all(&info.max_bounces.to_le_bytes())?;
+    writer.write_all(&(info.first_iteration as u64).to_le_bytes())?;
+    writer.write_all(&(info.next_iteration as u64).to_le_bytes())?;
+    writer.write_all(&(acc_buffer.get_width() as u64).to_le_bytes())?;
+    writer.write_all(&(acc_buffer.get_height() as u64).to_le_bytes())?;
+    for pixel in acc_buffer.pixels() {
+        let (r, g, b) = pixel.color.get_value();
+        for value in [r, g, b, pixel.lum, pixel.lum_sq] {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        writer.write_all(&pixel.samples.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// Loads render state from a checkpoint file
+pub fn load_checkpoint(filename: &str) -> Result<(CheckpointInfo, AccBuffer), Box<dyn Error>> {
+    let mut reader = BufReader::new(File::open(filename)?);
+    read_checkpoint(&mut reader).map_err(|e| format!("{} : {}", filename, e).into())
+}
+
+/// Reads render state in the checkpoint format
+pub fn read_checkpoint(
+    reader: &mut impl Read,
+) -> Result<(CheckpointInfo, AccBuffer), Box<dyn Error>> {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err("not a checkpoint".into());
+    }
+
+    let info = CheckpointInfo {
+        scene_hash: read_u64(reader)?,
+        seed: read_u64(reader)?,
+        max_bounces: read_u32(reader)?,
+        first_iteration: read_u64(reader)? as usize,
+        next_iteration: read_u64(reader)? as usize,
+    };
+    let width = read_u64(reader)? as usize;
+    let height = read_u64(reader)? as usize;
+
+    // The pixels are read before being allocated, so that a corrupted size is an error
+    let size = width
+        .checked_mul(height)
+        .and_then(|count| count.checked_mul(PIXEL_SIZE))
+        .ok_or("corrupted checkpoint")?;
+    let mut bytes = vec![];
+    reader.take(size as u64 + 1).read_to_end(&mut bytes)?;
+    if bytes.len() != size {
+        return Err(format!(
+            "corrupted checkpoint ({}x{} pixels need {} bytes)",
+            width, height, size
+        )
+        .into());
+    }
+
+    let mut pixels = Vec::with_capacity(width * height);
+    for mut pixel in bytes.chunks_exact(PIXEL_SIZE) {
+        let reader = &mut pixel;
+        let (r, g, b) = (read_f64(reader)?, read_f64(reader)?, read_f64(reader)?);
+        pixels.push(PixelStats {
+            color: ColorRBGOF::new(r, g, b),
+            lum: read_f64(reader)?,
+            lum_sq: read_f64(reader)?,
+            samples: read_u32(reader)?,
+        });
+    }
+    let acc_buffer = AccBuffer::from_pixels(width, height, pixels).ok_or("corrupted checkpoint")?;
+
+    Ok((info, acc_buffer))
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32, Box<dyn Error>> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> Result<u64, Box<dyn Error>> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_f64(reader: &mut impl Read) -> Result<f64, Box<dyn Error>> {
+    Ok(f64::from_bits(read_u64(reader)?))
+}
+
+#[cfg(test)]
+mod tests_checkpoint {
+    use crate::mods::{buffer::AccBuffer, color::ColorRBG};
+
+    use super::{
+        load_checkpoint, read_checkpoint, save_checkpoint, write_checkpoint, CheckpointInfo,
+    };
+
+    #[test]
+    fn round_trip() {
+        let path = std::env::temp_dir().join("tests_checkpoint_round_trip.bin");
+        let filename = path.to_str().unwrap();
+        let info = CheckpointInfo {
+            scene_hash: 0xDEADBEEF,
+            seed: 42,
+            max_bounces: 5,
+            first_iteration: 1,
+            next_iteration: 3,
+        };
+        let mut buffer = AccBuffer::new(2, 3);
+        buffer.add_sample(1, 2, ColorRBG::new(0.1, 0.2, 0.3));
+        buffer.add_sample(1, 2, ColorRBG::WHITE);
+
+        save_checkpoint(filename, &info, &buffer).unwrap();
+        let (loaded_info, loaded_buffer) = load_checkpoint(filename).unwrap();
+        std::fs::remove_file(filename).unwrap();
+
+        assert_eq!(loaded_info, info);
+        assert_eq!(loaded_buffer.get_width(), 2);
+        assert_eq!(loaded_buffer.get_height(), 3);
+        assert_eq!(loaded_buffer.get(1, 2).samples, 2);
+        assert_eq!(loaded_buffer.get(1, 2).lum, buffer.get(1, 2).lum);
+        assert_eq!(loaded_buffer.get(1, 2).mean(), buffer.get(1, 2).mean());
+    }
+
+    #[test]
+    fn overlap() {
+        let range = |first_iteration, next_iteration| CheckpointInfo {
+            scene_hash: 0,
+            seed: 0,
+            max_bounces: 0,
+            first_iteration,
+            next_iteration,
+        };
+
+        assert!(!range(0, 10).overlaps(&range(10, 20)));
+        assert!(range(0, 11).overlaps(&range(10, 20)));
+        assert!(range(12, 15).overlaps(&range(10, 20)));
+        assert!(!range(20, 30).overlaps(&range(10, 20)));
+    }
+
+    #[test]
+    fn not_a_checkpoint() {
+        let path = std::env::temp_dir().join("tests_checkpoint_invalid.bin");
+        let filename = path.to_str().unwrap();
+        std::fs::write(filename, "P3\n1 1\n255\n0 0 0\n").unwrap();
+        let result = load_checkpoint(filename);
+        std::fs::remove_file(filename).unwrap();
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn wrong_size() {
+        let info = CheckpointInfo {
+            scene_hash: 0,
+            seed: 0,
+            max_bounces: 0,
+            first_iteration: 0,
+            next_iteration: 1,
+        };
+        let mut bytes = vec![];
+        write_checkpoint(&mut bytes, &info, &AccBuffer::new(2, 3)).unwrap();
+        let error = |bytes: &[u8]| read_checkpoint(&mut &bytes[..]).unwrap_err().to_string();
+
+        // Width and height follow the 44 bytes of the signature and render state
+        let mut huge = bytes.clone();
+        huge[44..60].copy_from_slice(&[0xFF; 16]);
+        assert_eq!(error(&huge), "corrupted checkpoint");
+        let mut wide = bytes.clone();
+        wide[44..52].copy_from_slice(&(1u64 << 40).to_le_bytes());
+        assert_eq!(
+            error(&wide),
+            "corrupted checkpoint (1099511627776x3 pixels need 145135534866432 bytes)"
+        );
+        assert_eq!(
+            error(&bytes[..bytes.len() - 1]),
+            "corrupted checkpoint (2x3 pixels need 264 bytes)"
+        );
+        bytes.push(0);
+        assert_eq!(
+            error(&bytes),
+            "corrupted checkpoint (2x3 pixels need 264 bytes)"
+        );
+    }
+}
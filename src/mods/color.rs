@@ -30,6 +30,12 @@ impl ColorRBG {
         )
     }
 
+    /// Get relative luminance (Rec. 709)
+    #[inline]
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
     /// Get RGB max value (0.0 to 1.0)
     #[inline]
     pub fn max_component(&self) -> f64 {
@@ -155,6 +161,17 @@ pub struct ColorRBGOF {
 }
 
 impl ColorRBGOF {
+    /// RGB Color with overflow constructor
+    pub fn new(r: f64, g: f64, b: f64) -> ColorRBGOF {
+        ColorRBGOF { r, g, b }
+    }
+
+    /// Get RGB value (not clamped)
+    #[inline]
+    pub fn get_value(&self) -> (f64, f64, f64) {
+        (self.r, self.g, self.b)
+    }
+
     /// RGBOF BLACK
     pub const BLACK: Self = Self {
         r: 0.0,
@@ -1,5 +1,15 @@
 use clap::ArgMatches;
-use std::str::FromStr;
+use std::{
+    ops::{Range, RangeInclusive},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use super::{
+    funcs::parse_duration,
+    report::{ConsoleReporter, JsonReporter, QuietReporter, Reporter},
+};
 
 /// Structure for quality choice
 pub struct RenderQuality {
@@ -56,6 +66,70 @@ impl RenderResolution {
     }
 }
 
+/// When to write the current image during a progressive render
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProgressiveMode {
+    EachIteration,
+    Every(Duration),
+}
+
+/// Adaptive sampling: after the warm-up, only pixels with a high relative error are sampled
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveSampling {
+    pub warmup: usize,
+    pub threshold: f64,
+}
+
+impl AdaptiveSampling {
+    pub const DEFAULT_WARMUP: usize = 16;
+    pub const DEFAULT_THRESHOLD: f64 = 0.05;
+}
+
+/// How the render is reported on the console
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum OutputMode {
+    #[default]
+    Pretty,
+    Quiet,
+    Json,
+}
+
+impl OutputMode {
+    /// Get the reporter of this output mode
+    pub fn reporter(&self) -> Arc<dyn Reporter> {
+        match self {
+            OutputMode::Pretty => Arc::new(ConsoleReporter::new()),
+            OutputMode::Quiet => Arc::new(QuietReporter),
+            OutputMode::Json => Arc::new(JsonReporter::new()),
+        }
+    }
+}
+
+/// Format of the scene statistics
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatsFormat {
+    Text,
+    Json,
+}
+
+/// Optional render settings
+#[derive(Debug, Clone, Default)]
+pub struct RenderOptions {
+    pub progressive: Option<ProgressiveMode>,
+    pub time_limit: Option<Duration>,
+    pub target_noise: Option<f64>,
+    pub adaptive: Option<AdaptiveSampling>,
+    pub sample_heatmap: Option<String>,
+    pub checkpoint: Option<String>,
+    pub checkpoint_every: Option<usize>,
+    pub resume: Option<String>,
+    pub iterations_range: Option<Range<usize>>,
+    pub tile_size: Option<usize>,
+    pub output: OutputMode,
+    pub lenient: bool,
+    pub stats: Option<StatsFormat>,
+}
+
 pub fn parse_quality(matches: &ArgMatches) -> (usize, u32) {
     let mut quality = RenderQuality::MEDIUM;
 
@@ -141,3 +215,153 @@ pub fn parse_resolution(matches: &ArgMatches) -> (u32, u32) {
 
     resolution.rs()
 }
+
+pub fn parse_frames(matches: &ArgMatches) -> Option<RangeInclusive<u32>> {
+    let frames = matches.get_one::<String>("frames")?;
+
+    let range = match frames.split_once("..") {
+        Some((start, end)) => (u32::from_str(start), u32::from_str(end)),
+        None => (u32::from_str(frames), u32::from_str(frames)),
+    };
+
+    match range {
+        (Ok(start), Ok(end)) if start <= end => Some(start..=end),
+        _ => {
+            eprintln!("wrong frames argument");
+            std::process::exit(1);
+        }
+    }
+}
+
+pub fn parse_workers(matches: &ArgMatches) -> Option<Vec<String>> {
+    let workers: Vec<String> = matches.get_many::<String>("workers")?.cloned().collect();
+    if workers.iter().any(|w| !w.contains(':')) {
+        eprintln!("wrong workers argument");
+        std::process::exit(1);
+    }
+    Some(workers)
+}
+
+/// Get the output mode of a command (--quiet or --json-progress)
+pub fn parse_output(matches: &ArgMatches) -> OutputMode {
+    if matches.get_flag("json_progress") {
+        OutputMode::Json
+    } else if matches.get_flag("quiet") {
+        OutputMode::Quiet
+    } else {
+        OutputMode::Pretty
+    }
+}
+
+pub fn parse_options(matches: &ArgMatches) -> RenderOptions {
+    let mut options = RenderOptions::default();
+
+    if let Some(mut values) = matches.get_many::<String>("progressive") {
+        options.progressive = match values.next() {
+            None => Some(ProgressiveMode::EachIteration),
+            Some(interval) => match parse_duration(interval) {
+                Some(interval) => Some(ProgressiveMode::Every(interval)),
+                None => {
+                    eprintln!("wrong progressive argument");
+                    std::process::exit(1);
+                }
+            },
+        };
+    }
+
+    if let Some(time_limit) = matches.get_one::<String>("time_limit") {
+        match parse_duration(time_limit) {
+            Some(time_limit) => options.time_limit = Some(time_limit),
+            None => {
+                eprintln!("wrong time limit argument");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(target_noise) = matches.get_one::<String>("target_noise") {
+        match f64::from_str(target_noise) {
+            Ok(target_noise) if target_noise > 0.0 => options.target_noise = Some(target_noise),
+            _ => {
+                eprintln!("wrong target noise argument");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(mut values) = matches.get_many::<String>("adaptive") {
+        let threshold = match values.next().map(|t| f64::from_str(t)) {
+            None => AdaptiveSampling::DEFAULT_THRESHOLD,
+            Some(Ok(threshold)) if threshold > 0.0 => threshold,
+            _ => {
+                eprintln!("wrong adaptive argument");
+                std::process::exit(1);
+            }
+        };
+        let warmup = match matches.get_one::<String>("adaptive_warmup") {
+            None => AdaptiveSampling::DEFAULT_WARMUP,
+            Some(warmup) => match usize::from_str(warmup) {
+                Ok(warmup) if warmup >= 2 => warmup,
+                _ => {
+                    eprintln!("wrong adaptive warmup argument");
+                    std::process::exit(1);
+                }
+            },
+        };
+        options.adaptive = Some(AdaptiveSampling { warmup, threshold });
+    }
+
+    options.sample_heatmap = matches.get_one::<String>("sample_heatmap").cloned();
+
+    if let Some(range) = matches.get_one::<String>("iterations_range") {
+        let range = range
+            .split_once("..")
+            .map(|(start, end)| (usize::from_str(start), usize::from_str(end)));
+        match range {
+            Some((Ok(start), Ok(end))) if start < end => {
+                options.iterations_range = Some(start..end)
+            }
+            _ => {
+                eprintln!("wrong iterations range argument");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(tile_size) = matches.get_one::<String>("tile_size") {
+        match usize::from_str(tile_size) {
+            Ok(tile_size) if tile_size > 0 => options.tile_size = Some(tile_size),
+            _ => {
+                eprintln!("wrong tile size argument");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    options.output = parse_output(matches);
+
+    options.lenient = matches.get_flag("lenient");
+    options.stats = match matches.get_one::<String>("stats").map(String::as_str) {
+        None => None,
+        Some("text") => Some(StatsFormat::Text),
+        Some("json") => Some(StatsFormat::Json),
+        Some(_) => {
+            eprintln!("wrong stats argument (text or json)");
+            std::process::exit(1);
+        }
+    };
+
+    options.checkpoint = matches.get_one::<String>("checkpoint").cloned();
+    options.resume = matches.get_one::<String>("resume").cloned();
+    if let Some(every) = matches.get_one::<String>("checkpoint_every") {
+        match usize::from_str(every) {
+            Ok(every) if every > 0 => options.checkpoint_every = Some(every),
+            _ => {
+                eprintln!("wrong checkpoint interval argument");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    options
+}
@@ -0,0 +1,520 @@
+use std::path::Path;
+
+use serde_json::Value;
+
+use super::{funcs::closest_name, lexer::Token};
+
+/// Scene description format other than .rtp, chosen by the file extension
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DocumentFormat {
+    Json,
+    Toml,
+}
+
+impl DocumentFormat {
+    /// Format of a scene file (None for .rtp files)
+    pub fn from_file_name(file_name: &str) -> Option<DocumentFormat> {
+        match Path::new(file_name).extension()?.to_str()? {
+            "json" => Some(DocumentFormat::Json),
+            "toml" => Some(DocumentFormat::Toml),
+            _ => None,
+        }
+    }
+}
+
+/// Expected type of a property
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Number,
+    Numbers(usize),
+    Name,
+    /// One or more names
+    Names,
+}
+
+/// Scene description key, with the .rtp block its objects map to and their properties
+struct BlockSchema {
+    key: &'static str,
+    block: &'static str,
+    properties: &'static [(&'static str, Kind)],
+    /// Properties that keyframes can give
+    animated: &'static [&'static str],
+}
+
+/// Keys of a scene description, in the order their blocks are parsed
+/// (materials being defined before the objects using them)
+const BLOCKS: [BlockSchema; 7] = [
+    BlockSchema {
+        key: "camera",
+        block: "camera",
+        properties: &[
+            ("position", Kind::Numbers(3)),
+            ("rotation", Kind::Numbers(4)),
+            ("end_position", Kind::Numbers(3)),
+            ("end_rotation", Kind::Numbers(4)),
+            ("shutter", Kind::Numbers(2)),
+            ("focal_length", Kind::Number),
+            ("fov", Kind::Number),
+        ],
+        animated: &["position", "rotation", "focal_length", "fov"],
+    },
+    BlockSchema {
+        key: "materials",
+        block: "material",
+        properties: &[
+            ("name", Kind::Name),
+            ("color", Kind::Numbers(3)),
+            ("emission_color", Kind::Numbers(3)),
+            ("specular_color", Kind::Numbers(3)),
+            ("emission_strength", Kind::Number),
+            ("smoothness", Kind::Number),
+            ("specular_prob", Kind::Number),
+        ],
+        animated: &[
+            "color",
+            "emission_color",
+            "specular_color",
+            "emission_strength",
+            "smoothness",
+            "specular_prob",
+        ],
+    },
+    BlockSchema {
+        key: "spheres",
+        block: "sphere",
+        properties: &[
+            ("position", Kind::Numbers(3)),
+            ("end_position", Kind::Numbers(3)),
+            ("radius", Kind::Number),
+            ("mat", Kind::Name),
+        ],
+        animated: &["position", "radius"],
+    },
+    BlockSchema {
+        key: "planes",
+        block: "plane",
+        properties: &[
+            ("point", Kind::Numbers(3)),
+            ("normal", Kind::Numbers(3)),
+            ("mat", Kind::Name),
+        ],
+        animated: &[],
+    },
+    BlockSchema {
+        key: "triangles",
+        block: "triangle",
+        properties: &[
+            ("point_1", Kind::Numbers(3)),
+            ("point_2", Kind::Numbers(3)),
+            ("point_3", Kind::Numbers(3)),
+            ("mat", Kind::Name),
+        ],
+        animated: &[],
+    },
+    BlockSchema {
+        key: "cubes",
+        block: "cube",
+        properties: &[
+            ("position", Kind::Numbers(3)),
+            ("rotation", Kind::Numbers(4)),
+            ("size", Kind::Number),
+            ("mat", Kind::Name),
+        ],
+        animated: &["position", "rotation", "size"],
+    },
+    BlockSchema {
+        key: "meshes",
+        block: "mesh",
+        properties: &[
+            ("obj_file", Kind::Name),
+            ("ply_file", Kind::Name),
+            ("stl_file", Kind::Name),
+            ("objects", Kind::Names),
+            ("groups", Kind::Names),
+            ("scale", Kind::Number),
+            ("weld", Kind::Number),
+            ("position", Kind::Numbers(3)),
+            ("rotation", Kind::Numbers(4)),
+            ("end_position", Kind::Numbers(3)),
+            ("end_rotation", Kind::Numbers(4)),
+            ("mat", Kind::Name),
+        ],
+        animated: &["position", "rotation"],
+    },
+];
+
+/// Properties of a keyframe besides the animated ones
+const KEYFRAME_PROPERTIES: [(&str, Kind); 2] =
+    [("frame", Kind::Number), ("interpolation", Kind::Name)];
+
+/// Tokens of the .rtp blocks a scene description maps to, with the key path of each token
+/// (such as `spheres[1].radius`) and the errors found
+#[derive(Debug, Clone, Default)]
+pub struct DocumentTokens {
+    pub tokens: Vec<Token>,
+    pub paths: Vec<String>,
+    /// Line and column of syntax errors ((0, 0) for schema errors, their message starting
+    /// with their key path)
+    pub errors: Vec<((usize, usize), String)>,
+}
+
+/// Reads a JSON or TOML scene description, checking it against the schema of the .rtp blocks
+///
+/// Invalid properties are left out of the tokens, so that the rest of the scene is still parsed
+pub fn document_tokens(source: &str, format: DocumentFormat) -> DocumentTokens {
+    let mut document = DocumentTokens::default();
+    let root = match format {
+        DocumentFormat::Json => serde_json::from_str::<Value>(source).map_err(|e| {
+            let message = e.to_string();
+            let suffix = format!(" at line {} column {}", e.line(), e.column());
+            let message = message.strip_suffix(&suffix).unwrap_or(&message);
+            ((e.line(), e.column()), message.to_string())
+        }),
+        DocumentFormat::Toml => toml::from_str::<Value>(source).map_err(|e| {
+            let position = e
+                .span()
+                .map_or((0, 0), |span| line_and_column(source, span.start));
+            (position, e.message().trim().replace('\n', ", "))
+        }),
+    };
+    let root = match root {
+        Ok(root) => root,
+        Err(error) => {
+            document.errors.push(error);
+            return document;
+        }
+    };
+
+    let mut reader = Reader { format, document };
+    reader.root(&root);
+    reader.document
+}
+
+/// Line and column of a byte offset
+fn line_and_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// Checks a scene description while writing its tokens
+struct Reader {
+    format: DocumentFormat,
+    document: DocumentTokens,
+}
+
+impl Reader {
+    fn push(&mut self, token: Token, path: &str) {
+        self.document.tokens.push(token);
+        self.document.paths.push(path.to_string());
+    }
+
+    /// Adds an error at a key path (the whole document for an empty path)
+    fn error(&mut self, path: &str, message: String) {
+        let message = match path {
+            "" => message,
+            path => format!("{}: {}", path, message),
+        };
+        self.document.errors.push(((0, 0), message));
+    }
+
+    /// Description of a value, for errors
+    fn describe(&self, value: &Value) -> String {
+        match value {
+            Value::Null => String::from("null"),
+            Value::Bool(_) => String::from("a boolean"),
+            Value::Number(_) => String::from("a number"),
+            Value::String(_) => String::from("a string"),
+            Value::Array(array) => format!("an array of {}", array.len()),
+            Value::Object(_) => String::from(self.object_name()),
+        }
+    }
+
+    /// Name of objects in the format
+    fn object_name(&self) -> &'static str {
+        match self.format {
+            DocumentFormat::Json => "an object",
+            DocumentFormat::Toml => "a table",
+        }
+    }
+
+    /// Error for an unknown key, suggesting the closest expected one
+    fn unknown_key(&mut self, path: &str, key: &str, expected: &[&str]) {
+        let mut message = format!("unknown key `{}`", key);
+        match closest_name(key, expected.iter().copied()) {
+            Some(closest) => message += &format!(", did you mean `{}`?", closest),
+            None => message += &format!(" (expected {})", expected.join(", ")),
+        }
+        self.error(path, message);
+    }
+
+    /// Writes the blocks of the scene, camera and materials first
+    fn root(&mut self, root: &Value) {
+        let Value::Object(root) = root else {
+            let message = format!(
+                "expected {}, found {}",
+                self.object_name(),
+                self.describe(root)
+            );
+            self.error("", message);
+            return;
+        };
+        let keys: Vec<&str> = BLOCKS.iter().map(|schema| schema.key).collect();
+        for key in root.keys() {
+            if !keys.contains(&key.as_str()) {
+                self.unknown_key(key, key, &keys);
+            }
+        }
+
+        for schema in &BLOCKS {
+            match (schema.key, root.get(schema.key)) {
+                (_, None) => {}
+                ("camera", Some(camera)) => self.block(schema, camera, "camera"),
+                (key, Some(Value::Array(objects))) => {
+                    for (i, object) in objects.iter().enumerate() {
+                        self.block(schema, object, &format!("{}[{}]", key, i));
+                    }
+                }
+                (key, Some(value)) => {
+                    let message = format!("expected an array, found {}", self.describe(value));
+                    self.error(key, message);
+                }
+            }
+        }
+    }
+
+    /// Writes a block (an empty one if its value is not an object, so that the error is
+    /// only reported once)
+    fn block(&mut self, schema: &BlockSchema, value: &Value, path: &str) {
+        self.push(Token::Identifier(schema.block.to_string()), path);
+        self.push(Token::LBrace, path);
+        self.push(Token::Newline, path);
+        match value {
+            Value::Object(object) => {
+                let mut expected: Vec<&str> = schema.properties.iter().map(|(p, _)| *p).collect();
+                if !schema.animated.is_empty() {
+                    expected.push("keyframes");
+                }
+                for (key, value) in object {
+                    let property_path = format!("{}.{}", path, key);
+                    match schema.properties.iter().find(|(p, _)| p == key) {
+                        Some(&(_, kind)) => self.property(key, kind, value, &property_path),
+                        None if key == "keyframes" && !schema.animated.is_empty() => {
+                            self.keyframes(schema, value, &property_path)
+                        }
+                        None => self.unknown_key(&property_path, key, &expected),
+                    }
+                }
+            }
+            value => {
+                let message = format!(
+                    "expected {}, found {}",
+                    self.object_name(),
+                    self.describe(value)
+                );
+                self.error(path, message);
+            }
+        }
+        self.push(Token::RBrace, path);
+        self.push(Token::Newline, path);
+    }
+
+    /// Writes the keyframe blocks of an array of keyframes
+    fn keyframes(&mut self, schema: &BlockSchema, value: &Value, path: &str) {
+        let Value::Array(keyframes) = value else {
+            let message = format!("expected an array, found {}", self.describe(value));
+            self.error(path, message);
+            return;
+        };
+        for (i, keyframe) in keyframes.iter().enumerate() {
+            let keyframe_path = format!("{}[{}]", path, i);
+            let Value::Object(keyframe) = keyframe else {
+                let message = format!(
+                    "expected {}, found {}",
+                    self.object_name(),
+                    self.describe(keyframe)
+                );
+                self.error(&keyframe_path, message);
+                continue;
+            };
+            let properties: Vec<(&str, Kind)> = KEYFRAME_PROPERTIES
+                .into_iter()
+                .chain(schema.animated.iter().filter_map(|animated| {
+                    schema
+                        .properties
+                        .iter()
+                        .copied()
+                        .find(|(p, _)| p == animated)
+                }))
+                .collect();
+            let expected: Vec<&str> = properties.iter().map(|(p, _)| *p).collect();
+
+            self.push(Token::Identifier(String::from("keyframe")), &keyframe_path);
+            self.push(Token::LBrace, &keyframe_path);
+            self.push(Token::Newline, &keyframe_path);
+            for (key, value) in keyframe {
+                let property_path = format!("{}.{}", keyframe_path, key);
+                match properties.iter().find(|(p, _)| p == key) {
+                    Some(&(_, kind)) => self.property(key, kind, value, &property_path),
+                    None => self.unknown_key(&property_path, key, &expected),
+                }
+            }
+            self.push(Token::RBrace, &keyframe_path);
+            self.push(Token::Newline, &keyframe_path);
+        }
+    }
+
+    /// Writes a property line, if its value has the expected type
+    fn property(&mut self, key: &str, kind: Kind, value: &Value, path: &str) {
+        let values: Vec<Token> = match (kind, value) {
+            (Kind::Number, Value::Number(n)) => vec![Token::Number(n.as_f64().unwrap_or(0.0))],
+            (Kind::Numbers(count), Value::Array(array))
+                if array.len() == count && array.iter().all(Value::is_number) =>
+            {
+                array
+                    .iter()
+                    .map(|n| Token::Number(n.as_f64().unwrap_or(0.0)))
+                    .collect()
+            }
+            (Kind::Name, Value::String(name)) => vec![Token::Str(name.clone())],
+            (Kind::Names, Value::Array(names))
+                if !names.is_empty() && names.iter().all(Value::is_string) =>
+            {
+                names
+                    .iter()
+                    .map(|name| Token::Str(name.as_str().unwrap_or("").to_string()))
+                    .collect()
+            }
+            (kind, value) => {
+                let expected = match kind {
+                    Kind::Number => String::from("a number"),
+                    Kind::Numbers(count) => format!("an array of {} numbers", count),
+                    Kind::Name => String::from("a string"),
+                    Kind::Names => String::from("an array of strings"),
+                };
+                let found = match (kind, value) {
+                    (Kind::Numbers(count), Value::Array(array)) if array.len() == count => {
+                        let element = array.iter().find(|n| !n.is_number()).unwrap_or(value);
+                        format!("an array containing {}", self.describe(element))
+                    }
+                    _ => self.describe(value),
+                };
+                self.error(path, format!("expected {}, found {}", expected, found));
+                return;
+            }
+        };
+        self.push(Token::Identifier(key.to_string()), path);
+        self.push(Token::Colon, path);
+        for token in values {
+            self.push(token, path);
+        }
+        self.push(Token::Newline, path);
+    }
+}
+
+#[cfg(test)]
+mod tests_document {
+    use super::DocumentFormat;
+    use crate::mods::{color::ColorRBG, parser::Parser, position::Vect3};
+
+    /// Messages of the errors of a scene
+    fn errors(source: &str, file_name: &str) -> Vec<String> {
+        match Parser::new(source, file_name).parse_scene() {
+            Ok(_) => vec![],
+            Err(errors) => errors.0.into_iter().map(|e| e.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn formats() {
+        assert_eq!(
+            DocumentFormat::from_file_name("scenes/a.json"),
+            Some(DocumentFormat::Json)
+        );
+        assert_eq!(
+            DocumentFormat::from_file_name("a.toml"),
+            Some(DocumentFormat::Toml)
+        );
+        assert_eq!(DocumentFormat::from_file_name("a.rtp"), None);
+    }
+
+    #[test]
+    fn json() {
+        let source = r#"{
+            "camera": {"position": [0, 1, 10], "rotation": [0, 0, 1, 0], "fov": 30},
+            "spheres": [{"position": [0, 1, 0], "radius": 0.5, "mat": "red"}],
+            "materials": [{"name": "red", "color": [1, 0, 0]}],
+            "cubes": [{"size": 2, "mat": "red"}]
+        }"#;
+        let scene = Parser::new(source, "scene.json").parse_scene().unwrap();
+
+        assert_eq!(scene.camera.transform.get_pos(), Vect3::new(0.0, 1.0, 10.0));
+        assert_eq!(scene.spheres[0].radius, 0.5);
+        assert_eq!(scene.spheres[0].get_mat().color, ColorRBG::RED);
+        assert_eq!(scene.triangles.len(), 12);
+    }
+
+    #[test]
+    fn toml() {
+        let source = r#"
+[camera]
+fov = 30
+
+[[materials]]
+name = "lamp"
+emission_strength = 1
+
+[[spheres]]
+mat = "lamp"
+
+[[spheres.keyframes]]
+frame = 0
+radius = 1
+
+[[spheres.keyframes]]
+frame = 10
+radius = 3
+"#;
+        let mut parser = Parser::new(source, "scene.toml");
+        parser.set_frame(5.0);
+        let scene = parser.parse_scene().unwrap();
+
+        assert_eq!(scene.spheres[0].radius, 2.0);
+        assert_eq!(scene.spheres[0].get_mat().emission_strengh, 1.0);
+    }
+
+    #[test]
+    fn key_paths() {
+        let source = r#"{
+            "camera": {"fov": "wide"},
+            "spheres": [{"radius": 1}, {"position": [0, "1", 0], "radiu": 1, "mat": "red"}],
+            "sphere": []
+        }"#;
+
+        assert_eq!(
+            errors(source, "a.json"),
+            vec![
+                "a.json: sphere: unknown key `sphere`, did you mean `spheres`?",
+                "a.json: camera.fov: expected a number, found a string",
+                "a.json: spheres[1].position: expected an array of 3 numbers, found an array containing a string",
+                "a.json: spheres[1].radiu: unknown key `radiu`, did you mean `radius`?",
+                "a.json: spheres[1].mat: undefined material `red`",
+            ]
+        );
+        assert_eq!(
+            errors("[[spheres]]\nradius = 1\n", "a.toml"),
+            vec!["a.toml: missing `camera`"]
+        );
+        assert_eq!(
+            errors("[camera]\nfov = [30\n", "a.toml"),
+            vec!["a.toml:3:1: invalid array, expected `]`"]
+        );
+        assert_eq!(
+            errors("{\"camera\": {}", "a.json"),
+            vec!["a.json:1:13: EOF while parsing an object"]
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use std::{f64::consts::PI, fmt};
+
+/// Value of an expression of a scene file
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(f64),
+    /// Vector, color or quaternion components
+    Vector(Vec<f64>),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Number(_) => write!(f, "a number"),
+            Value::Vector(v) => write!(f, "a vector of {}", v.len()),
+        }
+    }
+}
+
+/// Arithmetic operator
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl Value {
+    /// Applies an operator, vectors being added component-wise and scaled by numbers
+    pub fn apply(self, operator: Operator, other: Value) -> Result<Value, String> {
+        let op = |a: f64, b: f64| match operator {
+            Operator::Add => a + b,
+            Operator::Sub => a - b,
+            Operator::Mul => a * b,
+            Operator::Div => a / b,
+        };
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => Ok(Value::Number(op(a, b))),
+            (Value::Vector(a), Value::Vector(b))
+                if a.len() == b.len() && matches!(operator, Operator::Add | Operator::Sub) =>
+            {
+                Ok(Value::Vector(
+                    a.iter().zip(&b).map(|(a, b)| op(*a, *b)).collect(),
+                ))
+            }
+            (Value::Vector(a), Value::Number(b))
+                if matches!(operator, Operator::Mul | Operator::Div) =>
+            {
+                Ok(Value::Vector(a.iter().map(|a| op(*a, b)).collect()))
+            }
+            (Value::Number(a), Value::Vector(b)) if operator == Operator::Mul => {
+                Ok(Value::Vector(b.iter().map(|b| a * b).collect()))
+            }
+            (a, b) => Err(format!(
+                "cannot {} {} and {}",
+                match operator {
+                    Operator::Add => "add",
+                    Operator::Sub => "subtract",
+                    Operator::Mul => "multiply",
+                    Operator::Div => "divide",
+                },
+                a,
+                b
+            )),
+        }
+    }
+
+    /// Negated value
+    pub fn negate(self) -> Value {
+        match self {
+            Value::Number(a) => Value::Number(-a),
+            Value::Vector(a) => Value::Vector(a.iter().map(|a| -a).collect()),
+        }
+    }
+}
+
+/// Value of a named constant
+pub fn constant(name: &str) -> Option<Value> {
+    match name {
+        "pi" => Some(Value::Number(PI)),
+        _ => None,
+    }
+}
+
+/// Whether a function exists
+pub fn is_function(name: &str) -> bool {
+    function(name).is_some()
+}
+
+/// Function of numbers
+type Function = fn(&[f64]) -> f64;
+
+/// Number function and its argument count
+fn function(name: &str) -> Option<(usize, Function)> {
+    Some(match name {
+        "sin" => (1, |x| x[0].sin()),
+        "cos" => (1, |x| x[0].cos()),
+        "tan" => (1, |x| x[0].tan()),
+        "sqrt" => (1, |x| x[0].sqrt()),
+        "abs" => (1, |x| x[0].abs()),
+        "deg" => (1, |x| x[0].to_degrees()),
+        "rad" => (1, |x| x[0].to_radians()),
+        "min" => (2, |x| x[0].min(x[1])),
+        "max" => (2, |x| x[0].max(x[1])),
+        "pow" => (2, |x| x[0].powf(x[1])),
+        _ => return None,
+    })
+}
+
+/// Calls a function on numbers (one argument functions also apply to each component of a vector)
+pub fn call(name: &str, args: &[Value]) -> Result<Value, String> {
+    let (count, function) = function(name).ok_or(format!("unknown function `{}`", name))?;
+    if args.len() != count {
+        return Err(format!(
+            "function `{}` takes {} argument{}, {} given",
+            name,
+            count,
+            if count == 1 { "" } else { "s" },
+            args.len()
+        ));
+    }
+    match args {
+        [Value::Vector(v)] => Ok(Value::Vector(v.iter().map(|x| function(&[*x])).collect())),
+        _ => {
+            let numbers = args
+                .iter()
+                .map(|arg| match arg {
+                    Value::Number(n) => Ok(*n),
+                    vector => Err(format!(
+                        "function `{}` expects numbers, found {}",
+                        name, vector
+                    )),
+                })
+                .collect::<Result<Vec<f64>, String>>()?;
+            Ok(Value::Number(function(&numbers)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests_expr {
+    use super::{call, Operator, Value};
+
+    #[test]
+    fn operators() {
+        let vector = Value::Vector(vec![1.0, 2.0, 3.0]);
+
+        assert_eq!(
+            Value::Number(6.0).apply(Operator::Div, Value::Number(4.0)),
+            Ok(Value::Number(1.5))
+        );
+        assert_eq!(
+            vector.clone().apply(Operator::Add, vector.clone()),
+            Ok(Value::Vector(vec![2.0, 4.0, 6.0]))
+        );
+        assert_eq!(
+            Value::Number(2.0).apply(Operator::Mul, vector.clone()),
+            Ok(Value::Vector(vec![2.0, 4.0, 6.0]))
+        );
+        assert_eq!(
+            vector.clone().apply(Operator::Sub, Value::Number(1.0)),
+            Err(String::from("cannot subtract a vector of 3 and a number"))
+        );
+        assert_eq!(vector.negate(), Value::Vector(vec![-1.0, -2.0, -3.0]));
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(
+            call("max", &[Value::Number(1.0), Value::Number(2.0)]),
+            Ok(Value::Number(2.0))
+        );
+        assert_eq!(
+            call("sqrt", &[Value::Vector(vec![4.0, 9.0])]),
+            Ok(Value::Vector(vec![2.0, 3.0]))
+        );
+        assert_eq!(
+            call("sin", &[]),
+            Err(String::from("function `sin` takes 1 argument, 0 given"))
+        );
+        assert!(call("sine", &[Value::Number(1.0)]).is_err());
+    }
+}
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use super::position::Vect3;
 
 /// Solve a quadratic equation from its coefficients
@@ -38,15 +40,89 @@ pub fn s_to_hms(secs: f64) -> String {
     format!("[{}:{}:{}]", h, m, s)
 }
 
+/// FNV-1a hash of bytes, starting from a previous hash (FNV_OFFSET to start a new one)
+pub fn hash_bytes(hash: u64, bytes: &[u8]) -> u64 {
+    const FNV_PRIME: u64 = 0x100000001b3;
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// FNV-1a hash offset basis
+pub const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+
+/// Parses a duration such as "90", "30s", "10m", "1h30m" (plain numbers are seconds)
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let mut secs = 0.0;
+    let mut number = String::new();
+
+    for c in text.trim().chars() {
+        if c.is_ascii_digit() || c == '.' {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            'h' => 3600.0,
+            'm' => 60.0,
+            's' => 1.0,
+            _ => return None,
+        };
+        secs += number.parse::<f64>().ok()? * unit;
+        number.clear();
+    }
+    if !number.is_empty() {
+        secs += number.parse::<f64>().ok()?;
+    }
+
+    if secs > 0.0 {
+        Some(Duration::from_secs_f64(secs))
+    } else {
+        None
+    }
+}
+
+/// Levenshtein distance between two strings (number of inserted, removed or replaced chars)
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let replaced = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = replaced.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Closest name to a misspelled one, if close enough to be a likely typo
+pub fn closest_name<'a>(name: &str, names: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let max_distance = name.chars().count().div_ceil(3).max(1);
+    names
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
 #[cfg(test)]
 mod tests_funcs {
     use approx::assert_abs_diff_eq;
 
     use crate::mods::position::Vect3;
 
+    use std::time::Duration;
+
+    use super::closest_name;
     use super::det_from_3_vects;
+    use super::edit_distance;
+    use super::hash_bytes;
+    use super::parse_duration;
     use super::reflect;
     use super::s_to_hms;
+    use super::FNV_OFFSET;
 
     #[test]
     fn test_det() {
@@ -76,4 +152,41 @@ mod tests_funcs {
         assert_eq!(s_to_hms(120.0), String::from("[0:2:0]"));
         assert_eq!(s_to_hms(3674.0), String::from("[1:1:14]"));
     }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("30s"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_duration("10m"), Some(Duration::from_secs(600)));
+        assert_eq!(parse_duration("1h30m"), Some(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("0.5s"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_duration("10x"), None);
+        assert_eq!(parse_duration("0"), None);
+    }
+
+    #[test]
+    fn fnv_hash() {
+        assert_eq!(hash_bytes(FNV_OFFSET, b""), FNV_OFFSET);
+        assert_eq!(hash_bytes(FNV_OFFSET, b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(
+            hash_bytes(hash_bytes(FNV_OFFSET, b"ab"), b"c"),
+            hash_bytes(FNV_OFFSET, b"abc")
+        );
+    }
+
+    #[test]
+    fn typos() {
+        assert_eq!(edit_distance("red", "red"), 0);
+        assert_eq!(edit_distance("redd", "red"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+
+        let names = ["red", "blue", "light_white"];
+        assert_eq!(closest_name("bleu", names.into_iter()), Some("blue"));
+        assert_eq!(
+            closest_name("light_whit", names.into_iter()),
+            Some("light_white")
+        );
+        assert_eq!(closest_name("green", names.into_iter()), None);
+    }
 }
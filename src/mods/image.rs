@@ -1,8 +1,10 @@
 // Image stuff
 
-use std::{error::Error, fs::File, io::prelude::*};
-
-use console::style;
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::prelude::*,
+};
 
 /// Image implementation
 #[derive(Clone)]
@@ -37,6 +39,12 @@ impl ImageRGB {
         self.data.len() * self.data[0].len()
     }
 
+    /// Get a pixel's color
+    #[inline]
+    pub fn get_pixel(&self, x: usize, y: usize) -> (u8, u8, u8) {
+        self.data[y][x]
+    }
+
     /// Set a pixel's color
     #[inline]
     pub fn set_pixel(&mut self, x: usize, y: usize, value: (u8, u8, u8)) {
@@ -45,35 +53,41 @@ impl ImageRGB {
 
     /// Save as ppm file (not optimal...)
     pub fn save_as_ppm(&mut self, filename: &str) -> Result<(), Box<dyn Error>> {
-        let mut filename: String = filename.to_string();
-        if !filename.ends_with(".ppm") {
-            filename = format!("{}.ppm", filename);
-        }
-        print_save_info(&filename);
-        let mut file = File::create(filename)?;
-        let header = format!("P3\n{} {}\n255\n", self.data[0].len(), self.data.len());
+        let mut file = File::create(ppm_filename(filename))?;
+        file.write_all(self.ppm_content().as_bytes())?;
+        Ok(())
+    }
 
-        file.write_all(header.as_bytes())?;
+    /// Write ppm file atomically: readers never see a partially written image
+    pub fn write_ppm_atomic(&self, filename: &str) -> Result<(), Box<dyn Error>> {
+        let filename = ppm_filename(filename);
+        let tmp_filename = format!("{}.tmp", filename);
+        let mut file = File::create(&tmp_filename)?;
+        file.write_all(self.ppm_content().as_bytes())?;
+        file.sync_all()?;
+        fs::rename(tmp_filename, filename)?;
+        Ok(())
+    }
 
-        let mut content = String::new();
+    /// Get ppm file content
+    fn ppm_content(&self) -> String {
+        let mut content = format!("P3\n{} {}\n255\n", self.data[0].len(), self.data.len());
         for line in self.data.iter() {
             for el in line {
                 content += format!("{} {} {}\n", el.0, el.1, el.2).as_str();
             }
         }
-        file.write_all(content.as_bytes())?;
-        println!("      Saved image to ppm file");
-        Ok(())
+        content
     }
 }
 
-/// Prints saving information
-fn print_save_info(filename: &String) {
-    println!(
-        "{} Saving image to : {}",
-        style("[3/3]").bold().green(),
-        style(filename).italic().dim()
-    );
+/// Adds the .ppm extension to a file name if missing
+pub fn ppm_filename(filename: &str) -> String {
+    if filename.ends_with(".ppm") {
+        filename.to_string()
+    } else {
+        format!("{}.ppm", filename)
+    }
 }
 
 #[cfg(test)]
@@ -103,4 +117,15 @@ mod tests_image {
 
         assert!(image.save_as_ppm("tests_image_save").is_ok())
     }
+
+    #[test]
+    fn save_atomic() {
+        let filename = std::env::temp_dir().join("tests_image_save_atomic.ppm");
+        let filename = filename.to_str().unwrap();
+        let image = ImageRGB::new(10, 5);
+
+        assert!(image.write_ppm_atomic(filename).is_ok());
+        assert!(std::path::Path::new(filename).exists());
+        assert!(!std::path::Path::new(&format!("{}.tmp", filename)).exists());
+    }
 }
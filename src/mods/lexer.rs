@@ -0,0 +1,424 @@
+use std::fmt;
+
+use super::expr::Operator;
+
+/// Token of a scene or .obj file
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Identifier(String),
+    Str(String),
+    Number(f64),
+    Bool(bool),
+    Colon,
+    LBrace,
+    RBrace,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+    Equals,
+    DotDot,
+    Operator(Operator),
+    Newline,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Identifier(name) => write!(f, "`{}`", name),
+            Token::Str(text) => write!(f, "string {:?}", text),
+            Token::Number(n) => write!(f, "number {}", n),
+            Token::Bool(b) => write!(f, "`{}`", b),
+            Token::Colon => write!(f, "':'"),
+            Token::LBrace => write!(f, "'{{'"),
+            Token::RBrace => write!(f, "'}}'"),
+            Token::LParen => write!(f, "'('"),
+            Token::RParen => write!(f, "')'"),
+            Token::LBracket => write!(f, "'['"),
+            Token::RBracket => write!(f, "']'"),
+            Token::Comma => write!(f, "','"),
+            Token::Equals => write!(f, "'='"),
+            Token::DotDot => write!(f, "'..'"),
+            Token::Operator(operator) => write!(
+                f,
+                "'{}'",
+                match operator {
+                    Operator::Add => '+',
+                    Operator::Sub => '-',
+                    Operator::Mul => '*',
+                    Operator::Div => '/',
+                }
+            ),
+            Token::Newline => write!(f, "end of line"),
+        }
+    }
+}
+
+/// Position of a token, from its first character to the one after its last
+/// (lines and columns starting from 1)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+/// Tokens of a file, with their spans and the errors found
+#[derive(Debug, Clone, Default)]
+pub struct Tokens {
+    pub tokens: Vec<Token>,
+    pub spans: Vec<Span>,
+    /// Line and column of the end of the file
+    pub end: (usize, usize),
+    pub errors: Vec<(Span, String)>,
+}
+
+/// Characters before which a sign stuck to a number makes it negative or positive
+/// ("1 -2" being two numbers, "1-2" and "1 - 2" subtractions)
+const SIGN_PREFIXES: &str = ":([,=+-*/";
+
+/// Splits a file into tokens, going on after invalid characters so that every error is found
+pub fn tokenize(input: &str) -> Tokens {
+    Lexer {
+        chars: input.chars().collect(),
+        i: 0,
+        line: 1,
+        line_start: 0,
+        tokens: Tokens::default(),
+    }
+    .run()
+}
+
+/// Tokenizer state
+struct Lexer {
+    chars: Vec<char>,
+    i: usize,
+    line: usize,
+    line_start: usize,
+    tokens: Tokens,
+}
+
+impl Lexer {
+    /// Tokenizes every character
+    fn run(mut self) -> Tokens {
+        while self.i < self.chars.len() {
+            let start = self.position();
+            let token = self.next_token(start);
+            if let Some(token) = token {
+                let end = self.position();
+                self.tokens.tokens.push(token);
+                self.tokens.spans.push(span(start, end));
+            }
+        }
+        self.tokens.end = self.position();
+        self.tokens
+    }
+
+    /// Line and column of the current character
+    fn position(&self) -> (usize, usize) {
+        (self.line, self.i - self.line_start + 1)
+    }
+
+    /// Character at an offset from the current one
+    fn char_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.i + offset).copied()
+    }
+
+    /// Adds an error from a position to the current character
+    fn error(&mut self, start: (usize, usize), message: String) {
+        let end = self.position();
+        self.tokens.errors.push((span(start, end), message));
+    }
+
+    /// Reads the token starting at the current character (None for spaces and comments)
+    fn next_token(&mut self, start: (usize, usize)) -> Option<Token> {
+        let c = self.chars[self.i];
+        let single = match c {
+            ':' => Some(Token::Colon),
+            '{' => Some(Token::LBrace),
+            '}' => Some(Token::RBrace),
+            '(' => Some(Token::LParen),
+            ')' => Some(Token::RParen),
+            '[' => Some(Token::LBracket),
+            ']' => Some(Token::RBracket),
+            ',' => Some(Token::Comma),
+            '=' => Some(Token::Equals),
+            '*' => Some(Token::Operator(Operator::Mul)),
+            '/' => Some(Token::Operator(Operator::Div)),
+            _ => None,
+        };
+        if single.is_some() {
+            self.i += 1;
+            return single;
+        }
+
+        match c {
+            '\n' => {
+                self.i += 1;
+                let token = Some(Token::Newline);
+                self.line += 1;
+                self.line_start = self.i;
+                token
+            }
+            c if c.is_whitespace() => {
+                self.i += 1;
+                None
+            }
+            '#' => {
+                while self.char_at(0).is_some_and(|c| c != '\n') {
+                    self.i += 1;
+                }
+                None
+            }
+            '.' if self.char_at(1) == Some('.') => {
+                self.i += 2;
+                Some(Token::DotDot)
+            }
+            '"' => Some(self.string(start)),
+            '+' | '-' if self.starts_number(1) && self.after_separator() => {
+                Some(self.number(start))
+            }
+            '+' => {
+                self.i += 1;
+                Some(Token::Operator(Operator::Add))
+            }
+            '-' => {
+                self.i += 1;
+                Some(Token::Operator(Operator::Sub))
+            }
+            c if c.is_ascii_digit() || (c == '.' && self.starts_number(0)) => {
+                Some(self.number(start))
+            }
+            c if c.is_alphabetic() || c == '_' => Some(self.identifier()),
+            c => {
+                self.i += 1;
+                self.error(start, format!("unexpected character `{}`", c));
+                None
+            }
+        }
+    }
+
+    /// Whether a number starts at an offset: a digit, or a dot and a digit
+    fn starts_number(&self, offset: usize) -> bool {
+        match self.char_at(offset) {
+            Some(c) if c.is_ascii_digit() => true,
+            Some('.') => self.char_at(offset + 1).is_some_and(|c| c.is_ascii_digit()),
+            _ => false,
+        }
+    }
+
+    /// Whether the current character follows a space, an opening or an operator
+    fn after_separator(&self) -> bool {
+        self.i == 0 || {
+            let previous = self.chars[self.i - 1];
+            previous.is_whitespace() || SIGN_PREFIXES.contains(previous)
+        }
+    }
+
+    /// Whether the current character continues a word (so that "1.2.3" or "12abc"
+    /// are single invalid numbers)
+    fn in_word(&self) -> bool {
+        match self.char_at(0) {
+            Some('.') => self.char_at(1) != Some('.'),
+            Some(c) => c.is_alphanumeric() || c == '_',
+            None => false,
+        }
+    }
+
+    /// Skips digits
+    fn digits(&mut self) {
+        while self.char_at(0).is_some_and(|c| c.is_ascii_digit()) {
+            self.i += 1;
+        }
+    }
+
+    /// Reads a number: optional sign, digits, optional fraction and exponent
+    fn number(&mut self, start: (usize, usize)) -> Token {
+        let first = self.i;
+        if matches!(self.char_at(0), Some('+' | '-')) {
+            self.i += 1;
+        }
+        self.digits();
+        if self.char_at(0) == Some('.') && self.char_at(1) != Some('.') {
+            self.i += 1;
+            self.digits();
+        }
+        if matches!(self.char_at(0), Some('e' | 'E')) {
+            let sign = usize::from(matches!(self.char_at(1), Some('+' | '-')));
+            if self.char_at(1 + sign).is_some_and(|c| c.is_ascii_digit()) {
+                self.i += 1 + sign;
+                self.digits();
+            }
+        }
+
+        let valid = !self.in_word();
+        while self.in_word() {
+            self.i += 1;
+        }
+        let text: String = self.chars[first..self.i].iter().collect();
+        match text.parse() {
+            Ok(value) if valid => Token::Number(value),
+            _ => {
+                // Keeps a number, so that the error is only reported once
+                self.error(start, format!("invalid number `{}`", text));
+                Token::Number(0.0)
+            }
+        }
+    }
+
+    /// Reads a name (which may be a path such as obj/monkey.obj, so `a/b` is one name
+    /// while `2/3` is a division), or a boolean
+    fn identifier(&mut self) -> Token {
+        let first = self.i;
+        while self.in_word() || self.char_at(0) == Some('/') {
+            self.i += 1;
+        }
+        let name: String = self.chars[first..self.i].iter().collect();
+        match name.as_str() {
+            "true" => Token::Bool(true),
+            "false" => Token::Bool(false),
+            _ => Token::Identifier(name),
+        }
+    }
+
+    /// Reads a quoted string, with the escapes \" \\ \n and \t
+    fn string(&mut self, start: (usize, usize)) -> Token {
+        self.i += 1;
+        let mut text = String::new();
+        loop {
+            match self.char_at(0) {
+                Some('"') => {
+                    self.i += 1;
+                    break;
+                }
+                Some('\\') => {
+                    let escape_start = self.position();
+                    self.i += 1;
+                    match self.char_at(0) {
+                        Some('"') => text.push('"'),
+                        Some('\\') => text.push('\\'),
+                        Some('n') => text.push('\n'),
+                        Some('t') => text.push('\t'),
+                        Some('\n') | None => continue,
+                        Some(c) => {
+                            self.i += 1;
+                            self.error(escape_start, format!("unknown escape `\\{}`", c));
+                            continue;
+                        }
+                    }
+                    self.i += 1;
+                }
+                Some('\n') | None => {
+                    self.error(start, String::from("unterminated string"));
+                    break;
+                }
+                Some(c) => {
+                    text.push(c);
+                    self.i += 1;
+                }
+            }
+        }
+        Token::Str(text)
+    }
+}
+
+/// Span from a position to another
+fn span(start: (usize, usize), end: (usize, usize)) -> Span {
+    Span {
+        line: start.0,
+        column: start.1,
+        end_line: end.0,
+        end_column: end.1,
+    }
+}
+
+#[cfg(test)]
+mod tests_lexer {
+    use super::{tokenize, Span, Token};
+    use crate::mods::expr::Operator;
+
+    /// Tokens of a line, without their spans
+    fn tokens(input: &str) -> Vec<Token> {
+        tokenize(input).tokens
+    }
+
+    /// Messages of the errors of a line
+    fn errors(input: &str) -> Vec<String> {
+        tokenize(input)
+            .errors
+            .into_iter()
+            .map(|(_, message)| message)
+            .collect()
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(
+            tokens("1 .5 -2.5 +3 1e-3 2.5E+2 -.25"),
+            [1.0, 0.5, -2.5, 3.0, 1e-3, 250.0, -0.25].map(Token::Number)
+        );
+        assert_eq!(
+            tokens("1-2 (-1)"),
+            vec![
+                Token::Number(1.0),
+                Token::Operator(Operator::Sub),
+                Token::Number(2.0),
+                Token::LParen,
+                Token::Number(-1.0),
+                Token::RParen
+            ]
+        );
+        assert_eq!(
+            tokens("0..n"),
+            vec![
+                Token::Number(0.0),
+                Token::DotDot,
+                Token::Identifier(String::from("n"))
+            ]
+        );
+        assert_eq!(
+            errors("1.2.3 12abc 1e x"),
+            vec![
+                "invalid number `1.2.3`",
+                "invalid number `12abc`",
+                "invalid number `1e`"
+            ]
+        );
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(
+            tokens(r#""my file.obj" "a \"quote\" \\ " true"#),
+            vec![
+                Token::Str(String::from("my file.obj")),
+                Token::Str(String::from("a \"quote\" \\ ")),
+                Token::Bool(true)
+            ]
+        );
+        assert_eq!(
+            errors("\"a \\q\"\n\"open\n"),
+            vec!["unknown escape `\\q`", "unterminated string"]
+        );
+    }
+
+    #[test]
+    fn spans() {
+        let tokens = tokenize("radius: 1.5 # comment\n  \"x\"");
+
+        assert_eq!(
+            tokens.spans[2],
+            Span {
+                line: 1,
+                column: 9,
+                end_line: 1,
+                end_column: 12
+            }
+        );
+        assert_eq!((tokens.spans[4].line, tokens.spans[4].column), (2, 3));
+        assert_eq!(tokens.spans[4].end_column, 6);
+        assert_eq!(tokens.end, (2, 6));
+    }
+}
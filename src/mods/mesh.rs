@@ -0,0 +1,691 @@
+use std::{collections::HashMap, mem};
+
+use super::{
+    color::ColorRBG,
+    funcs::closest_name,
+    material::Material,
+    objs::Triangle,
+    parse_error::{ParseError, ParseErrors},
+    position::Vect3,
+};
+
+/// Triangle of a mesh, with the object and groups it belongs to
+#[derive(Debug, Clone, PartialEq)]
+pub struct Face {
+    /// Vertex indices, counter-clockwise seen from the front
+    pub vertices: [usize; 3],
+    /// Index of the face's object in the mesh's objects
+    pub object: Option<usize>,
+    /// Index of the face's groups in the mesh's group sets
+    pub groups: usize,
+    /// Index of the face's material in the mesh's materials
+    pub material: Option<usize>,
+    /// Color of the face, for faces without a material
+    pub color: Option<ColorRBG>,
+}
+
+/// Material given to faces of a mesh by `usemtl` statements
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeshMaterial {
+    pub name: String,
+    /// Line of the first statement using it
+    pub line: usize,
+    /// Definition found in the mesh's material files
+    pub material: Option<Material>,
+}
+
+/// Format of a mesh file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MeshFormat {
+    Obj,
+    Ply,
+    Stl,
+}
+
+impl MeshFormat {
+    /// Property of a mesh block naming a file of the format
+    pub fn key(self) -> &'static str {
+        match self {
+            MeshFormat::Obj => "obj_file",
+            MeshFormat::Ply => "ply_file",
+            MeshFormat::Stl => "stl_file",
+        }
+    }
+}
+
+/// Triangle mesh read from a file, before it is placed in a scene
+#[derive(Debug, Clone, Default)]
+pub struct Mesh {
+    pub vertices: Vec<Vect3>,
+    pub faces: Vec<Face>,
+    /// Object names, in order of appearance
+    pub objects: Vec<String>,
+    /// Group names, in order of appearance
+    pub groups: Vec<String>,
+    /// Groups given to the faces by each group statement (indices in groups, the first
+    /// set being empty)
+    pub group_sets: Vec<Vec<usize>>,
+    /// Material files (`mtllib` statements), with their line
+    pub libraries: Vec<(String, usize)>,
+    /// Materials used by the faces, in order of first use
+    pub materials: Vec<MeshMaterial>,
+    /// Problems that did not prevent reading the file (ignored statements...)
+    pub warnings: Vec<ParseError>,
+}
+
+impl Mesh {
+    /// Index of an object
+    pub fn object_index(&self, name: &str) -> Result<usize, String> {
+        find_name(name, &self.objects, "object")
+    }
+
+    /// Index of a group
+    pub fn group_index(&self, name: &str) -> Result<usize, String> {
+        find_name(name, &self.groups, "group")
+    }
+
+    /// Triangles of the faces in one of the given objects and one of the given groups
+    /// (every face when no object or group is given), with their material (the default
+    /// material when it is not defined, with the face's color if it has one)
+    pub fn triangles(&self, objects: &[usize], groups: &[usize]) -> Vec<Triangle> {
+        self.faces
+            .iter()
+            .filter(|face| objects.is_empty() || face.object.is_some_and(|o| objects.contains(&o)))
+            .filter(|face| {
+                groups.is_empty()
+                    || self.group_sets[face.groups]
+                        .iter()
+                        .any(|g| groups.contains(g))
+            })
+            .map(|face| {
+                let [a, b, c] = face.vertices.map(|i| self.vertices[i]);
+                let material = match (face.material, face.color) {
+                    (Some(m), _) => self.materials[m].material.unwrap_or_default(),
+                    (None, Some(color)) => Material {
+                        color,
+                        ..Material::default()
+                    },
+                    (None, None) => Material::default(),
+                };
+                // Triangle points go clockwise, so that the normal faces the front
+                Triangle::new(a, c, b, material)
+            })
+            .collect()
+    }
+
+    /// Merges the vertices closer than a tolerance, removing the faces this collapses
+    pub fn weld(&mut self, tolerance: f64) {
+        // Vertices are looked for in the cells of a grid around each vertex
+        let cell = if tolerance > 0.0 { tolerance } else { 1.0 };
+        let mut grid: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+        let mut welded: Vec<Vect3> = vec![];
+        let mut indices = vec![];
+        for &point in &self.vertices {
+            let [x, y, z] = point.to_arr().map(|c| (c / cell).floor() as i64);
+            let mut neighbors = (-1..=1).flat_map(|dx| {
+                (-1..=1).flat_map(move |dy| (-1..=1).map(move |dz| [x + dx, y + dy, z + dz]))
+            });
+            let found = neighbors.find_map(|key| {
+                grid.get(&key)?
+                    .iter()
+                    .copied()
+                    .find(|&i| (welded[i] - point).norm() <= tolerance)
+            });
+            let index = found.unwrap_or_else(|| {
+                welded.push(point);
+                grid.entry([x, y, z]).or_default().push(welded.len() - 1);
+                welded.len() - 1
+            });
+            indices.push(index);
+        }
+        self.vertices = welded;
+        self.faces.retain_mut(|face| {
+            face.vertices = face.vertices.map(|v| indices[v]);
+            let [a, b, c] = face.vertices;
+            a != b && b != c && c != a
+        });
+    }
+}
+
+/// Index of a name in a list of a mesh's names
+fn find_name(name: &str, list: &[String], kind: &str) -> Result<usize, String> {
+    list.iter().position(|n| n == name).ok_or_else(|| {
+        let mut message = format!("the mesh has no {} `{}`", kind, name);
+        match closest_name(name, list.iter().map(String::as_str)) {
+            Some(closest) => message += &format!(", did you mean `{}`?", closest),
+            None if list.is_empty() => message += &format!(" (it has no {}s)", kind),
+            None => message += &format!(" (its {}s are {})", kind, list.join(", ")),
+        }
+        message
+    })
+}
+
+/// Word of an .obj or .mtl line, with its column
+pub struct Word<'a> {
+    pub text: &'a str,
+    pub column: usize,
+}
+
+/// Splits a line into words, without its comment
+pub fn words(line: &str) -> Vec<Word<'_>> {
+    let line = line.split('#').next().unwrap_or("");
+    let mut words = vec![];
+    let mut start: Option<(usize, usize)> = None;
+    for (column, (i, c)) in line.char_indices().enumerate() {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some((i, column + 1)),
+            (true, Some((first, first_column))) => {
+                words.push(Word {
+                    text: &line[first..i],
+                    column: first_column,
+                });
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some((first, column)) = start {
+        words.push(Word {
+            text: &line[first..],
+            column,
+        });
+    }
+    words
+}
+
+/// Statements of an .obj or .mtl file, with their line
+///
+/// Lines ending with a backslash go on on the next line
+pub fn statements(source: &str) -> Vec<(String, usize)> {
+    let mut statements = vec![];
+    let mut statement = String::new();
+    let mut statement_line = 1;
+    for (i, line) in source.lines().enumerate() {
+        if statement.is_empty() {
+            statement_line = i + 1;
+        }
+        match line.strip_suffix('\\') {
+            Some(start) => {
+                statement += start;
+                statement.push(' ');
+            }
+            None => {
+                statement += line;
+                statements.push((mem::take(&mut statement), statement_line));
+            }
+        }
+    }
+    if !statement.is_empty() {
+        statements.push((statement, statement_line));
+    }
+    statements
+}
+
+/// Reads between min and max numbers, giving the column and message of an error
+pub fn numbers(
+    args: &[Word],
+    min: usize,
+    max: usize,
+    column: usize,
+) -> Result<Vec<f64>, (usize, String)> {
+    if args.len() < min || args.len() > max {
+        let expected = if min == max {
+            min.to_string()
+        } else {
+            format!("{} to {}", min, max)
+        };
+        return Err((
+            column,
+            format!("expected {} numbers, found {}", expected, args.len()),
+        ));
+    }
+    args.iter()
+        .map(|arg| match arg.text.parse::<f64>() {
+            Ok(n) if n.is_finite() => Ok(n),
+            _ => Err((
+                arg.column,
+                format!("expected a number, found `{}`", arg.text),
+            )),
+        })
+        .collect()
+}
+
+/// Reads an .obj file
+///
+/// Faces with more than three vertices are triangulated, and vertex indices may be
+/// negative (counting back from the last vertex) and have texture and normal indices
+/// (`v/vt/vn`, `v//vn`). The materials of the faces are named, their `mtllib` files
+/// being read separately. Other statements are ignored with a warning. Every error of the
+/// file is returned
+pub fn read_obj(source: &str, file_name: &str) -> Result<Mesh, ParseErrors> {
+    let mut reader = ObjReader {
+        file_name,
+        mesh: Mesh {
+            group_sets: vec![vec![]],
+            ..Mesh::default()
+        },
+        texture_count: 0,
+        normal_count: 0,
+        object: None,
+        groups: 0,
+        material: None,
+        ignored: vec![],
+        errors: vec![],
+    };
+
+    for (statement, line) in statements(source) {
+        reader.statement(&statement, line);
+    }
+
+    for (keyword, line, count) in reader.ignored {
+        reader.mesh.warnings.push(ParseError::new(
+            file_name,
+            line,
+            1,
+            format!(
+                "`{}` statements are not supported, {} ignored",
+                keyword, count
+            ),
+        ));
+    }
+    if !reader.errors.is_empty() {
+        return Err(ParseErrors(reader.errors));
+    }
+    Ok(reader.mesh)
+}
+
+/// .obj reader state
+struct ObjReader<'a> {
+    file_name: &'a str,
+    mesh: Mesh,
+    texture_count: usize,
+    normal_count: usize,
+    /// Current object, group set and material
+    object: Option<usize>,
+    groups: usize,
+    material: Option<usize>,
+    /// Ignored statements, with the line of the first one and their count
+    ignored: Vec<(String, usize, usize)>,
+    errors: Vec<ParseError>,
+}
+
+impl ObjReader<'_> {
+    fn error(&mut self, line: usize, column: usize, message: String) {
+        self.errors
+            .push(ParseError::new(self.file_name, line, column, message));
+    }
+
+    /// Reads a statement
+    fn statement(&mut self, text: &str, line: usize) {
+        let words = words(text);
+        let Some(keyword) = words.first() else {
+            return;
+        };
+        let args = &words[1..];
+        match keyword.text {
+            "v" => {
+                if let Some(v) = self.numbers(args, 3, 4, line, keyword.column) {
+                    self.mesh.vertices.push(Vect3::new(v[0], v[1], v[2]));
+                }
+            }
+            "vt" => {
+                if self.numbers(args, 1, 3, line, keyword.column).is_some() {
+                    self.texture_count += 1;
+                }
+            }
+            "vn" => {
+                if self.numbers(args, 3, 3, line, keyword.column).is_some() {
+                    self.normal_count += 1;
+                }
+            }
+            "f" => self.face(args, line, keyword.column),
+            "o" => {
+                let name = join(args);
+                self.object = Some(match self.mesh.objects.iter().position(|o| *o == name) {
+                    Some(index) => index,
+                    None => {
+                        self.mesh.objects.push(name);
+                        self.mesh.objects.len() - 1
+                    }
+                });
+            }
+            "g" => {
+                let mut set = vec![];
+                for arg in args {
+                    let index = match self.mesh.groups.iter().position(|g| g == arg.text) {
+                        Some(index) => index,
+                        None => {
+                            self.mesh.groups.push(arg.text.to_string());
+                            self.mesh.groups.len() - 1
+                        }
+                    };
+                    set.push(index);
+                }
+                self.groups = match self.mesh.group_sets.iter().position(|s| *s == set) {
+                    Some(index) => index,
+                    None => {
+                        self.mesh.group_sets.push(set);
+                        self.mesh.group_sets.len() - 1
+                    }
+                };
+            }
+            "mtllib" => {
+                for arg in args {
+                    self.mesh.libraries.push((arg.text.to_string(), line));
+                }
+            }
+            "usemtl" => {
+                let name = join(args);
+                self.material = Some(
+                    match self.mesh.materials.iter().position(|m| m.name == name) {
+                        Some(index) => index,
+                        None => {
+                            self.mesh.materials.push(MeshMaterial {
+                                name,
+                                line,
+                                material: None,
+                            });
+                            self.mesh.materials.len() - 1
+                        }
+                    },
+                );
+            }
+            keyword => match self.ignored.iter_mut().find(|(k, _, _)| k == keyword) {
+                Some((_, _, count)) => *count += 1,
+                None => self.ignored.push((keyword.to_string(), line, 1)),
+            },
+        }
+    }
+
+    /// Reads between min and max numbers
+    fn numbers(
+        &mut self,
+        args: &[Word],
+        min: usize,
+        max: usize,
+        line: usize,
+        column: usize,
+    ) -> Option<Vec<f64>> {
+        numbers(args, min, max, column)
+            .map_err(|(column, message)| self.error(line, column, message))
+            .ok()
+    }
+
+    /// Reads a face, triangulating it
+    fn face(&mut self, args: &[Word], line: usize, column: usize) {
+        if args.len() < 3 {
+            self.error(
+                line,
+                column,
+                format!("face has {} vertices, at least 3 are needed", args.len()),
+            );
+            return;
+        }
+        let mut polygon = vec![];
+        for arg in args {
+            match self.face_vertex(arg) {
+                Ok(vertex) => polygon.push(vertex),
+                Err(message) => {
+                    self.error(line, arg.column, message);
+                    return;
+                }
+            }
+        }
+
+        let points: Vec<Vect3> = polygon.iter().map(|&i| self.mesh.vertices[i]).collect();
+        for [a, b, c] in triangulate(&points) {
+            self.mesh.faces.push(Face {
+                vertices: [polygon[a], polygon[b], polygon[c]],
+                object: self.object,
+                groups: self.groups,
+                material: self.material,
+                color: None,
+            });
+        }
+    }
+
+    /// Reads a face vertex (`v`, `v/vt`, `v//vn` or `v/vt/vn`), giving its vertex index
+    fn face_vertex(&self, word: &Word) -> Result<usize, String> {
+        let parts: Vec<&str> = word.text.split('/').collect();
+        if parts.len() > 3 {
+            return Err(format!("invalid face vertex `{}`", word.text));
+        }
+        let counts = [
+            ("vertex", "vertices", self.mesh.vertices.len()),
+            (
+                "texture coordinate",
+                "texture coordinates",
+                self.texture_count,
+            ),
+            ("normal", "normals", self.normal_count),
+        ];
+        let mut vertex = 0;
+        for (i, (part, (name, plural, count))) in parts.iter().zip(counts).enumerate() {
+            if part.is_empty() && i > 0 {
+                continue;
+            }
+            let index = part
+                .parse::<i64>()
+                .map_err(|_| format!("invalid face vertex `{}`", word.text))?;
+            let resolved = match index {
+                0 => None,
+                index if index > 0 => Some(index as usize - 1).filter(|&i| i < count),
+                index => count.checked_sub(index.unsigned_abs() as usize),
+            };
+            match resolved {
+                Some(resolved) if i == 0 => vertex = resolved,
+                Some(_) => {}
+                None => {
+                    return Err(format!(
+                        "face uses {} {}, but {} {} are defined",
+                        name, index, count, plural
+                    ))
+                }
+            }
+        }
+        Ok(vertex)
+    }
+}
+
+/// Text of words, separated by spaces
+fn join(words: &[Word]) -> String {
+    words
+        .iter()
+        .map(|w| w.text)
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// Splits a polygon into triangles of the same winding (indices of its points), by ear
+/// clipping so that concave polygons are split correctly
+///
+/// A fan from the first point is used when the polygon is degenerate
+pub fn triangulate(points: &[Vect3]) -> Vec<[usize; 3]> {
+    let count = points.len();
+    let fan = || (1..count - 1).map(|i| [0, i, i + 1]).collect();
+    if count == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    // Newell normal, to project the polygon on its plane
+    let mut normal = Vect3::ZERO;
+    for i in 0..count {
+        let (p, q) = (points[i], points[(i + 1) % count]);
+        normal = normal
+            + Vect3::new(
+                (p.y() - q.y()) * (p.z() + q.z()),
+                (p.z() - q.z()) * (p.x() + q.x()),
+                (p.x() - q.x()) * (p.y() + q.y()),
+            );
+    }
+    if normal.norm() < 1e-12 {
+        return fan();
+    }
+    let axis_u = if normal.x().abs() > 0.9 * normal.norm() {
+        Vect3::UP
+    } else {
+        Vect3::RIGHT
+    };
+    let u = axis_u.prod(normal).normalize();
+    let v = normal.prod(u).normalize();
+    let flat: Vec<(f64, f64)> = points.iter().map(|p| (p.dot(&u), p.dot(&v))).collect();
+    let area: f64 = (0..count)
+        .map(|i| {
+            let (a, b) = (flat[i], flat[(i + 1) % count]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum();
+    let orientation = area.signum();
+
+    let cross = |a: (f64, f64), b: (f64, f64), c: (f64, f64)| {
+        ((b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)) * orientation
+    };
+    let mut remaining: Vec<usize> = (0..count).collect();
+    let mut triangles = vec![];
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let ear = (0..n).find(|&i| {
+            let (a, b, c) = (
+                remaining[(i + n - 1) % n],
+                remaining[i],
+                remaining[(i + 1) % n],
+            );
+            cross(flat[a], flat[b], flat[c]) > 0.0
+                && remaining.iter().all(|&p| {
+                    [a, b, c].contains(&p)
+                        || cross(flat[a], flat[b], flat[p]) < 0.0
+                        || cross(flat[b], flat[c], flat[p]) < 0.0
+                        || cross(flat[c], flat[a], flat[p]) < 0.0
+                })
+        });
+        let Some(i) = ear else {
+            return fan();
+        };
+        triangles.push([
+            remaining[(i + n - 1) % n],
+            remaining[i],
+            remaining[(i + 1) % n],
+        ]);
+        remaining.remove(i);
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
+
+#[cfg(test)]
+mod tests_mesh {
+    use super::{read_obj, triangulate};
+    use crate::mods::position::Vect3;
+
+    /// Messages of the errors of an .obj file, with their line and column
+    fn errors(source: &str) -> Vec<(usize, usize, String)> {
+        match read_obj(source, "a.obj") {
+            Ok(_) => vec![],
+            Err(errors) => errors
+                .0
+                .into_iter()
+                .map(|e| (e.line, e.column, e.message))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn faces() {
+        let mesh = read_obj(
+            "v 0 0 0\nv 1 0 0\nv 1 1 0 # comment\nv 0 1 0\nvt 0 0\nvn 0 0 1\nf 1/1/1 2//1 3/1 -1\nf -4 -3 \\\n -2\n",
+            "a.obj",
+        )
+        .unwrap();
+
+        let faces: Vec<[usize; 3]> = mesh.faces.iter().map(|f| f.vertices).collect();
+        assert_eq!(faces, vec![[3, 0, 1], [1, 2, 3], [0, 1, 2]]);
+        assert!(mesh.warnings.is_empty());
+    }
+
+    #[test]
+    fn concave() {
+        // An arrow, whose fan from the first point would go outside
+        let points =
+            [(0.0, 0.0), (2.0, 1.0), (0.0, 2.0), (1.0, 1.0)].map(|(x, y)| Vect3::new(x, y, 0.0));
+        let triangles = triangulate(&points);
+
+        assert_eq!(triangles.len(), 2);
+        assert!(!triangles.contains(&[0, 1, 2]));
+        let area: f64 = triangles
+            .iter()
+            .map(|[a, b, c]| {
+                (points[*b] - points[*a])
+                    .prod(points[*c] - points[*a])
+                    .norm()
+                    / 2.0
+            })
+            .sum();
+        assert!((area - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn groups() {
+        let mesh = read_obj(
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\no body\ng left side\nf 1 2 3\ng right\nf 1 2 3\no wheel\nf 1 2 3\ns off\nl 1 2\ns 1\n",
+            "a.obj",
+        )
+        .unwrap();
+
+        let body = mesh.object_index("body").unwrap();
+        let wheel = mesh.object_index("wheel").unwrap();
+        let side = mesh.group_index("side").unwrap();
+        let right = mesh.group_index("right").unwrap();
+        assert_eq!(mesh.triangles(&[], &[]).len(), 3);
+        assert_eq!(mesh.triangles(&[body], &[]).len(), 2);
+        assert_eq!(mesh.triangles(&[], &[side]).len(), 1);
+        assert_eq!(mesh.triangles(&[wheel], &[right]).len(), 1);
+        assert_eq!(mesh.triangles(&[body, wheel], &[side, right]).len(), 3);
+        assert_eq!(
+            mesh.group_index("rigth"),
+            Err(String::from(
+                "the mesh has no group `rigth`, did you mean `right`?"
+            ))
+        );
+
+        let warnings: Vec<String> = mesh.warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            warnings,
+            vec![
+                "a.obj:11:1: `s` statements are not supported, 2 ignored",
+                "a.obj:12:1: `l` statements are not supported, 1 ignored"
+            ]
+        );
+    }
+
+    #[test]
+    fn obj_errors() {
+        assert_eq!(
+            errors("v 0 0 0\nv 1 0 0\nf 1 2 3\nf 1 2\nv 1 x 0\nf 1 2 -3\nf 1/1 2 1\n"),
+            vec![
+                (
+                    3,
+                    7,
+                    String::from("face uses vertex 3, but 2 vertices are defined")
+                ),
+                (
+                    4,
+                    1,
+                    String::from("face has 2 vertices, at least 3 are needed")
+                ),
+                (5, 5, String::from("expected a number, found `x`")),
+                (
+                    6,
+                    7,
+                    String::from("face uses vertex -3, but 2 vertices are defined")
+                ),
+                (
+                    7,
+                    3,
+                    String::from(
+                        "face uses texture coordinate 1, but 0 texture coordinates are defined"
+                    )
+                ),
+            ]
+        );
+    }
+}
@@ -1,11 +1,29 @@
+pub mod animation;
+pub mod buffer;
+pub mod builder;
+pub mod check;
+pub mod checkpoint;
 pub mod color;
 pub mod config;
+pub mod document;
+pub mod expr;
 pub mod funcs;
 pub mod image;
+pub mod lexer;
 pub mod material;
+pub mod mesh;
+pub mod mtl;
+pub mod network;
 pub mod objs;
+pub mod parse_error;
 pub mod parser;
+pub mod ply;
 pub mod position;
 pub mod random;
 pub mod ray;
 pub mod render;
+pub mod report;
+pub mod stats;
+pub mod stl;
+pub mod tile;
+pub mod writer;
@@ -0,0 +1,260 @@
+use super::{
+    color::ColorRBG,
+    material::Material,
+    mesh::{numbers, statements, words, Word},
+    parse_error::{ParseError, ParseErrors},
+};
+
+/// Materials of an .mtl file
+#[derive(Debug, Clone, Default)]
+pub struct MaterialLibrary {
+    /// Materials, in order of definition
+    pub materials: Vec<(String, Material)>,
+    /// Problems that did not prevent reading the file (textures, transparency...)
+    pub warnings: Vec<ParseError>,
+}
+
+impl MaterialLibrary {
+    /// Material of a name (its first definition)
+    pub fn get(&self, name: &str) -> Option<Material> {
+        self.materials
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, material)| *material)
+    }
+}
+
+/// Material of an .mtl file, as written
+struct MtlMaterial {
+    name: String,
+    diffuse: ColorRBG,
+    specular: ColorRBG,
+    emission: ColorRBG,
+    exponent: f64,
+    /// Opacity (`d`, or 1 - `Tr`), with its line
+    opacity: Option<(f64, usize)>,
+}
+
+impl MtlMaterial {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            diffuse: ColorRBG::WHITE,
+            specular: ColorRBG::BLACK,
+            emission: ColorRBG::BLACK,
+            exponent: 0.0,
+            opacity: None,
+        }
+    }
+
+    /// rbpt material: the largest component of the specular and emitted colors gives
+    /// the specular probability and the emission strength
+    fn material(&self) -> Material {
+        let (specular_color, specular_prob) = normalize(self.specular);
+        let (emission_color, emission_strength) = normalize(self.emission);
+        // Phong exponent to smoothness, through the matching Beckmann roughness
+        let smoothness = 1.0 - (2.0 / (self.exponent.max(0.0) + 2.0)).sqrt();
+        Material::new(
+            self.diffuse,
+            emission_color,
+            specular_color,
+            emission_strength,
+            smoothness,
+            specular_prob.min(1.0),
+        )
+    }
+}
+
+/// Color with a largest component of 1, and its largest component
+fn normalize(color: ColorRBG) -> (ColorRBG, f64) {
+    let max = color.max_component();
+    if max > 0.0 {
+        ((1.0 / max) * color, max)
+    } else {
+        (ColorRBG::BLACK, 0.0)
+    }
+}
+
+/// Reads a color (one number giving a gray, or three)
+fn color(args: &[Word], column: usize) -> Result<ColorRBG, (usize, String)> {
+    match numbers(args, 1, 3, column)?.as_slice() {
+        [v] => Ok(ColorRBG::new(*v, *v, *v)),
+        [r, g, b] => Ok(ColorRBG::new(*r, *g, *b)),
+        values => Err((
+            column,
+            format!("expected 1 or 3 numbers, found {}", values.len()),
+        )),
+    }
+}
+
+/// Reads a single number
+fn number(args: &[Word], column: usize) -> Result<f64, (usize, String)> {
+    Ok(numbers(args, 1, 1, column)?[0])
+}
+
+/// Reads an .mtl file
+///
+/// `Kd`, `Ks`, `Ke` and `Ns` give the rbpt materials. Textures and transparency are not
+/// supported, and are ignored with a warning like other statements. Every error of the
+/// file is returned
+pub fn read_mtl(source: &str, file_name: &str) -> Result<MaterialLibrary, ParseErrors> {
+    let mut materials: Vec<MtlMaterial> = vec![];
+    let mut errors = vec![];
+    let mut warnings = vec![];
+    // Ignored statements, with the line of the first one and their count
+    let mut ignored: Vec<(String, usize, usize)> = vec![];
+
+    for (statement, line) in statements(source) {
+        let words = words(&statement);
+        let Some(keyword) = words.first() else {
+            continue;
+        };
+        let args = &words[1..];
+        let result = match keyword.text {
+            "newmtl" => {
+                let name = args.iter().map(|w| w.text).collect::<Vec<&str>>().join(" ");
+                materials.push(MtlMaterial::new(name));
+                Ok(())
+            }
+            "Kd" | "Ks" | "Ke" | "Ns" | "d" | "Tr" | "Ni" => match materials.last_mut() {
+                Some(material) => {
+                    let column = keyword.column;
+                    match keyword.text {
+                        "Kd" => color(args, column).map(|c| material.diffuse = c),
+                        "Ks" => color(args, column).map(|c| material.specular = c),
+                        "Ke" => color(args, column).map(|c| material.emission = c),
+                        "Ns" => number(args, column).map(|n| material.exponent = n),
+                        "d" => number(args, column).map(|n| material.opacity = Some((n, line))),
+                        "Tr" => {
+                            number(args, column).map(|n| material.opacity = Some((1.0 - n, line)))
+                        }
+                        // The index of refraction has no effect without transparency
+                        _ => number(args, column).map(|_| ()),
+                    }
+                }
+                None => Err((
+                    keyword.column,
+                    format!("`{}` before the first `newmtl` statement", keyword.text),
+                )),
+            },
+            // The ambient color and illumination model have no meaning for a path tracer
+            "Ka" | "illum" => Ok(()),
+            keyword => {
+                match ignored.iter_mut().find(|(k, _, _)| k == keyword) {
+                    Some((_, _, count)) => *count += 1,
+                    None => ignored.push((keyword.to_string(), line, 1)),
+                }
+                Ok(())
+            }
+        };
+        if let Err((column, message)) = result {
+            errors.push(ParseError::new(file_name, line, column, message));
+        }
+    }
+
+    for material in &materials {
+        if let Some((opacity, line)) = material.opacity.filter(|(opacity, _)| *opacity < 1.0) {
+            warnings.push(ParseError::new(
+                file_name,
+                line,
+                1,
+                format!(
+                    "material `{}` is transparent (opacity {}), it is rendered opaque",
+                    material.name, opacity
+                ),
+            ));
+        }
+    }
+    for (keyword, line, count) in ignored {
+        let message = if keyword.starts_with("map_") || keyword == "bump" {
+            format!(
+                "textures are not supported, {} `{}` ignored (the material colors are used)",
+                count, keyword
+            )
+        } else {
+            format!(
+                "`{}` statements are not supported, {} ignored",
+                keyword, count
+            )
+        };
+        warnings.push(ParseError::new(file_name, line, 1, message));
+    }
+    if !errors.is_empty() {
+        return Err(ParseErrors(errors));
+    }
+    Ok(MaterialLibrary {
+        materials: materials
+            .iter()
+            .map(|material| (material.name.clone(), material.material()))
+            .collect(),
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests_mtl {
+    use super::read_mtl;
+    use crate::mods::color::ColorRBG;
+
+    #[test]
+    fn materials() {
+        let library = read_mtl(
+            "# Exported\nnewmtl red paint\nKa 1 1 1\nKd 0.8 0.1 0.1\nKs 0.5\nNs 48\nillum 2\n\nnewmtl lamp\nKe 4 2 0\nd 0.5\nmap_Kd lamp.png\n",
+            "a.mtl",
+        )
+        .unwrap();
+
+        let red = library.get("red paint").unwrap();
+        assert_eq!(red.color, ColorRBG::new(0.8, 0.1, 0.1));
+        assert_eq!(red.specular_color, ColorRBG::WHITE);
+        assert_eq!(red.specular_prob, 0.5);
+        assert!((red.smoothness - 0.8).abs() < 1e-12);
+
+        let lamp = library.get("lamp").unwrap();
+        assert_eq!(lamp.color, ColorRBG::WHITE);
+        assert_eq!(lamp.emission_color, ColorRBG::new(1.0, 0.5, 0.0));
+        assert_eq!(lamp.emission_strengh, 4.0);
+        assert_eq!(lamp.specular_prob, 0.0);
+
+        let warnings: Vec<(usize, String)> = library
+            .warnings
+            .into_iter()
+            .map(|w| (w.line, w.message))
+            .collect();
+        assert_eq!(
+            warnings,
+            vec![
+                (
+                    11,
+                    "material `lamp` is transparent (opacity 0.5), it is rendered opaque"
+                        .to_string()
+                ),
+                (
+                    12,
+                    "textures are not supported, 1 `map_Kd` ignored (the material colors are used)"
+                        .to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn mtl_errors() {
+        let errors: Vec<(usize, usize, String)> =
+            read_mtl("Kd 1 1 1\nnewmtl a\nKd 1 1\nNs high\n", "a.mtl")
+                .unwrap_err()
+                .0
+                .into_iter()
+                .map(|e| (e.line, e.column, e.message))
+                .collect();
+
+        assert_eq!(
+            errors,
+            vec![
+                (1, 1, "`Kd` before the first `newmtl` statement".to_string()),
+                (3, 1, "expected 1 or 3 numbers, found 2".to_string()),
+                (4, 4, "expected a number, found `high`".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,512 @@
+use std::{
+    error::Error,
+    io::{prelude::*, BufReader, BufWriter},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use super::{
+    buffer::AccBuffer,
+    checkpoint::{read_checkpoint, write_checkpoint, CheckpointInfo},
+    parser::{FileAccess, Parser},
+    render::SEED,
+    report::{QuietReporter, Reporter},
+};
+
+/// Job request signature (protocol version 1)
+static JOB_MAGIC: &[u8; 8] = b"RBPTJOB1";
+
+/// Error response signature, a successful response being a checkpoint
+static ERROR_MAGIC: &[u8; 8] = b"RBPTERR1";
+
+/// Maximum size of a scene sent to a worker
+static MAX_SCENE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Maximum number of pixels of a job's image (4K UHD), each taking about 100 bytes
+static MAX_PIXELS: u64 = 3840 * 2160;
+
+/// Maximum number of iterations of a job
+static MAX_ITERATIONS: usize = 1_000_000;
+
+/// Maximum number of bounces of a job, rays being traced recursively
+static MAX_BOUNCES: u32 = 1000;
+
+/// Maximum number of jobs a worker renders at the same time, others being refused
+static MAX_CONCURRENT_JOBS: usize = 4;
+
+/// Time a connection may wait to send or receive data
+static IO_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Time a client waits for the samples of a job
+static JOB_TIMEOUT: Duration = Duration::from_secs(4 * 3600);
+
+/// Number of jobs each worker gets on average, so that faster workers can take more
+static JOBS_PER_WORKER: usize = 4;
+
+/// Render job: a range of iterations of a scene, sent to a worker
+#[derive(Debug, Clone, PartialEq)]
+pub struct Job {
+    pub scene: String,
+    pub iterations: Range<usize>,
+    pub max_bounces: u32,
+    pub resolution: (u32, u32),
+}
+
+impl Job {
+    /// Writes job request
+    pub fn write(&self, writer: &mut impl Write) -> Result<(), Box<dyn Error>> {
+        writer.write_all(JOB_MAGIC)?;
+        writer.write_all(&(self.iterations.start as u64).to_le_bytes())?;
+        writer.write_all(&(self.iterations.end as u64).to_le_bytes())?;
+        writer.write_all(&self.max_bounces.to_le_bytes())?;
+        writer.write_all(&self.resolution.0.to_le_bytes())?;
+        writer.write_all(&self.resolution.1.to_le_bytes())?;
+        write_string(writer, &self.scene)?;
+        Ok(())
+    }
+
+    /// Reads job request
+    pub fn read(reader: &mut impl Read) -> Result<Job, Box<dyn Error>> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != JOB_MAGIC {
+            return Err("not a render job".into());
+        }
+        let start = read_u64(reader)? as usize;
+        let end = read_u64(reader)? as usize;
+        let max_bounces = read_u32(reader)?;
+        let resolution = (read_u32(reader)?, read_u32(reader)?);
+        // Jobs come from any machine, they are checked before anything is allocated
+        if start >= end {
+            return Err("empty iteration range".into());
+        }
+        if end > MAX_ITERATIONS {
+            return Err(format!("more than {} iterations", MAX_ITERATIONS).into());
+        }
+        if max_bounces > MAX_BOUNCES {
+            return Err(format!("more than {} max bounces", MAX_BOUNCES).into());
+        }
+        let pixels = resolution.0 as u64 * resolution.1 as u64;
+        if pixels == 0 || pixels > MAX_PIXELS {
+            return Err(format!(
+                "invalid resolution {}x{} (at most {} pixels)",
+                resolution.0, resolution.1, MAX_PIXELS
+            )
+            .into());
+        }
+        let scene = read_string(reader)?;
+
+        Ok(Job {
+            scene,
+            iterations: start..end,
+            max_bounces,
+            resolution,
+        })
+    }
+
+    /// Renders the job, giving the accumulated samples
+    ///
+    /// The scene comes from another machine: it may not include files, and its mesh files
+    /// are read in mesh_dir (none being read without it)
+    pub fn render(
+        &self,
+        mesh_dir: Option<&Path>,
+    ) -> Result<(CheckpointInfo, AccBuffer), Box<dyn Error>> {
+        // The scene was checked by the client, which may have allowed undefined materials
+        // The worker reports its jobs, not their parsing and rendering
+        let mut parser = Parser::from_source(&self.scene);
+        parser.set_lenient(true);
+        parser.set_file_access(FileAccess::MeshDir(mesh_dir.map(Path::to_path_buf)));
+        parser.set_reporter(Arc::new(QuietReporter));
+        let mut scene = parser.parse_scene()?;
+        scene.set_reporter(Arc::new(QuietReporter));
+        scene.set_first_iteration(self.iterations.start);
+        scene.render(self.iterations.end, self.max_bounces, self.resolution);
+
+        let info = CheckpointInfo {
+            scene_hash: parser.get_source_hash(),
+            seed: SEED,
+            max_bounces: self.max_bounces,
+            first_iteration: self.iterations.start,
+            next_iteration: self.iterations.end,
+        };
+        let acc_buffer = scene.get_acc_buffer().ok_or("nothing was rendered")?;
+        Ok((info, acc_buffer.clone()))
+    }
+}
+
+/// Accepts render jobs forever, each connection being handled in its own thread
+///    address: address to listen on ("127.0.0.1" for this machine only, "0.0.0.0" for every network)
+///    port: port to listen on
+///    mesh_dir: directory the mesh files of the jobs are read in (none being read without it)
+///    reporter: receives the jobs and their errors
+pub fn serve(
+    address: &str,
+    port: u16,
+    mesh_dir: Option<PathBuf>,
+    reporter: Arc<dyn Reporter>,
+) -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::bind((address, port))?;
+    reporter.info(&format!(
+        "Waiting for render jobs on {}",
+        listener.local_addr()?
+    ));
+    let mesh_dir = Arc::new(mesh_dir);
+    let running = Arc::new(AtomicUsize::new(0));
+
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let peer = stream
+            .peer_addr()
+            .map_or(String::from("unknown"), |a| a.to_string());
+        if running.fetch_add(1, Ordering::SeqCst) >= MAX_CONCURRENT_JOBS {
+            running.fetch_sub(1, Ordering::SeqCst);
+            reporter.warning(&format!("Job from {} refused : too many jobs", peer));
+            let _ = stream
+                .set_write_timeout(Some(IO_TIMEOUT))
+                .map_err(|e| e.into())
+                .and_then(|_| write_error(&stream, "the worker is busy"));
+            continue;
+        }
+        let (mesh_dir, running, reporter) = (mesh_dir.clone(), running.clone(), reporter.clone());
+        thread::spawn(move || {
+            if let Err(e) = handle_connection(stream, mesh_dir.as_deref(), reporter.as_ref()) {
+                reporter.warning(&format!("Job from {} failed : {}", peer, e));
+            }
+            running.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+    Ok(())
+}
+
+/// Reads a job, renders it and sends back the samples (or the error)
+///    stream: connection of the client
+///    mesh_dir: directory the mesh files of the job are read in (none being read without it)
+///    reporter: receives the job
+pub fn handle_connection(
+    stream: TcpStream,
+    mesh_dir: Option<&Path>,
+    reporter: &dyn Reporter,
+) -> Result<(), Box<dyn Error>> {
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    let result = Job::read(&mut BufReader::new(&stream)).and_then(|job| {
+        reporter.info(&format!(
+            "Job from {} : iterations {}..{}",
+            stream.peer_addr()?,
+            job.iterations.start,
+            job.iterations.end
+        ));
+        job.render(mesh_dir)
+    });
+
+    match &result {
+        Ok((info, acc_buffer)) => {
+            let mut writer = BufWriter::new(&stream);
+            write_checkpoint(&mut writer, info, acc_buffer)?;
+            writer.flush()?;
+        }
+        Err(e) => write_error(&stream, &e.to_string())?,
+    }
+    result.map(|_| ())
+}
+
+/// Sends an error response
+fn write_error(stream: &TcpStream, message: &str) -> Result<(), Box<dyn Error>> {
+    let mut writer = BufWriter::new(stream);
+    writer.write_all(ERROR_MAGIC)?;
+    write_string(&mut writer, message)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Sends a job to a worker ("host:port") and waits for its samples
+pub fn request_job(worker: &str, job: &Job) -> Result<(CheckpointInfo, AccBuffer), Box<dyn Error>> {
+    let address = worker
+        .to_socket_addrs()?
+        .next()
+        .ok_or(format!("unknown address {}", worker))?;
+    let stream = TcpStream::connect_timeout(&address, IO_TIMEOUT)?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    stream.set_read_timeout(Some(JOB_TIMEOUT))?;
+    let mut writer = BufWriter::new(&stream);
+    job.write(&mut writer)?;
+    writer.flush()?;
+
+    let mut reader = BufReader::new(&stream);
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic == ERROR_MAGIC {
+        return Err(read_string(&mut reader)?.into());
+    }
+    read_checkpoint(&mut magic.as_slice().chain(reader))
+}
+
+/// Splits the iterations of a scene between workers and merges their samples
+///
+/// A worker that fails is not given more jobs, its job going to the other workers
+pub fn render_on_workers(
+    scene: &str,
+    scene_hash: u64,
+    render_iterations: usize,
+    max_bounces: u32,
+    resolution: (u32, u32),
+    workers: &[String],
+    reporter: &dyn Reporter,
+) -> Result<AccBuffer, Box<dyn Error>> {
+    let job_size = (render_iterations / (workers.len() * JOBS_PER_WORKER)).max(1);
+    let mut ranges: Vec<Range<usize>> = (0..render_iterations)
+        .step_by(job_size)
+        .map(|start| start..(start + job_size).min(render_iterations))
+        .collect();
+    ranges.reverse();
+
+    let queue = Mutex::new(ranges);
+    let merged = Mutex::new(AccBuffer::new(resolution.0 as usize, resolution.1 as usize));
+    let mismatch: Mutex<Option<String>> = Mutex::new(None);
+
+    thread::scope(|s| {
+        for worker in workers {
+            let (queue, merged, mismatch) = (&queue, &merged, &mismatch);
+            s.spawn(move || loop {
+                let Some(iterations) = queue.lock().unwrap().pop() else {
+                    break;
+                };
+                let job = Job {
+                    scene: scene.to_string(),
+                    iterations: iterations.clone(),
+                    max_bounces,
+                    resolution,
+                };
+
+                let error = match request_job(worker, &job) {
+                    Ok((info, _)) if info.scene_hash != scene_hash => {
+                        let message = format!("{} has different mesh files", worker);
+                        *mismatch.lock().unwrap() = Some(message.clone());
+                        message
+                    }
+                    Ok((_, acc_buffer))
+                        if (acc_buffer.get_width(), acc_buffer.get_height())
+                            != (resolution.0 as usize, resolution.1 as usize) =>
+                    {
+                        String::from("wrong resolution")
+                    }
+                    Ok((_, acc_buffer)) => {
+                        merged.lock().unwrap().merge(&acc_buffer);
+                        reporter.info(&format!(
+                            "{} rendered iterations {}..{}",
+                            worker, iterations.start, iterations.end
+                        ));
+                        continue;
+                    }
+                    Err(e) => e.to_string(),
+                };
+                reporter.warning(&format!("Worker {} failed : {}", worker, error));
+                queue.lock().unwrap().push(iterations);
+                break;
+            });
+        }
+    });
+
+    if let Some(message) = mismatch.into_inner().unwrap() {
+        return Err(message.into());
+    }
+    let left = queue.into_inner().unwrap();
+    if !left.is_empty() {
+        return Err(format!("no worker left to render {} jobs", left.len()).into());
+    }
+    Ok(merged.into_inner().unwrap())
+}
+
+fn write_string(writer: &mut impl Write, string: &str) -> Result<(), Box<dyn Error>> {
+    writer.write_all(&(string.len() as u64).to_le_bytes())?;
+    writer.write_all(string.as_bytes())?;
+    Ok(())
+}
+
+fn read_string(reader: &mut impl Read) -> Result<String, Box<dyn Error>> {
+    let len = read_u64(reader)?;
+    if len > MAX_SCENE_SIZE {
+        return Err("message too long".into());
+    }
+    let mut bytes = vec![0u8; len as usize];
+    reader.read_exact(&mut bytes)?;
+    Ok(String::from_utf8(bytes)?)
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32, Box<dyn Error>> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> Result<u64, Box<dyn Error>> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests_network {
+    use std::{net::TcpListener, thread};
+
+    use super::{handle_connection, render_on_workers, request_job, Job};
+    use crate::mods::{parser::Parser, report::QuietReporter};
+
+    static SCENE: &str = "camera {
+    position: 0.0 0.0 5.0
+    rotation: 1.0 0.0 0.0 0.0
+    focal_length: 5.0
+    fov: 40.0
+}
+material {
+    name: light
+    color: 1.0 1.0 1.0
+    emission_color: 1.0 0.5 0.5
+    specular_color: 1.0 1.0 1.0
+    emission_strength: 1.0
+    smoothness: 0.5
+    specular_prob: 0.5
+}
+sphere {
+    position: 0.0 0.0 0.0
+    radius: 1.0
+    mat: light
+}
+";
+
+    /// Starts a worker on localhost handling a given number of connections
+    fn start_worker(connections: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || {
+            for stream in listener.incoming().take(connections) {
+                let _ = handle_connection(stream.unwrap(), None, &QuietReporter);
+            }
+        });
+        address
+    }
+
+    fn job(iterations: std::ops::Range<usize>) -> Job {
+        Job {
+            scene: SCENE.to_string(),
+            iterations,
+            max_bounces: 3,
+            resolution: (8, 6),
+        }
+    }
+
+    #[test]
+    fn job_round_trip() {
+        let job = job(2..5);
+        let mut bytes = vec![];
+        job.write(&mut bytes).unwrap();
+
+        assert_eq!(Job::read(&mut bytes.as_slice()).unwrap(), job);
+    }
+
+    #[test]
+    fn job_limits() {
+        let error = |job: Job| {
+            let mut bytes = vec![];
+            job.write(&mut bytes).unwrap();
+            Job::read(&mut bytes.as_slice()).unwrap_err().to_string()
+        };
+
+        assert_eq!(error(job(5..5)), "empty iteration range");
+        assert_eq!(error(job(0..usize::MAX)), "more than 1000000 iterations");
+        assert_eq!(
+            error(Job {
+                max_bounces: u32::MAX,
+                ..job(0..1)
+            }),
+            "more than 1000 max bounces"
+        );
+        assert_eq!(
+            error(Job {
+                resolution: (u32::MAX, 2),
+                ..job(0..1)
+            }),
+            "invalid resolution 4294967295x2 (at most 8294400 pixels)"
+        );
+        assert_eq!(
+            error(Job {
+                resolution: (5000, 5000),
+                ..job(0..1)
+            }),
+            "invalid resolution 5000x5000 (at most 8294400 pixels)"
+        );
+    }
+
+    #[test]
+    fn job_files() {
+        let worker = start_worker(2);
+        let error = |line: &str| {
+            let job = Job {
+                scene: format!("{}{}\n", SCENE, line),
+                ..job(0..1)
+            };
+            request_job(&worker, &job).unwrap_err().to_string()
+        };
+
+        assert!(error("include \"other.rtp\"").contains("includes are not allowed here"));
+        assert!(error("mesh {\n    obj_file: \"/etc/passwd\"\n}")
+            .contains("mesh files are not allowed here"));
+    }
+
+    #[test]
+    fn remote_job() {
+        let worker = start_worker(1);
+        let (info, acc_buffer) = request_job(&worker, &job(2..5)).unwrap();
+        let (local_info, local_buffer) = job(2..5).render(None).unwrap();
+
+        assert_eq!(info, local_info);
+        assert_eq!(acc_buffer.get(4, 3).samples, 3);
+        assert_eq!(acc_buffer.get(4, 3).mean(), local_buffer.get(4, 3).mean());
+    }
+
+    #[test]
+    fn workers() {
+        let mut parser = Parser::from_source(SCENE);
+        parser.parse_scene().unwrap();
+        let workers = vec![start_worker(8), start_worker(8)];
+        let acc_buffer = render_on_workers(
+            SCENE,
+            parser.get_source_hash(),
+            6,
+            3,
+            (8, 6),
+            &workers,
+            &QuietReporter,
+        )
+        .unwrap();
+
+        assert_eq!(acc_buffer.get(0, 0).samples, 6);
+        assert_eq!(acc_buffer.get(7, 5).samples, 6);
+    }
+
+    #[test]
+    fn failing_worker() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let dead_worker = listener.local_addr().unwrap().to_string();
+        drop(listener);
+        let workers = vec![dead_worker, start_worker(8)];
+        let mut parser = Parser::from_source(SCENE);
+        parser.parse_scene().unwrap();
+        let hash = parser.get_source_hash();
+
+        let acc_buffer =
+            render_on_workers(SCENE, hash, 4, 3, (8, 6), &workers, &QuietReporter).unwrap();
+        assert_eq!(acc_buffer.get(3, 3).samples, 4);
+        assert!(
+            render_on_workers(SCENE, hash, 4, 3, (8, 6), &workers[..1], &QuietReporter).is_err()
+        );
+    }
+}
@@ -1,10 +1,13 @@
 use core::f64;
+use std::ops::Range;
 
 use super::{
     funcs::solve_quadratic,
     image::ImageRGB,
     material::Material,
+    mesh::MeshFormat,
     position::{Angle, Quat, Transform, Vect3},
+    random::LCG,
     ray::{Intersection, Ray},
 };
 
@@ -12,6 +15,8 @@ use super::{
 #[derive(Clone)]
 pub struct Camera {
     pub transform: Transform,
+    pub end_transform: Option<Transform>,
+    shutter: (f64, f64),
     focal: f64,
     fov: Angle,
     pub image: ImageRGB,
@@ -22,6 +27,8 @@ impl Camera {
     pub fn new(position: Vect3, rotation: Quat, focal: f64, fov: Angle, image: ImageRGB) -> Camera {
         Camera {
             transform: Transform::new(position, rotation),
+            end_transform: None,
+            shutter: (0.0, 0.0),
             focal,
             fov,
             image,
@@ -39,17 +46,70 @@ impl Camera {
     ) -> Camera {
         Camera {
             transform: Transform::new(position, rotation),
+            end_transform: None,
+            shutter: (0.0, 0.0),
             focal,
             fov,
             image: ImageRGB::new(w, h),
         }
     }
 
+    /// Get focal length
+    pub fn get_focal(&self) -> f64 {
+        self.focal
+    }
+
+    /// Get field of view
+    pub fn get_fov(&self) -> Angle {
+        self.fov
+    }
+
     /// Set Image resolution
     pub fn set_image_resolution(&mut self, w: u32, h: u32) {
         self.image = ImageRGB::new(w, h);
     }
 
+    /// Set Camera transform at the end of the frame (time = 1)
+    pub fn set_motion(&mut self, end_transform: Transform) {
+        self.end_transform = Some(end_transform);
+    }
+
+    /// Set shutter open and close times (between 0 and 1)
+    pub fn set_shutter(&mut self, open: f64, close: f64) {
+        self.shutter = (open.min(close), open.max(close));
+    }
+
+    /// Get shutter open and close times
+    pub fn get_shutter(&self) -> (f64, f64) {
+        self.shutter
+    }
+
+    /// Is the camera moving during the shutter interval
+    pub fn is_moving(&self) -> bool {
+        self.end_transform.is_some() && self.shutter.0 != self.shutter.1
+    }
+
+    /// Get a random time in the shutter interval
+    ///
+    /// No random number is drawn when the shutter is instantaneous
+    #[inline]
+    pub fn sample_time(&self, randomizer: &mut LCG) -> f64 {
+        let (open, close) = self.shutter;
+        if open == close {
+            open
+        } else {
+            open + (close - open) * randomizer.next_f64()
+        }
+    }
+
+    /// Get Camera transform at a given time
+    pub fn transform_at(&self, time: f64) -> Transform {
+        match self.end_transform {
+            Some(end) => self.transform.interpolate(&end, time),
+            None => self.transform,
+        }
+    }
+
     /// Get ray direction for a given pixel
     pub fn get_ray_direction(
         &self,
@@ -69,6 +129,7 @@ impl Camera {
 /// Sphere implementation
 pub struct Sphere {
     pub transform: Transform,
+    pub end_transform: Option<Transform>,
     pub radius: f64,
     material: Material,
 }
@@ -78,10 +139,25 @@ impl Sphere {
     pub fn new(position: Vect3, radius: f64, material: Material) -> Sphere {
         Sphere {
             transform: Transform::new(position, Quat::identity()),
+            end_transform: None,
             radius,
             material,
         }
     }
+
+    /// Set Sphere transform at the end of the frame (time = 1)
+    pub fn set_motion(&mut self, end_transform: Transform) {
+        self.end_transform = Some(end_transform);
+    }
+
+    /// Get Sphere position at a given time
+    #[inline]
+    pub fn position_at(&self, time: f64) -> Vect3 {
+        match self.end_transform {
+            Some(end) => self.transform.interpolate(&end, time).get_pos(),
+            None => self.transform.get_pos(),
+        }
+    }
 }
 
 impl Sphere {
@@ -89,7 +165,7 @@ impl Sphere {
     pub fn intersect(&self, ray: &Ray, min_distance: f64) -> Option<Intersection> {
         let rd = ray.get_dir();
         let rs = ray.get_start();
-        let pos = self.transform.get_pos();
+        let pos = self.position_at(ray.get_time());
         let rs_pos = rs - pos;
 
         let a = rd * rd;
@@ -118,6 +194,11 @@ impl Sphere {
     pub fn get_mat(&self) -> &Material {
         &self.material
     }
+
+    /// Set Sphere's material
+    pub fn set_material(&mut self, material: Material) {
+        self.material = material;
+    }
 }
 
 /// Plane implementation
@@ -169,15 +250,57 @@ impl Plane {
     pub fn get_mat(&self) -> &Material {
         &self.material
     }
+
+    /// Set Plane's material
+    pub fn set_material(&mut self, material: Material) {
+        self.material = material;
+    }
+
+    /// Get a point of the Plane
+    pub fn get_point(&self) -> Vect3 {
+        self.point
+    }
+
+    /// Get Plane's normal (normalized)
+    pub fn get_normal(&self) -> Vect3 {
+        self.normal
+    }
+}
+
+/// Rigid motion of an object between the start (time = 0) and the end (time = 1) of a frame
+///
+/// Like meshes, points are moved by position then rotated. Rotations are normalized
+#[derive(Debug, Clone, Copy)]
+pub struct Motion {
+    start: Transform,
+    end: Transform,
+}
+
+impl Motion {
+    /// New Motion constructor
+    pub fn new(start: Transform, end: Transform) -> Motion {
+        Motion {
+            start: Transform::new(start.get_pos(), start.get_rot().normalize()),
+            end: Transform::new(end.get_pos(), end.get_rot().normalize()),
+        }
+    }
+
+    /// Get transform at a given time
+    #[inline]
+    pub fn at(&self, time: f64) -> Transform {
+        self.start.interpolate(&self.end, time)
+    }
 }
 
 /// Triangle implementation
+#[derive(Clone)]
 pub struct Triangle {
     point_1: Vect3,
     normal: Vect3,
     vect_1: Vect3,
     vect_2: Vect3,
     material: Material,
+    motion: Option<Box<Motion>>,
 }
 
 impl Triangle {
@@ -192,6 +315,7 @@ impl Triangle {
             vect_1,
             vect_2,
             material,
+            motion: None,
         }
     }
 
@@ -200,11 +324,67 @@ impl Triangle {
         &self.material
     }
 
+    /// Get Triangle's points (at rest, for a moving Triangle)
+    pub fn points(&self) -> [Vect3; 3] {
+        [
+            self.point_1,
+            self.point_1 + self.vect_1,
+            self.point_1 + self.vect_2,
+        ]
+    }
+
+    /// Get Triangle's points at a given time
+    pub fn points_at(&self, time: f64) -> [Vect3; 3] {
+        match &self.motion {
+            None => self.points(),
+            Some(motion) => {
+                let transform = motion.at(time);
+                self.points()
+                    .map(|p| transform.get_rot().rotate(p + transform.get_pos()))
+            }
+        }
+    }
+
+    /// Get Triangle's area
+    pub fn area(&self) -> f64 {
+        self.vect_1.prod(self.vect_2).norm() / 2.0
+    }
+
+    /// Whether the Triangle has no area (its normal not being defined)
+    pub fn is_degenerate(&self) -> bool {
+        self.vect_1.prod(self.vect_2).norm() < 1e-12
+    }
+
     /// Triangle instersector
     pub fn intersect(&self, ray: &Ray, min_distance: f64) -> Option<Intersection> {
         let rd = ray.get_dir();
         let rs = ray.get_start();
 
+        match &self.motion {
+            None => {
+                let t = self.hit_distance(rs, rd, min_distance)?;
+                let point = rs + rd * t;
+                Some(Intersection::new(t, self.material, point, self.normal))
+            }
+            Some(motion) => {
+                // Moves the ray in the triangle's space instead of moving the triangle
+                let transform = motion.at(ray.get_time());
+                let rotation = transform.get_rot();
+                let inverse = rotation.conjugate();
+                let local_rs = inverse.rotate(rs) - transform.get_pos();
+                let local_rd = inverse.rotate(rd);
+
+                let t = self.hit_distance(local_rs, local_rd, min_distance)?;
+                let point = rs + rd * t;
+                let normal = rotation.rotate(self.normal);
+                Some(Intersection::new(t, self.material, point, normal))
+            }
+        }
+    }
+
+    /// Möller–Trumbore distance from ray start to Triangle
+    #[inline]
+    fn hit_distance(&self, rs: Vect3, rd: Vect3, min_distance: f64) -> Option<f64> {
         let edge1 = self.vect_1;
         let edge2 = self.vect_2;
 
@@ -231,19 +411,28 @@ impl Triangle {
 
         let t = f * edge2 * q;
         if t > EPSILON && t < min_distance {
-            let point = rs + rd * t;
-            Some(Intersection::new(t, self.material, point, self.normal))
+            Some(t)
         } else {
             None
         }
     }
 
-    /// Rotate Triangle with quaternion
+    /// Move Triangle by position then rotate it with quaternion (like a Motion)
     pub fn rotate(&mut self, rotation: Quat, position: Vect3) {
         self.point_1 = rotation.rotate(self.point_1 + position);
-        self.normal = rotation.rotate(self.normal + position);
-        self.vect_1 = rotation.rotate(self.vect_1 + position);
-        self.vect_2 = rotation.rotate(self.vect_2 + position);
+        self.normal = rotation.rotate(self.normal);
+        self.vect_1 = rotation.rotate(self.vect_1);
+        self.vect_2 = rotation.rotate(self.vect_2);
+    }
+
+    /// Whether the Triangle has a motion
+    pub fn is_moving(&self) -> bool {
+        self.motion.is_some()
+    }
+
+    /// Set Triangle's motion, the triangle stays in its own space
+    pub fn set_motion(&mut self, motion: Motion) {
+        self.motion = Some(Box::new(motion));
     }
 
     /// Set Triangle's material
@@ -252,6 +441,39 @@ impl Triangle {
     }
 }
 
+/// Block that created some triangles of a scene
+#[derive(Debug, Clone)]
+pub enum TriangleBlock {
+    Cube {
+        position: Vect3,
+        rotation: Quat,
+        size: f64,
+    },
+    Mesh {
+        file: String,
+        format: MeshFormat,
+        /// Objects and groups of the file the triangles were taken from (all when empty)
+        objects: Vec<String>,
+        groups: Vec<String>,
+        /// Scale of the file's coordinates, and distance under which its vertices are merged
+        scale: f64,
+        weld: Option<f64>,
+        position: Vect3,
+        rotation: Quat,
+        /// Position and rotation at the end of the frame
+        motion: Option<(Vect3, Quat)>,
+    },
+}
+
+/// Triangles of a scene created by a cube or mesh block, so that the scene can be written back
+#[derive(Debug, Clone)]
+pub struct TriangleGroup {
+    pub block: TriangleBlock,
+    /// Material of the block, None when the triangles have the materials of the mesh file
+    pub material: Option<Material>,
+    pub triangles: Range<usize>,
+}
+
 /// Creates triangles for a cube
 pub fn create_cube_triangles(
     position: Vect3,
@@ -290,3 +512,67 @@ pub fn create_cube_triangles(
 
     triangles
 }
+
+#[cfg(test)]
+mod tests_triangles {
+    use approx::assert_abs_diff_eq;
+
+    use super::{Motion, Triangle};
+    use crate::mods::{
+        material::Material,
+        position::{Quat, Transform, Vect3},
+        ray::Ray,
+    };
+
+    fn triangle() -> Triangle {
+        Triangle::new(
+            Vect3::new(-1.0, -1.0, 0.0),
+            Vect3::new(1.0, -1.0, 0.0),
+            Vect3::new(0.0, 1.0, 0.0),
+            Material::default(),
+        )
+    }
+
+    #[test]
+    fn rotate_keeps_edges() {
+        let rotation = Quat::from_axis_angle_deg(Vect3::UP, 30.0);
+        let position = Vect3::new(0.3, -0.2, 1.0);
+        let mut rotated = triangle();
+        rotated.rotate(rotation, position);
+        let [p1, p2, p3] = triangle().points().map(|p| rotation.rotate(p + position));
+        let expected = Triangle::new(p1, p2, p3, Material::default());
+
+        for (point, expected_point) in rotated.points().iter().zip(expected.points()) {
+            assert_abs_diff_eq!(*point, expected_point, epsilon = 1e-9);
+        }
+        // Edges are only rotated: the triangle keeps its shape and area
+        assert_abs_diff_eq!(rotated.area(), triangle().area(), epsilon = 1e-9);
+        assert_abs_diff_eq!(rotated.normal, expected.normal, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn motion_matches_static_transform() {
+        let ray = Ray::new_at_time(Vect3::new(0.6, -0.3, 5.0), Vect3::new(0.0, 0.0, -1.0), 0.5);
+        let transforms = [
+            (Quat::identity(), Vect3::ZERO),
+            (
+                Quat::from_axis_angle_deg(Vect3::UP, 30.0),
+                Vect3::new(0.3, -0.2, 1.0),
+            ),
+        ];
+
+        for (rotation, position) in transforms {
+            let mut fixed = triangle();
+            fixed.rotate(rotation, position);
+            let mut moving = triangle();
+            let transform = Transform::new(position, rotation);
+            moving.set_motion(Motion::new(transform, transform));
+
+            let fixed_hit = fixed.intersect(&ray, f64::INFINITY).unwrap();
+            let moving_hit = moving.intersect(&ray, f64::INFINITY).unwrap();
+            assert_abs_diff_eq!(fixed_hit.distance, moving_hit.distance, epsilon = 1e-9);
+            assert_abs_diff_eq!(fixed_hit.point, moving_hit.point, epsilon = 1e-9);
+            assert_abs_diff_eq!(fixed_hit.normal, moving_hit.normal, epsilon = 1e-9);
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use std::{error::Error, fmt};
+
+/// Error found while parsing a file, at a given line and column (starting from 1,
+/// line 0 being the whole file)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    /// New ParseError constructor
+    pub fn new(file: &str, line: usize, column: usize, message: String) -> ParseError {
+        ParseError {
+            file: file.to_string(),
+            line,
+            column,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            return write!(f, "{}: {}", self.file, self.message);
+        }
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.file, self.line, self.column, self.message
+        )
+    }
+}
+
+impl Error for ParseError {}
+
+/// Every error found while parsing a file (the parser going on after an error)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseErrors(pub Vec<ParseError>);
+
+impl ParseErrors {
+    /// Get errors, in the order they were found
+    pub fn errors(&self) -> &[ParseError] {
+        &self.0
+    }
+}
+
+impl fmt::Display for ParseErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let count = self.0.len();
+        write!(
+            f,
+            "{} error{} found while parsing",
+            count,
+            if count == 1 { "" } else { "s" }
+        )?;
+        for error in &self.0 {
+            write!(f, "\n  {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ParseErrors {}
@@ -1,122 +1,228 @@
-use std::{error::Error, fs};
-
-use console::style;
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs, mem,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use super::{
+    animation::{AnimatedValue, Interpolation, Tracks},
     color::ColorRBG,
+    document::{document_tokens, DocumentFormat},
+    expr::{self, Operator, Value},
+    funcs::{closest_name, hash_bytes, FNV_OFFSET},
+    lexer::{self, Span, Token},
     material::Material,
-    objs::{create_cube_triangles, Camera, Plane, Sphere, Triangle},
-    position::{Angle, Quat, Vect3},
+    mesh::{read_obj, Mesh, MeshFormat},
+    mtl::{read_mtl, MaterialLibrary},
+    objs::{
+        create_cube_triangles, Camera, Motion, Plane, Sphere, Triangle, TriangleBlock,
+        TriangleGroup,
+    },
+    parse_error::{ParseError, ParseErrors},
+    ply::read_ply,
+    position::{Angle, Quat, Transform, Vect3},
     render::Scene,
+    report::{QuietReporter, Reporter},
+    stl::read_stl,
 };
 
-/// Tokeneiser enum
+/// Kind of value of an animated property
+#[derive(Debug, Clone, Copy)]
+enum ValueKind {
+    Number,
+    Vect3,
+    Quat,
+    Color,
+}
+
+/// Maximum number of repetitions of a repeat block
+const MAX_REPEAT: usize = 100_000;
+
+/// Animated properties of each block
+const CAMERA_KEYS: [(&str, ValueKind); 4] = [
+    ("position", ValueKind::Vect3),
+    ("rotation", ValueKind::Quat),
+    ("focal_length", ValueKind::Number),
+    ("fov", ValueKind::Number),
+];
+const MATERIAL_KEYS: [(&str, ValueKind); 6] = [
+    ("color", ValueKind::Color),
+    ("emission_color", ValueKind::Color),
+    ("specular_color", ValueKind::Color),
+    ("emission_strength", ValueKind::Number),
+    ("smoothness", ValueKind::Number),
+    ("specular_prob", ValueKind::Number),
+];
+const SPHERE_KEYS: [(&str, ValueKind); 2] = [
+    ("position", ValueKind::Vect3),
+    ("radius", ValueKind::Number),
+];
+const CUBE_KEYS: [(&str, ValueKind); 3] = [
+    ("position", ValueKind::Vect3),
+    ("rotation", ValueKind::Quat),
+    ("size", ValueKind::Number),
+];
+const MESH_KEYS: [(&str, ValueKind); 2] = [
+    ("position", ValueKind::Vect3),
+    ("rotation", ValueKind::Quat),
+];
+
+/// Material block, with the position of its name and whether an object uses it
+struct NamedMaterial {
+    name: String,
+    material: Material,
+    file: String,
+    depth: usize,
+    pos: usize,
+    used: bool,
+}
+
+/// Blocks of a scene, filled by the scene file and the files it includes
+#[derive(Default)]
+struct SceneBlocks {
+    cameras: Vec<Camera>,
+    spheres: Vec<Sphere>,
+    planes: Vec<Plane>,
+    triangles: Vec<Triangle>,
+    triangle_groups: Vec<TriangleGroup>,
+}
+
+impl SceneBlocks {
+    /// Adds the triangles of a cube or mesh block
+    fn add_group(
+        &mut self,
+        (block, material, triangles): (TriangleBlock, Option<Material>, Vec<Triangle>),
+    ) {
+        let start = self.triangles.len();
+        self.triangles.extend(triangles);
+        self.triangle_groups.push(TriangleGroup {
+            block,
+            material,
+            triangles: start..self.triangles.len(),
+        });
+    }
+}
+
+/// Files a parser may read
 #[derive(Debug, Clone, PartialEq)]
-enum Token {
-    Identifier(String),
-    Number(f64),
-    Colon,
-    LBrace,
-    RBrace,
-    Newline,
+pub enum FileAccess {
+    /// Any included or mesh file
+    All,
+    /// No included file, and only the mesh files (and their .mtl files) inside a
+    /// directory, or none: for scenes sent by other machines
+    MeshDir(Option<PathBuf>),
 }
 
 /// Parser implementation
 pub struct Parser {
     tokens: Vec<Token>,
-    materials: Vec<(String, Material)>,
+    spans: Vec<Span>,
+    /// Key path of each token of a JSON or TOML scene (errors giving it instead of a line)
+    paths: Vec<String>,
+    document: bool,
+    end: (usize, usize),
+    file_name: String,
+    lex_errors: Vec<ParseError>,
+    errors: Vec<ParseError>,
+    warnings: Vec<ParseError>,
+    warnings_reported: bool,
+    lenient: bool,
+    lint: bool,
+    meshes: Vec<(String, usize)>,
+    materials: Vec<NamedMaterial>,
+    variables: HashMap<String, Value>,
     pos: usize,
+    frame: f64,
+    mesh_cache: HashMap<String, Mesh>,
+    /// Material files read, by path (None when unreadable)
+    mtl_cache: HashMap<String, Option<MaterialLibrary>>,
+    include_cache: HashMap<PathBuf, String>,
+    /// Files being parsed, from the scene file to this one, to find include cycles
+    include_stack: Vec<PathBuf>,
+    /// Files already included in the scene, each being parsed once
+    included: Vec<PathBuf>,
+    depth: usize,
+    source_hash: u64,
+    file_access: FileAccess,
+    reporter: Arc<dyn Reporter>,
 }
 
 impl Parser {
     /// Build parser from text file
     pub fn build(filename: &str) -> Result<Self, Box<dyn Error>> {
+        let file_content =
+            fs::read_to_string(filename).map_err(|e| format!("{}: {}", filename, e))?;
+        Ok(Self::new(&file_content, filename))
+    }
+
+    /// Build parser from text (mesh paths are relative to the working directory)
+    pub fn from_source(source: &str) -> Self {
+        Self::new(source, "<source>")
+    }
+
+    /// Build parser from text, naming its file in errors
+    pub fn new(source: &str, file_name: &str) -> Self {
         let mut parser = Self {
             tokens: vec![],
+            spans: vec![],
+            paths: vec![],
+            document: false,
+            end: (1, 1),
+            file_name: file_name.to_string(),
+            lex_errors: vec![],
+            errors: vec![],
+            warnings: vec![],
+            warnings_reported: false,
+            lenient: false,
+            lint: false,
+            meshes: vec![],
             materials: vec![],
+            variables: HashMap::new(),
             pos: 0,
+            frame: 0.0,
+            mesh_cache: HashMap::new(),
+            mtl_cache: HashMap::new(),
+            include_cache: HashMap::new(),
+            include_stack: fs::canonicalize(file_name).into_iter().collect(),
+            included: vec![],
+            depth: 0,
+            source_hash: hash_bytes(FNV_OFFSET, source.as_bytes()),
+            file_access: FileAccess::All,
+            reporter: Arc::new(QuietReporter),
         };
-
-        if filename.ends_with(".rtp") {
-            println!(
-                "{} Parsing scene file : {}",
-                style("[1/3]").bold().green(),
-                style(filename).italic().dim()
-            );
-        } else if filename.ends_with(".obj") {
-            println!(
-                "        - Parsing mesh file : {}",
-                style(filename).italic().dim()
-            );
+        match DocumentFormat::from_file_name(file_name) {
+            Some(format) => parser.read_document(source, format),
+            None => parser.tokenize(source),
         }
-        let file_content: &str = &fs::read_to_string(filename)?;
-        parser.tokenize(file_content);
-        Ok(parser)
+        parser
     }
 
-    /// Tokenize text file
+    /// Tokenize text file, keeping the span of each token
     fn tokenize(&mut self, input: &str) {
-        let mut chars = input.chars().peekable();
-
-        while let Some(&ch) = chars.peek() {
-            match ch {
-                c if c.is_whitespace() && c != '\n' => {
-                    chars.next();
-                }
-                '\n' => {
-                    self.tokens.push(Token::Newline);
-                    chars.next();
-                }
-                ':' => {
-                    self.tokens.push(Token::Colon);
-                    chars.next();
-                }
-                '{' => {
-                    self.tokens.push(Token::LBrace);
-                    chars.next();
-                }
-                '}' => {
-                    self.tokens.push(Token::RBrace);
-                    chars.next();
-                }
-                c if c.is_ascii_digit() || c == '-' => {
-                    let mut num_str = String::new();
-                    while let Some(&c) = chars.peek() {
-                        if c.is_ascii_digit() || c == '.' || c == '-' {
-                            num_str.push(c);
-                            chars.next();
-                        } else {
-                            break;
-                        }
-                    }
-                    let value: f64 = num_str.parse().unwrap();
-                    self.tokens.push(Token::Number(value));
-                }
-                c if c.is_alphabetic() => {
-                    let mut ident = String::new();
-                    while let Some(&c) = chars.peek() {
-                        if c.is_alphanumeric() || c == '_' || c == '.' || c == '/' {
-                            ident.push(c);
-                            chars.next();
-                        } else {
-                            break;
-                        }
-                    }
-                    self.tokens.push(Token::Identifier(ident));
-                }
-                '#' => {
-                    for c in chars.by_ref() {
-                        if c == '\n' {
-                            self.tokens.push(Token::Newline);
-                            break;
-                        }
-                    }
-                }
-                _ => {
-                    panic!("Unknown character: {}", ch);
-                }
-            }
-        }
+        let lexed = lexer::tokenize(input);
+        self.lex_errors = lexed
+            .errors
+            .into_iter()
+            .map(|(span, message)| self.error_at_position((span.line, span.column), message))
+            .collect();
+        self.tokens = lexed.tokens;
+        self.spans = lexed.spans;
+        self.end = lexed.end;
+    }
+
+    /// Read a JSON or TOML scene, as the tokens of the .rtp blocks it maps to
+    fn read_document(&mut self, source: &str, format: DocumentFormat) {
+        let document = document_tokens(source, format);
+        self.lex_errors = document
+            .errors
+            .into_iter()
+            .map(|(position, message)| self.error_at_position(position, message))
+            .collect();
+        self.tokens = document.tokens;
+        self.paths = document.paths;
+        self.document = true;
     }
 
     // Iteration functions
@@ -134,103 +240,477 @@ impl Parser {
     }
 
     /// Expect token
-    fn expect(&mut self, expected: &Token) {
-        let token = self.next().expect("Unexpected end of input");
-        if token != expected {
-            panic!("Expected {:?}, got {:?}", expected, token);
+    fn expect(&mut self, expected: &Token) -> Result<(), ParseError> {
+        if self.peek() != Some(expected) {
+            return Err(self.expected(&expected.to_string()));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    /// Records an error and skips the rest of the line (and the blocks it opens),
+    /// stopping before the end of the current block
+    fn recover(&mut self, error: ParseError) {
+        self.errors.push(error);
+        let mut depth = 0;
+        while let Some(token) = self.peek() {
+            match token {
+                Token::LBrace => depth += 1,
+                Token::RBrace if depth == 0 => break,
+                Token::RBrace => depth -= 1,
+                Token::Newline if depth == 0 => {
+                    self.pos += 1;
+                    break;
+                }
+                _ => {}
+            }
+            self.pos += 1;
         }
     }
 
+    // Errors
+
+    /// Error at a line and column
+    fn error_at_position(&self, position: (usize, usize), message: String) -> ParseError {
+        ParseError::new(&self.file_name, position.0, position.1, message)
+    }
+
+    /// Error at a token (or at the end of the file)
+    fn error_at(&self, pos: usize, message: String) -> ParseError {
+        if self.document {
+            let message = match self.paths.get(pos) {
+                Some(path) => format!("{}: {}", path, message),
+                None => message,
+            };
+            return self.error_at_position((0, 0), message);
+        }
+        let position = self
+            .spans
+            .get(pos)
+            .map_or(self.end, |span| (span.line, span.column));
+        self.error_at_position(position, message)
+    }
+
+    /// Error at the current token
+    fn error_here(&self, message: String) -> ParseError {
+        self.error_at(self.pos, message)
+    }
+
+    /// Error giving what was expected instead of the current token
+    /// (right after the previous token at the end of a line)
+    fn expected(&self, expected: &str) -> ParseError {
+        let message = match self.peek() {
+            Some(token) => format!("expected {}, found {}", expected, token),
+            None => format!("expected {}, found end of file", expected),
+        };
+        match (
+            self.peek(),
+            self.pos.checked_sub(1).map(|pos| &self.tokens[pos]),
+        ) {
+            (Some(Token::Newline), Some(previous))
+                if *previous != Token::Newline && !self.document =>
+            {
+                let span = self.spans[self.pos - 1];
+                self.error_at_position((span.end_line, span.end_column), message)
+            }
+            _ => self.error_here(message),
+        }
+    }
+
+    /// Error for an unexpected current token in a block
+    fn unexpected(&self, block: &str) -> ParseError {
+        let found = match self.peek() {
+            Some(token) => token.to_string(),
+            None => String::from("end of file"),
+        };
+        self.error_here(format!("unexpected {} in {} block", found, block))
+    }
+
+    /// Takes the errors found, the tokenizer's errors being put back in line order
+    ///
+    /// The errors of a JSON or TOML scene's schema come first
+    fn take_errors(&mut self) -> ParseErrors {
+        let mut errors = mem::take(&mut self.errors);
+        if self.document {
+            return ParseErrors(self.lex_errors.iter().cloned().chain(errors).collect());
+        }
+        for lex_error in &self.lex_errors {
+            insert_in_order(&mut errors, lex_error.clone());
+        }
+        ParseErrors(errors)
+    }
+
+    /// Set whether undefined materials are warnings (the object getting the default material)
+    /// instead of errors
+    pub fn set_lenient(&mut self, lenient: bool) {
+        self.lenient = lenient;
+    }
+
+    /// Set whether objects that parse but cannot render well (zero radius, degenerate triangles,
+    /// non-unit rotations...) are warnings
+    pub fn set_lint(&mut self, lint: bool) {
+        self.lint = lint;
+    }
+
+    /// Get .obj file and triangle count of each mesh block of the last parsed scene
+    pub fn get_meshes(&self) -> &[(String, usize)] {
+        &self.meshes
+    }
+
+    /// Get warnings of the last parsed scene
+    pub fn get_warnings(&self) -> &[ParseError] {
+        &self.warnings
+    }
+
+    /// Set reporter, also given to the parsed scenes (nothing is reported by default)
+    pub fn set_reporter(&mut self, reporter: Arc<dyn Reporter>) {
+        self.reporter = reporter;
+    }
+
+    /// Set the files the parser may read (any file by default)
+    pub fn set_file_access(&mut self, file_access: FileAccess) {
+        self.file_access = file_access;
+    }
+
+    /// Get hash of the parsed files' content (scene file and loaded meshes)
+    pub fn get_source_hash(&self) -> u64 {
+        self.source_hash
+    }
+
+    /// Set frame at which animated properties are evaluated
+    pub fn set_frame(&mut self, frame: f64) {
+        self.frame = frame;
+    }
+
     // Specific parsers
 
-    /// Parse Scene
-    pub fn parse_scene(&mut self) -> Scene {
-        let mut cameras: Vec<Camera> = vec![];
-        //let mut objects: Vec<Object> = vec![];
-        let mut spheres: Vec<Sphere> = vec![];
-        let mut planes: Vec<Plane> = vec![];
-        let mut triangles: Vec<Triangle> = vec![];
+    /// Parse Scene (from the start of the file, so that it can be parsed for each frame)
+    ///
+    /// Parsing goes on after an error, so that every error of the file is returned.
+    /// Warnings are given to the reporter the first time the scene is parsed
+    pub fn parse_scene(&mut self) -> Result<Scene, ParseErrors> {
+        self.pos = 0;
+        self.materials.clear();
+        self.errors.clear();
+        self.warnings.clear();
+        self.meshes.clear();
+        self.included.clear();
+        self.variables.clear();
+        let mut blocks = SceneBlocks::default();
+        let end = self.tokens.len();
+        self.parse_blocks(&mut blocks, end);
 
-        while let Some(token) = self.peek() {
-            match token {
+        // Materials of the included files are libraries, most of them being unused
+        let unused: Vec<ParseError> = self
+            .materials
+            .iter()
+            .filter(|m| !m.used && m.depth == 0)
+            .map(|m| self.error_at(m.pos, format!("material `{}` is never used", m.name)))
+            .collect();
+        for warning in unused {
+            self.warn(warning);
+        }
+        if !self.warnings_reported {
+            for warning in &self.warnings {
+                self.reporter.warning(&warning.to_string());
+            }
+            self.warnings_reported = true;
+        }
+
+        // A JSON or TOML scene that could not be read has no blocks at all
+        let unreadable = self.document && self.tokens.is_empty() && !self.lex_errors.is_empty();
+        if blocks.cameras.is_empty() && !unreadable {
+            let error = if self.document {
+                self.error_at_position((0, 0), String::from("missing `camera`"))
+            } else {
+                self.expected("a camera block")
+            };
+            self.errors.push(error);
+        }
+        if !self.errors.is_empty() || !self.lex_errors.is_empty() {
+            return Err(self.take_errors());
+        }
+
+        let SceneBlocks {
+            cameras,
+            spheres,
+            planes,
+            triangles,
+            triangle_groups,
+        } = blocks;
+        let mut scene = Scene::new(cameras[0].clone(), spheres, planes, triangles);
+        scene.triangle_groups = triangle_groups;
+        scene.set_reporter(self.reporter.clone());
+        scene.get_info();
+        Ok(scene)
+    }
+
+    /// Parse the blocks of a file until a token (the end of the file, or of a repeat block)
+    fn parse_blocks(&mut self, blocks: &mut SceneBlocks, end: usize) {
+        while self.pos < end {
+            let Some(token) = self.peek() else {
+                break;
+            };
+            let result = match token {
                 Token::Identifier(name) if name == "camera" => {
                     self.next(); // consume identifier
-                    cameras.push(self.parse_camera());
+                    self.parse_camera()
+                        .map(|camera| blocks.cameras.push(camera))
+                }
+                Token::Identifier(name) if name == "include" => {
+                    self.next();
+                    self.parse_include(blocks)
+                }
+                Token::Identifier(name) if name == "let" => {
+                    self.next();
+                    self.parse_let()
+                }
+                Token::Identifier(name) if name == "repeat" => {
+                    self.next();
+                    self.parse_repeat(blocks)
                 }
                 Token::Identifier(name) if name == "material" => {
                     self.next();
-                    let material = self.parse_material();
-                    self.materials.push(material);
+                    self.parse_material()
+                        .map(|material| self.add_material(material))
                 }
                 Token::Identifier(name) if name == "sphere" => {
                     self.next();
-                    spheres.push(self.parse_sphere());
+                    self.parse_sphere()
+                        .map(|sphere| blocks.spheres.push(sphere))
                 }
                 Token::Identifier(name) if name == "plane" => {
                     self.next();
-                    planes.push(self.parse_plane());
+                    self.parse_plane().map(|plane| blocks.planes.push(plane))
                 }
                 Token::Identifier(name) if name == "triangle" => {
                     self.next();
-                    triangles.push(self.parse_triangle());
+                    self.parse_triangle()
+                        .map(|triangle| blocks.triangles.push(triangle))
                 }
                 Token::Identifier(name) if name == "cube" => {
                     self.next();
-                    let cube = self.parse_cube();
-                    for tri in cube {
-                        triangles.push(tri);
-                    }
+                    self.parse_cube().map(|cube| blocks.add_group(cube))
                 }
                 Token::Identifier(name) if name == "mesh" => {
                     self.next();
-                    let mesh_triangles = self.parse_mesh();
-                    for tri in mesh_triangles {
-                        triangles.push(tri);
-                    }
+                    self.parse_mesh().map(|mesh| blocks.add_group(mesh))
                 }
                 Token::Newline => {
                     self.next();
+                    Ok(())
                 }
                 _ => {
-                    panic!("Unexpected token: {:?}", token);
+                    let error = self.expected(
+                        "a let, an include, a repeat or a camera, material, sphere, plane, triangle, cube or mesh block",
+                    );
+                    self.next();
+                    Err(error)
                 }
+            };
+            if let Err(error) = result {
+                self.recover(error);
             }
         }
+    }
 
-        let scene = Scene::new(cameras[0].clone(), spheres, planes, triangles);
-        scene.get_info();
-        scene
+    /// Parse "repeat name in start..end { blocks }", adding the blocks once for each
+    /// value of the variable (from start, by steps of 1, end being excluded)
+    ///
+    /// Errors and warnings of the blocks are only given once
+    fn parse_repeat(&mut self, blocks: &mut SceneBlocks) -> Result<(), ParseError> {
+        let name = match self.peek() {
+            Some(Token::Identifier(name)) if !expr::is_function(name) => name.clone(),
+            _ => return Err(self.expected("a variable name")),
+        };
+        self.pos += 1;
+        self.expect(&Token::Identifier(String::from("in")))?;
+        let start = self.parse_number()?;
+        self.expect(&Token::DotDot)?;
+        let range_end = self.parse_number()?;
+        let count = (range_end - start).ceil().max(0.0);
+        if count > MAX_REPEAT as f64 {
+            return Err(self.error_at(
+                self.pos - 1,
+                format!(
+                    "repeat of {} times, at most {} are allowed",
+                    count, MAX_REPEAT
+                ),
+            ));
+        }
+        self.expect(&Token::LBrace)?;
+
+        let body = self.pos;
+        let mut depth = 0;
+        let end = loop {
+            match self.tokens.get(self.pos) {
+                Some(Token::LBrace) => depth += 1,
+                Some(Token::RBrace) if depth == 0 => break self.pos,
+                Some(Token::RBrace) => depth -= 1,
+                Some(_) => {}
+                None => return Err(self.expected("'}' closing the repeat block")),
+            }
+            self.pos += 1;
+        };
+
+        let previous = self.variables.get(&name).cloned();
+        let (errors, warnings) = (self.errors.len(), self.warnings.len());
+        for index in 0..count as usize {
+            self.variables
+                .insert(name.clone(), Value::Number(start + index as f64));
+            self.pos = body;
+            self.parse_blocks(blocks, end);
+        }
+        self.pos = end + 1;
+        match previous {
+            Some(value) => self.variables.insert(name, value),
+            None => self.variables.remove(&name),
+        };
+        remove_repeated(&mut self.errors, errors);
+        remove_repeated(&mut self.warnings, warnings);
+        Ok(())
+    }
+
+    /// Parse include directive, adding the blocks of the included file
+    ///
+    /// The path is relative to the including file, and each file is included once
+    fn parse_include(&mut self, blocks: &mut SceneBlocks) -> Result<(), ParseError> {
+        let include = self.parse_string()?;
+        if self.file_access != FileAccess::All {
+            return Err(self.error_at(
+                self.pos - 1,
+                format!(
+                    "cannot include `{}`: includes are not allowed here",
+                    include
+                ),
+            ));
+        }
+        let path = match Path::new(&self.file_name).parent() {
+            Some(dir) if self.file_name != "<source>" => dir.join(&include),
+            _ => PathBuf::from(&include),
+        };
+        let file_name = path.to_string_lossy().to_string();
+        let read_error = |parser: &Self, e: std::io::Error| {
+            parser.error_at(
+                parser.pos - 1,
+                format!("cannot read included file `{}`: {}", file_name, e),
+            )
+        };
+        let canonical = fs::canonicalize(&path).map_err(|e| read_error(self, e))?;
+        if let Some(start) = self.include_stack.iter().position(|p| *p == canonical) {
+            let cycle: Vec<String> = self.include_stack[start..]
+                .iter()
+                .chain([&canonical])
+                .map(|p| {
+                    p.file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .to_string()
+                })
+                .collect();
+            return Err(self.error_at(
+                self.pos - 1,
+                format!("include cycle: {}", cycle.join(" -> ")),
+            ));
+        }
+        if self.included.contains(&canonical) {
+            return Ok(());
+        }
+        if !self.include_cache.contains_key(&canonical) {
+            let source = fs::read_to_string(&path).map_err(|e| read_error(self, e))?;
+            self.source_hash = hash_bytes(self.source_hash, source.as_bytes());
+            self.include_cache.insert(canonical.clone(), source);
+        }
+        self.included.push(canonical.clone());
+
+        let mut parser = Parser::new(&self.include_cache[&canonical], &file_name);
+        parser.lenient = self.lenient;
+        parser.lint = self.lint;
+        parser.frame = self.frame;
+        parser.reporter = self.reporter.clone();
+        parser.file_access = self.file_access.clone();
+        parser.depth = self.depth + 1;
+        parser.include_stack = self.include_stack.clone();
+        parser.include_stack.push(canonical);
+        parser.source_hash = self.source_hash;
+        parser.materials = mem::take(&mut self.materials);
+        parser.variables = mem::take(&mut self.variables);
+        parser.mesh_cache = mem::take(&mut self.mesh_cache);
+        parser.mtl_cache = mem::take(&mut self.mtl_cache);
+        parser.include_cache = mem::take(&mut self.include_cache);
+        parser.included = mem::take(&mut self.included);
+
+        let end = parser.tokens.len();
+        parser.parse_blocks(blocks, end);
+
+        self.materials = mem::take(&mut parser.materials);
+        self.variables = mem::take(&mut parser.variables);
+        self.mesh_cache = mem::take(&mut parser.mesh_cache);
+        self.mtl_cache = mem::take(&mut parser.mtl_cache);
+        self.include_cache = mem::take(&mut parser.include_cache);
+        self.included = mem::take(&mut parser.included);
+        self.source_hash = parser.source_hash;
+        self.errors.extend(parser.take_errors().0);
+        self.warnings.append(&mut parser.warnings);
+        self.meshes.append(&mut parser.meshes);
+        Ok(())
     }
 
     /// Parse Camera
-    fn parse_camera(&mut self) -> Camera {
-        self.expect(&Token::LBrace);
+    fn parse_camera(&mut self) -> Result<Camera, ParseError> {
+        self.expect(&Token::LBrace)?;
         let mut position = Vect3::ZERO;
         let mut rotation = Quat::identity();
         let mut focal = 1.0;
         let mut fov = Angle::new(0.0);
+        let mut end_position: Option<Vect3> = None;
+        let mut end_rotation: Option<Quat> = None;
+        let mut shutter = (0.0, 0.0);
+        let mut tracks = Tracks::new();
 
-        while let Some(token) = self.peek() {
-            match token {
+        loop {
+            let Some(token) = self.peek() else {
+                return Err(self.expected("'}' closing the camera block"));
+            };
+            let result = match token {
                 Token::Identifier(name) if name == "position" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    position = self.parse_vect3();
+                    self.parse_property(Self::parse_vect3)
+                        .map(|value| position = value)
                 }
                 Token::Identifier(name) if name == "rotation" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    rotation = self.parse_quat();
+                    self.parse_property(Self::parse_quat)
+                        .map(|value| rotation = value)
+                }
+                Token::Identifier(name) if name == "end_position" => {
+                    self.next();
+                    self.parse_property(Self::parse_vect3)
+                        .map(|value| end_position = Some(value))
+                }
+                Token::Identifier(name) if name == "end_rotation" => {
+                    self.next();
+                    self.parse_property(Self::parse_quat)
+                        .map(|value| end_rotation = Some(value))
+                }
+                Token::Identifier(name) if name == "shutter" => {
+                    self.next();
+                    self.parse_property(|parser| parser.parse_f64_array(2))
+                        .map(|data| shutter = (data[0], data[1]))
+                }
+                Token::Identifier(name) if name == "keyframe" => {
+                    self.next();
+                    self.parse_keyframe(&CAMERA_KEYS, &mut tracks)
                 }
                 Token::Identifier(name) if name == "focal_length" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    focal = self.parse_number();
+                    self.parse_property(Self::parse_number)
+                        .map(|value| focal = value)
                 }
                 Token::Identifier(name) if name == "fov" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    fov = self.parse_angle();
+                    self.parse_property(Self::parse_angle)
+                        .map(|value| fov = value)
                 }
 
                 Token::RBrace => {
@@ -239,61 +719,92 @@ impl Parser {
                 }
                 Token::Newline => {
                     self.next();
+                    Ok(())
                 }
-                _ => panic!("Unexpected token in camera block: {:?}", token),
+                _ => Err(self.unexpected("camera")),
+            };
+            if let Err(error) = result {
+                self.recover(error);
             }
         }
 
-        Camera::build(position, rotation, focal, fov, 160, 90)
+        let frame = self.frame;
+        let end_position = end_position.or(tracks.vect3("position", frame + 1.0));
+        let end_rotation = end_rotation.or(tracks.quat("rotation", frame + 1.0));
+        position = tracks.vect3("position", frame).unwrap_or(position);
+        rotation = tracks.quat("rotation", frame).unwrap_or(rotation);
+        focal = tracks.number("focal_length", frame).unwrap_or(focal);
+        fov = tracks.number("fov", frame).map_or(fov, Angle::from_deg);
+
+        let mut camera = Camera::build(position, rotation, focal, fov, 160, 90);
+        camera.set_shutter(shutter.0, shutter.1);
+        if end_position.is_some() || end_rotation.is_some() {
+            camera.set_motion(Transform::new(
+                end_position.unwrap_or(position),
+                end_rotation.unwrap_or(rotation),
+            ));
+        }
+        Ok(camera)
     }
 
     /// Parse Material
-    fn parse_material(&mut self) -> (String, Material) {
-        self.expect(&Token::LBrace);
+    fn parse_material(&mut self) -> Result<NamedMaterial, ParseError> {
+        let start = self.pos;
+        self.expect(&Token::LBrace)?;
         let mut name = String::new();
+        let mut name_pos = start;
         let mut color = ColorRBG::BLACK;
         let mut emission_color = ColorRBG::BLACK;
         let mut specular_color = ColorRBG::BLACK;
         let mut emission_strength = 0.0;
         let mut smoothness = 0.0;
         let mut specular_prob = 0.0;
+        let mut tracks = Tracks::new();
 
-        while let Some(token) = self.peek() {
-            match token {
+        loop {
+            let Some(token) = self.peek() else {
+                return Err(self.expected("'}' closing the material block"));
+            };
+            let result = match token {
                 Token::Identifier(n) if n == "name" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    name = self.parse_string();
+                    name_pos = self.pos + 1;
+                    self.parse_property(Self::parse_string)
+                        .map(|value| name = value)
                 }
                 Token::Identifier(name) if name == "color" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    color = self.parse_color();
+                    self.parse_property(Self::parse_color)
+                        .map(|value| color = value)
                 }
                 Token::Identifier(name) if name == "emission_color" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    emission_color = self.parse_color();
+                    self.parse_property(Self::parse_color)
+                        .map(|value| emission_color = value)
                 }
                 Token::Identifier(name) if name == "specular_color" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    specular_color = self.parse_color();
+                    self.parse_property(Self::parse_color)
+                        .map(|value| specular_color = value)
                 }
                 Token::Identifier(name) if name == "emission_strength" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    emission_strength = self.parse_number();
+                    self.parse_property(Self::parse_number)
+                        .map(|value| emission_strength = value)
                 }
                 Token::Identifier(name) if name == "smoothness" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    smoothness = self.parse_number();
+                    self.parse_property(Self::parse_number)
+                        .map(|value| smoothness = value)
                 }
                 Token::Identifier(name) if name == "specular_prob" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    specular_prob = self.parse_number();
+                    self.parse_property(Self::parse_number)
+                        .map(|value| specular_prob = value)
+                }
+                Token::Identifier(name) if name == "keyframe" => {
+                    self.next();
+                    self.parse_keyframe(&MATERIAL_KEYS, &mut tracks)
                 }
                 Token::RBrace => {
                     self.next();
@@ -301,14 +812,34 @@ impl Parser {
                 }
                 Token::Newline => {
                     self.next();
+                    Ok(())
                 }
-                _ => panic!("Unexpected token in material block: {:?}", token),
+                _ => Err(self.unexpected("material")),
+            };
+            if let Err(error) = result {
+                self.recover(error);
             }
         }
 
-        (
+        let frame = self.frame;
+        color = tracks.color("color", frame).unwrap_or(color);
+        emission_color = tracks
+            .color("emission_color", frame)
+            .unwrap_or(emission_color);
+        specular_color = tracks
+            .color("specular_color", frame)
+            .unwrap_or(specular_color);
+        emission_strength = tracks
+            .number("emission_strength", frame)
+            .unwrap_or(emission_strength);
+        smoothness = tracks.number("smoothness", frame).unwrap_or(smoothness);
+        specular_prob = tracks
+            .number("specular_prob", frame)
+            .unwrap_or(specular_prob);
+
+        Ok(NamedMaterial {
             name,
-            Material::new(
+            material: Material::new(
                 color,
                 emission_color,
                 specular_color,
@@ -316,32 +847,51 @@ impl Parser {
                 smoothness,
                 specular_prob,
             ),
-        )
+            file: self.file_name.clone(),
+            depth: self.depth,
+            pos: name_pos,
+            used: false,
+        })
     }
 
     /// Parse Sphere
-    fn parse_sphere(&mut self) -> Sphere {
-        self.expect(&Token::LBrace);
+    fn parse_sphere(&mut self) -> Result<Sphere, ParseError> {
+        let start = self.pos;
+        self.expect(&Token::LBrace)?;
         let mut position = Vect3::ZERO;
         let mut radius = 0.0;
-        let mut name = String::new();
+        let mut material = Material::default();
+        let mut end_position: Option<Vect3> = None;
+        let mut tracks = Tracks::new();
 
-        while let Some(token) = self.peek() {
-            match token {
+        loop {
+            let Some(token) = self.peek() else {
+                return Err(self.expected("'}' closing the sphere block"));
+            };
+            let result = match token {
                 Token::Identifier(name) if name == "position" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    position = self.parse_vect3();
+                    self.parse_property(Self::parse_vect3)
+                        .map(|value| position = value)
+                }
+                Token::Identifier(name) if name == "end_position" => {
+                    self.next();
+                    self.parse_property(Self::parse_vect3)
+                        .map(|value| end_position = Some(value))
+                }
+                Token::Identifier(name) if name == "keyframe" => {
+                    self.next();
+                    self.parse_keyframe(&SPHERE_KEYS, &mut tracks)
                 }
                 Token::Identifier(name) if name == "radius" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    radius = self.parse_number();
+                    self.parse_property(Self::parse_number)
+                        .map(|value| radius = value)
                 }
-                Token::Identifier(n) if n == "mat" => {
+                Token::Identifier(name) if name == "mat" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    name = self.parse_string();
+                    self.parse_property(Self::parse_material_name)
+                        .map(|value| material = value)
                 }
                 Token::RBrace => {
                     self.next();
@@ -349,37 +899,57 @@ impl Parser {
                 }
                 Token::Newline => {
                     self.next();
+                    Ok(())
                 }
-                _ => panic!("Unexpected token in sphere block: {:?}", token),
+                _ => Err(self.unexpected("sphere")),
+            };
+            if let Err(error) = result {
+                self.recover(error);
             }
         }
 
-        Sphere::new(position, radius, self.get_material(name))
+        let frame = self.frame;
+        let end_position = end_position.or(tracks.vect3("position", frame + 1.0));
+        position = tracks.vect3("position", frame).unwrap_or(position);
+        radius = tracks.number("radius", frame).unwrap_or(radius);
+        if radius <= 0.0 {
+            self.lint_at(start - 1, format!("sphere radius is {}", radius));
+        }
+
+        let mut sphere = Sphere::new(position, radius, material);
+        if let Some(end_position) = end_position {
+            sphere.set_motion(Transform::new(end_position, Quat::identity()));
+        }
+        Ok(sphere)
     }
 
     /// Parse Plane
-    fn parse_plane(&mut self) -> Plane {
-        self.expect(&Token::LBrace);
+    fn parse_plane(&mut self) -> Result<Plane, ParseError> {
+        let start = self.pos;
+        self.expect(&Token::LBrace)?;
         let mut point = Vect3::ZERO;
         let mut normal = Vect3::ZERO;
-        let mut name = String::new();
+        let mut material = Material::default();
 
-        while let Some(token) = self.peek() {
-            match token {
+        loop {
+            let Some(token) = self.peek() else {
+                return Err(self.expected("'}' closing the plane block"));
+            };
+            let result = match token {
                 Token::Identifier(name) if name == "point" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    point = self.parse_vect3();
+                    self.parse_property(Self::parse_vect3)
+                        .map(|value| point = value)
                 }
                 Token::Identifier(name) if name == "normal" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    normal = self.parse_vect3();
+                    self.parse_property(Self::parse_vect3)
+                        .map(|value| normal = value)
                 }
-                Token::Identifier(n) if n == "mat" => {
+                Token::Identifier(name) if name == "mat" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    name = self.parse_string();
+                    self.parse_property(Self::parse_material_name)
+                        .map(|value| material = value)
                 }
                 Token::RBrace => {
                     self.next();
@@ -387,43 +957,54 @@ impl Parser {
                 }
                 Token::Newline => {
                     self.next();
+                    Ok(())
                 }
-                _ => panic!("Unexpected token in sphere block: {:?}", token),
+                _ => Err(self.unexpected("plane")),
+            };
+            if let Err(error) = result {
+                self.recover(error);
             }
         }
 
-        Plane::new(point, normal, self.get_material(name))
+        if normal.norm() < 1e-12 {
+            self.lint_at(start - 1, String::from("plane normal has a zero length"));
+        }
+        Ok(Plane::new(point, normal, material))
     }
 
     /// Parse Triangle
-    fn parse_triangle(&mut self) -> Triangle {
-        self.expect(&Token::LBrace);
+    fn parse_triangle(&mut self) -> Result<Triangle, ParseError> {
+        let start = self.pos;
+        self.expect(&Token::LBrace)?;
         let mut point_1 = Vect3::ZERO;
         let mut point_2 = Vect3::ZERO;
         let mut point_3 = Vect3::ZERO;
-        let mut name = String::new();
+        let mut material = Material::default();
 
-        while let Some(token) = self.peek() {
-            match token {
+        loop {
+            let Some(token) = self.peek() else {
+                return Err(self.expected("'}' closing the triangle block"));
+            };
+            let result = match token {
                 Token::Identifier(name) if name == "point_1" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    point_1 = self.parse_vect3();
+                    self.parse_property(Self::parse_vect3)
+                        .map(|value| point_1 = value)
                 }
                 Token::Identifier(name) if name == "point_2" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    point_2 = self.parse_vect3();
+                    self.parse_property(Self::parse_vect3)
+                        .map(|value| point_2 = value)
                 }
                 Token::Identifier(name) if name == "point_3" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    point_3 = self.parse_vect3();
+                    self.parse_property(Self::parse_vect3)
+                        .map(|value| point_3 = value)
                 }
-                Token::Identifier(n) if n == "mat" => {
+                Token::Identifier(name) if name == "mat" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    name = self.parse_string();
+                    self.parse_property(Self::parse_material_name)
+                        .map(|value| material = value)
                 }
                 Token::RBrace => {
                     self.next();
@@ -431,43 +1012,65 @@ impl Parser {
                 }
                 Token::Newline => {
                     self.next();
+                    Ok(())
                 }
-                _ => panic!("Unexpected token in sphere block: {:?}", token),
+                _ => Err(self.unexpected("triangle")),
+            };
+            if let Err(error) = result {
+                self.recover(error);
             }
         }
 
-        Triangle::new(point_1, point_2, point_3, self.get_material(name))
+        let triangle = Triangle::new(point_1, point_2, point_3, material);
+        if triangle.is_degenerate() {
+            self.lint_at(
+                start - 1,
+                String::from("degenerate triangle (its points are aligned)"),
+            );
+        }
+        Ok(triangle)
     }
 
     /// Parse Cube
-    fn parse_cube(&mut self) -> Vec<Triangle> {
-        self.expect(&Token::LBrace);
+    fn parse_cube(
+        &mut self,
+    ) -> Result<(TriangleBlock, Option<Material>, Vec<Triangle>), ParseError> {
+        let start = self.pos;
+        self.expect(&Token::LBrace)?;
         let mut position = Vect3::ZERO;
         let mut rotation = Quat::identity();
         let mut size = 0.0;
-        let mut name = String::new();
+        let mut material = Material::default();
+        let mut tracks = Tracks::new();
 
-        while let Some(token) = self.peek() {
-            match token {
+        loop {
+            let Some(token) = self.peek() else {
+                return Err(self.expected("'}' closing the cube block"));
+            };
+            let result = match token {
                 Token::Identifier(name) if name == "position" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    position = self.parse_vect3();
+                    self.parse_property(Self::parse_vect3)
+                        .map(|value| position = value)
                 }
                 Token::Identifier(name) if name == "rotation" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    rotation = self.parse_quat();
+                    self.parse_property(Self::parse_quat)
+                        .map(|value| rotation = value)
                 }
                 Token::Identifier(name) if name == "size" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    size = self.parse_number();
+                    self.parse_property(Self::parse_number)
+                        .map(|value| size = value)
+                }
+                Token::Identifier(name) if name == "keyframe" => {
+                    self.next();
+                    self.parse_keyframe(&CUBE_KEYS, &mut tracks)
                 }
-                Token::Identifier(n) if n == "mat" => {
+                Token::Identifier(name) if name == "mat" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    name = self.parse_string();
+                    self.parse_property(Self::parse_material_name)
+                        .map(|value| material = value)
                 }
                 Token::RBrace => {
                     self.next();
@@ -475,45 +1078,131 @@ impl Parser {
                 }
                 Token::Newline => {
                     self.next();
+                    Ok(())
                 }
-                _ => panic!("Unexpected token in sphere block: {:?}", token),
+                _ => Err(self.unexpected("cube")),
+            };
+            if let Err(error) = result {
+                self.recover(error);
             }
         }
 
-        create_cube_triangles(position, rotation, size, self.get_material(name))
+        let frame = self.frame;
+        position = tracks.vect3("position", frame).unwrap_or(position);
+        rotation = tracks.quat("rotation", frame).unwrap_or(rotation);
+        size = tracks.number("size", frame).unwrap_or(size);
+        if size <= 0.0 {
+            self.lint_at(start - 1, format!("cube size is {}", size));
+        }
+
+        Ok((
+            TriangleBlock::Cube {
+                position,
+                rotation,
+                size,
+            },
+            Some(material),
+            create_cube_triangles(position, rotation, size, material),
+        ))
     }
 
     /// Parse mesh
-    fn parse_mesh(&mut self) -> Vec<Triangle> {
-        self.expect(&Token::LBrace);
+    fn parse_mesh(
+        &mut self,
+    ) -> Result<(TriangleBlock, Option<Material>, Vec<Triangle>), ParseError> {
+        let start = self.pos;
+        self.expect(&Token::LBrace)?;
         let mut position = Vect3::ZERO;
         let mut rotation = Quat::identity();
-        let mut triangles: Vec<Triangle> = vec![];
-        let mut mat_name = String::new();
+        let mut mesh: Option<Mesh> = None;
+        let mut mesh_file = String::new();
+        let mut mesh_format = MeshFormat::Obj;
+        let mut scale = 1.0;
+        let mut weld: Option<f64> = None;
+        let mut objects: Vec<(String, usize)> = vec![];
+        let mut groups: Vec<(String, usize)> = vec![];
+        let mut material: Option<Material> = None;
+        let mut end_position: Option<Vect3> = None;
+        let mut end_rotation: Option<Quat> = None;
+        let mut tracks = Tracks::new();
 
-        while let Some(token) = self.peek() {
-            match token {
+        loop {
+            let Some(token) = self.peek() else {
+                return Err(self.expected("'}' closing the mesh block"));
+            };
+            let result = match token {
                 Token::Identifier(name) if name == "position" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    position = self.parse_vect3();
+                    self.parse_property(Self::parse_vect3)
+                        .map(|value| position = value)
                 }
                 Token::Identifier(name) if name == "rotation" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    rotation = self.parse_quat();
+                    self.parse_property(Self::parse_quat)
+                        .map(|value| rotation = value)
+                }
+                Token::Identifier(name) if name == "end_position" => {
+                    self.next();
+                    self.parse_property(Self::parse_vect3)
+                        .map(|value| end_position = Some(value))
+                }
+                Token::Identifier(name) if name == "end_rotation" => {
+                    self.next();
+                    self.parse_property(Self::parse_quat)
+                        .map(|value| end_rotation = Some(value))
+                }
+                Token::Identifier(name) if name == "keyframe" => {
+                    self.next();
+                    self.parse_keyframe(&MESH_KEYS, &mut tracks)
                 }
                 Token::Identifier(name) if name == "obj_file" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    let file_name = self.parse_string();
-                    let mut obj_parser = Parser::build(&file_name).unwrap();
-                    triangles = obj_parser.parse_obj();
+                    self.parse_mesh_file(MeshFormat::Obj)
+                        .map(|(file_name, loaded)| {
+                            (mesh_file, mesh_format) = (file_name, MeshFormat::Obj);
+                            mesh = Some(loaded)
+                        })
+                }
+                Token::Identifier(name) if name == "ply_file" => {
+                    self.next();
+                    self.parse_mesh_file(MeshFormat::Ply)
+                        .map(|(file_name, loaded)| {
+                            (mesh_file, mesh_format) = (file_name, MeshFormat::Ply);
+                            mesh = Some(loaded)
+                        })
                 }
-                Token::Identifier(n) if n == "mat" => {
+                Token::Identifier(name) if name == "stl_file" => {
                     self.next();
-                    self.expect(&Token::Colon);
-                    mat_name = self.parse_string();
+                    self.parse_mesh_file(MeshFormat::Stl)
+                        .map(|(file_name, loaded)| {
+                            (mesh_file, mesh_format) = (file_name, MeshFormat::Stl);
+                            mesh = Some(loaded)
+                        })
+                }
+                Token::Identifier(name) if name == "scale" => {
+                    self.next();
+                    self.parse_property(Self::parse_number)
+                        .map(|value| scale = value)
+                }
+                Token::Identifier(name) if name == "weld" => {
+                    self.next();
+                    self.parse_property(Self::parse_number)
+                        .map(|value| weld = Some(value))
+                }
+                Token::Identifier(name) if name == "objects" => {
+                    self.next();
+                    self.parse_property(Self::parse_names)
+                        .map(|names| objects.extend(names))
+                }
+                Token::Identifier(name) if name == "groups" => {
+                    self.next();
+                    self.parse_property(Self::parse_names)
+                        .map(|names| groups.extend(names))
+                }
+                Token::Identifier(name) if name == "mat" => {
+                    self.next();
+                    self.parse_property(Self::parse_material_name)
+                        .map(|value| material = Some(value))
                 }
                 Token::RBrace => {
                     self.next();
@@ -521,137 +1210,1253 @@ impl Parser {
                 }
                 Token::Newline => {
                     self.next();
+                    Ok(())
                 }
-                _ => panic!("Unexpected token in camera block: {:?}", token),
+                _ => Err(self.unexpected("mesh")),
+            };
+            if let Err(error) = result {
+                self.recover(error);
             }
         }
 
-        let mat = self.get_material(mat_name);
+        let frame = self.frame;
+        let end_position = end_position.or(tracks.vect3("position", frame + 1.0));
+        let end_rotation = end_rotation.or(tracks.quat("rotation", frame + 1.0));
+        position = tracks.vect3("position", frame).unwrap_or(position);
+        rotation = tracks.quat("rotation", frame).unwrap_or(rotation);
+
+        if scale <= 0.0 {
+            self.lint_at(start - 1, format!("mesh scale is {}", scale));
+        }
+        if let Some(mesh) = &mut mesh {
+            // The tolerance is in the file's units
+            if let Some(tolerance) = weld {
+                mesh.weld(tolerance);
+            }
+            for vertex in mesh.vertices.iter_mut() {
+                *vertex = *vertex * scale;
+            }
+        }
+        let mut triangles = match &mesh {
+            Some(mesh) => {
+                let object_indices = self.find_mesh_names(&objects, |name| mesh.object_index(name));
+                let group_indices = self.find_mesh_names(&groups, |name| mesh.group_index(name));
+                mesh.triangles(&object_indices, &group_indices)
+            }
+            None => vec![],
+        };
+        self.meshes.push((mesh_file.clone(), triangles.len()));
+        let degenerate = triangles.iter().filter(|t| t.is_degenerate()).count();
+        if degenerate > 0 {
+            self.lint_at(
+                start - 1,
+                format!(
+                    "mesh has {} degenerate triangles (out of {})",
+                    degenerate,
+                    triangles.len()
+                ),
+            );
+        }
+
+        let motion = if end_position.is_some() || end_rotation.is_some() {
+            Some(Motion::new(
+                Transform::new(position, rotation),
+                Transform::new(
+                    end_position.unwrap_or(position),
+                    end_rotation.unwrap_or(rotation),
+                ),
+            ))
+        } else {
+            None
+        };
 
         for triangle in triangles.iter_mut() {
-            triangle.rotate(rotation, position);
-            triangle.set_material(mat);
+            match motion {
+                Some(motion) => triangle.set_motion(motion),
+                None => triangle.rotate(rotation, position),
+            }
+            // The mesh's material replaces the ones of its file
+            if let Some(material) = material {
+                triangle.set_material(material);
+            }
         }
 
-        triangles
+        let block = TriangleBlock::Mesh {
+            file: mesh_file,
+            format: mesh_format,
+            scale,
+            weld,
+            objects: objects.into_iter().map(|(name, _)| name).collect(),
+            groups: groups.into_iter().map(|(name, _)| name).collect(),
+            position,
+            rotation,
+            motion: motion.map(|_| {
+                (
+                    end_position.unwrap_or(position),
+                    end_rotation.unwrap_or(rotation),
+                )
+            }),
+        };
+        Ok((block, material, triangles))
     }
 
-    // Small parsers
-
-    /// Parse .obj file
-    pub fn parse_obj(&mut self) -> Vec<Triangle> {
-        let mut vertices: Vec<Vect3> = vec![];
-        let mut triangles: Vec<Triangle> = vec![];
+    /// Parse keyframe block, adding its values to the block's tracks
+    fn parse_keyframe(
+        &mut self,
+        properties: &[(&str, ValueKind)],
+        tracks: &mut Tracks,
+    ) -> Result<(), ParseError> {
+        self.expect(&Token::LBrace)?;
+        let mut frame = 0.0;
+        let mut interpolation = Interpolation::Linear;
+        let mut values: Vec<(String, AnimatedValue)> = vec![];
 
-        while let Some(token) = self.peek() {
-            match token {
-                Token::Identifier(name) if name == "v" => {
+        loop {
+            let Some(token) = self.peek() else {
+                return Err(self.expected("'}' closing the keyframe block"));
+            };
+            let result = match token {
+                Token::Identifier(name) if name == "frame" => {
                     self.next();
-                    vertices.push(self.parse_vect3());
+                    self.parse_property(Self::parse_number)
+                        .map(|value| frame = value)
                 }
-                Token::Identifier(name) if name == "f" => {
+                Token::Identifier(name) if name == "interpolation" => {
                     self.next();
-                    let face = self.parse_face();
-                    let point_1 = vertices[face[0] - 1];
-                    let point_2 = vertices[face[2] - 1];
-                    let point_3 = vertices[face[1] - 1];
-                    triangles.push(Triangle::new(
-                        point_1,
-                        point_2,
-                        point_3,
-                        Material::default(),
-                    ));
+                    self.parse_property(Self::parse_interpolation)
+                        .map(|value| interpolation = value)
+                }
+                Token::Identifier(name) => {
+                    let property = name.clone();
+                    match properties.iter().find(|(p, _)| *p == property) {
+                        Some(&(_, kind)) => {
+                            self.next();
+                            self.parse_property(|parser| parser.parse_animated_value(kind))
+                                .map(|value| values.push((property, value)))
+                        }
+                        None => Err(self.unexpected("keyframe")),
+                    }
+                }
+                Token::RBrace => {
+                    self.next();
+                    break;
                 }
                 Token::Newline => {
                     self.next();
+                    Ok(())
                 }
-                _ => {
-                    panic!("Unexpected token: {:?}", token);
+                _ => Err(self.unexpected("keyframe")),
+            };
+            if let Err(error) = result {
+                self.recover(error);
+            }
+        }
+
+        for (property, value) in values {
+            tracks.add_key(&property, frame, value, interpolation);
+        }
+        Ok(())
+    }
+
+    /// Parse the file property of a mesh block, loading the file
+    fn parse_mesh_file(&mut self, format: MeshFormat) -> Result<(String, Mesh), ParseError> {
+        let file_name = self.parse_property(Self::parse_string)?;
+        let mesh = self.load_mesh(&file_name, format)?;
+        Ok((file_name, mesh))
+    }
+
+    /// Load mesh file, each file being read only once
+    ///
+    /// The errors of the mesh file are added to the scene's errors, and its warnings
+    /// to the scene's warnings. Unlike includes, mesh paths are relative to the working
+    /// directory, whichever file the mesh block is in
+    fn load_mesh(&mut self, file_name: &str, format: MeshFormat) -> Result<Mesh, ParseError> {
+        if let Some(mesh) = self.mesh_cache.get(file_name) {
+            return Ok(mesh.clone());
+        }
+        self.reporter.mesh_parsing(file_name);
+        let bytes = self.read_file(file_name).map_err(|e| {
+            self.error_at(
+                self.pos - 1,
+                format!("cannot read mesh file: {}: {}", file_name, e),
+            )
+        })?;
+        let mesh = match format {
+            MeshFormat::Obj => read_obj(&String::from_utf8_lossy(&bytes), file_name),
+            MeshFormat::Ply => read_ply(&bytes, file_name),
+            MeshFormat::Stl => read_stl(&bytes, file_name),
+        };
+        let mut mesh = match mesh {
+            Ok(mesh) => mesh,
+            Err(errors) => {
+                self.errors.extend(errors.0);
+                return Ok(Mesh::default());
+            }
+        };
+        for warning in &mesh.warnings {
+            self.warn(warning.clone());
+        }
+        self.source_hash = hash_bytes(
+            self.source_hash,
+            &hash_bytes(FNV_OFFSET, &bytes).to_le_bytes(),
+        );
+        self.load_materials(&mut mesh, file_name);
+        self.mesh_cache.insert(file_name.to_string(), mesh.clone());
+        Ok(mesh)
+    }
+
+    /// Reads a mesh or material file, if the parser may read it
+    fn read_file(&self, file_name: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        match &self.file_access {
+            FileAccess::All => Ok(fs::read(file_name)?),
+            FileAccess::MeshDir(None) => Err("mesh files are not allowed here".into()),
+            FileAccess::MeshDir(Some(dir)) => {
+                // Resolving links and `..` first, so that no path leads out of the directory
+                let dir = fs::canonicalize(dir)?;
+                let path = fs::canonicalize(dir.join(file_name))?;
+                if !path.starts_with(&dir) {
+                    return Err("the file is outside of the mesh directory".into());
+                }
+                Ok(fs::read(path)?)
+            }
+        }
+    }
+
+    /// Gives a mesh the materials of its .mtl files (relative to its .obj file), warning
+    /// about unreadable files and undefined materials
+    fn load_materials(&mut self, mesh: &mut Mesh, obj_file: &str) {
+        if mesh.materials.is_empty() {
+            return;
+        }
+        let dir = Path::new(obj_file).parent().unwrap_or(Path::new(""));
+        let mut libraries = vec![];
+        let mut complete = true;
+        for (library, line) in &mesh.libraries {
+            let path = dir.join(library).to_string_lossy().to_string();
+            if !self.mtl_cache.contains_key(&path) {
+                let library = self.read_mtl_file(&path, obj_file, *line);
+                self.mtl_cache.insert(path.clone(), library);
+            }
+            match &self.mtl_cache[&path] {
+                Some(library) => libraries.push(library.clone()),
+                None => complete = false,
+            }
+        }
+        for material in mesh.materials.iter_mut() {
+            material.material = libraries.iter().find_map(|l| l.get(&material.name));
+            if material.material.is_none() && complete {
+                self.warn(ParseError::new(
+                    obj_file,
+                    material.line,
+                    1,
+                    format!(
+                        "material `{}` is not defined in the mesh's .mtl files, the default material is used",
+                        material.name
+                    ),
+                ));
+            }
+        }
+    }
+
+    /// Reads an .mtl file used by an .obj file (None when it cannot be read)
+    fn read_mtl_file(
+        &mut self,
+        path: &str,
+        obj_file: &str,
+        line: usize,
+    ) -> Option<MaterialLibrary> {
+        let bytes = match self.read_file(path) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                self.warn(ParseError::new(
+                    obj_file,
+                    line,
+                    1,
+                    format!("cannot read material file: {}: {}", path, e),
+                ));
+                return None;
+            }
+        };
+        self.source_hash = hash_bytes(
+            self.source_hash,
+            &hash_bytes(FNV_OFFSET, &bytes).to_le_bytes(),
+        );
+        match read_mtl(&String::from_utf8_lossy(&bytes), path) {
+            Ok(library) => {
+                for warning in &library.warnings {
+                    self.warn(warning.clone());
+                }
+                Some(library)
+            }
+            Err(errors) => {
+                self.errors.extend(errors.0);
+                Some(MaterialLibrary::default())
+            }
+        }
+    }
+
+    /// Indices of the objects or groups of a mesh, adding an error for each unknown name
+    fn find_mesh_names(
+        &mut self,
+        names: &[(String, usize)],
+        find: impl Fn(&str) -> Result<usize, String>,
+    ) -> Vec<usize> {
+        let mut indices = vec![];
+        for (name, pos) in names {
+            match find(name) {
+                Ok(index) => indices.push(index),
+                Err(message) => {
+                    let error = self.error_at(*pos, message);
+                    self.errors.push(error);
                 }
             }
         }
+        indices
+    }
 
-        triangles
+    // Small parsers
+
+    /// Parse ": value" of a property
+    fn parse_property<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        self.expect(&Token::Colon)?;
+        parse(self)
     }
 
-    /// Parse f64 array
-    fn parse_f64_array(&mut self, count: usize) -> [f64; 4] {
+    /// Parse value of an animated property
+    fn parse_animated_value(&mut self, kind: ValueKind) -> Result<AnimatedValue, ParseError> {
+        Ok(match kind {
+            ValueKind::Number => AnimatedValue::Number(self.parse_number()?),
+            ValueKind::Vect3 => AnimatedValue::Vect3(self.parse_vect3()?),
+            ValueKind::Quat => AnimatedValue::Quat(self.parse_quat()?),
+            ValueKind::Color => AnimatedValue::Color(self.parse_color()?),
+        })
+    }
+
+    /// Parse f64 array, either as count numbers or as a vector of count
+    fn parse_f64_array(&mut self, count: usize) -> Result<[f64; 4], ParseError> {
+        let start = self.pos;
         let mut result = [0.0; 4];
-        for res in result.iter_mut().take(count) {
-            *res = self.parse_number();
+        match self.parse_expr()? {
+            Value::Vector(vector) if vector.len() == count => {
+                result[..count].copy_from_slice(&vector);
+            }
+            Value::Number(n) => {
+                result[0] = n;
+                for res in result.iter_mut().take(count).skip(1) {
+                    *res = self.parse_number()?;
+                }
+            }
+            vector => {
+                return Err(self.error_at(
+                    start,
+                    format!("expected {} numbers, found {}", count, vector),
+                ))
+            }
         }
-        result
+        Ok(result)
     }
 
     /// Parse Vect3
-    fn parse_vect3(&mut self) -> Vect3 {
-        let data = self.parse_f64_array(3);
-        Vect3::new(data[0], data[1], data[2])
+    fn parse_vect3(&mut self) -> Result<Vect3, ParseError> {
+        let data = self.parse_f64_array(3)?;
+        Ok(Vect3::new(data[0], data[1], data[2]))
     }
 
-    /// Parse obj face
-    fn parse_face(&mut self) -> [usize; 3] {
-        let mut face = [0; 3];
-        for res in face.iter_mut().take(3) {
-            *res = self.parse_int();
+    /// Parse Quat
+    fn parse_quat(&mut self) -> Result<Quat, ParseError> {
+        let start = self.pos;
+        let data = self.parse_f64_array(4)?;
+        let quat = Quat::new(data[0], Vect3::new(data[1], data[2], data[3]));
+        if (quat.norm() - 1.0).abs() > 1e-2 {
+            self.lint_at(
+                start,
+                format!(
+                    "rotation has a norm of {:.3} instead of 1, so it also scales",
+                    quat.norm()
+                ),
+            );
         }
-        face
+        Ok(quat)
     }
 
-    /// Parse Quat
-    fn parse_quat(&mut self) -> Quat {
-        let data = self.parse_f64_array(4);
-        Quat::new(data[0], Vect3::new(data[1], data[2], data[3]))
+    /// Parse f64 number, given by an expression
+    fn parse_number(&mut self) -> Result<f64, ParseError> {
+        let start = self.pos;
+        match self.parse_expr()? {
+            Value::Number(n) => Ok(n),
+            vector => Err(self.error_at(start, format!("expected a number, found {}", vector))),
+        }
+    }
+
+    /// Parse "let name = expression", defining a variable
+    fn parse_let(&mut self) -> Result<(), ParseError> {
+        let name = match self.peek() {
+            Some(Token::Identifier(name)) if !expr::is_function(name) => name.clone(),
+            _ => return Err(self.expected("a variable name")),
+        };
+        self.pos += 1;
+        self.expect(&Token::Equals)?;
+        let value = self.parse_expr()?;
+        self.variables.insert(name, value);
+        Ok(())
     }
 
-    /// Parse f64 number
-    fn parse_number(&mut self) -> f64 {
-        match self.next() {
-            Some(Token::Number(n)) => *n,
-            other => panic!("Expected number, got {:?}", other),
+    /// Parse expression: terms added or subtracted
+    fn parse_expr(&mut self) -> Result<Value, ParseError> {
+        let mut value = self.parse_term()?;
+        while let Some(&Token::Operator(operator @ (Operator::Add | Operator::Sub))) = self.peek() {
+            let pos = self.pos;
+            self.pos += 1;
+            let term = self.parse_term()?;
+            value = value
+                .apply(operator, term)
+                .map_err(|message| self.error_at(pos, message))?;
         }
+        Ok(value)
     }
 
-    /// Parse usize number
-    fn parse_int(&mut self) -> usize {
-        match self.next() {
-            Some(Token::Number(n)) => *n as usize,
-            other => panic!("Expected number, got {:?}", other),
+    /// Parse term: factors multiplied or divided
+    fn parse_term(&mut self) -> Result<Value, ParseError> {
+        let mut value = self.parse_factor()?;
+        while let Some(&Token::Operator(operator @ (Operator::Mul | Operator::Div))) = self.peek() {
+            let pos = self.pos;
+            self.pos += 1;
+            let factor = self.parse_factor()?;
+            value = value
+                .apply(operator, factor)
+                .map_err(|message| self.error_at(pos, message))?;
+        }
+        Ok(value)
+    }
+
+    /// Parse factor: number, boolean (1 or 0), variable, function call, vector literal,
+    /// expression in parentheses, or signed factor
+    fn parse_factor(&mut self) -> Result<Value, ParseError> {
+        let pos = self.pos;
+        match self.peek() {
+            Some(&Token::Number(n)) => {
+                self.pos += 1;
+                Ok(Value::Number(n))
+            }
+            Some(&Token::Bool(b)) => {
+                self.pos += 1;
+                Ok(Value::Number(if b { 1.0 } else { 0.0 }))
+            }
+            Some(Token::Operator(Operator::Sub)) => {
+                self.pos += 1;
+                Ok(self.parse_factor()?.negate())
+            }
+            Some(Token::Operator(Operator::Add)) => {
+                self.pos += 1;
+                self.parse_factor()
+            }
+            Some(Token::LParen) => {
+                self.pos += 1;
+                let value = self.parse_expr()?;
+                self.expect(&Token::RParen)?;
+                Ok(value)
+            }
+            Some(Token::LBracket) => {
+                self.pos += 1;
+                let mut components = vec![self.parse_number()?];
+                while self.peek() == Some(&Token::Comma) {
+                    self.pos += 1;
+                    components.push(self.parse_number()?);
+                }
+                self.expect(&Token::RBracket)?;
+                Ok(Value::Vector(components))
+            }
+            Some(Token::Identifier(name)) if expr::is_function(name) => {
+                let name = name.clone();
+                self.pos += 1;
+                self.expect(&Token::LParen)?;
+                let mut args = vec![self.parse_expr()?];
+                while self.peek() == Some(&Token::Comma) {
+                    self.pos += 1;
+                    args.push(self.parse_expr()?);
+                }
+                self.expect(&Token::RParen)?;
+                expr::call(&name, &args).map_err(|message| self.error_at(pos, message))
+            }
+            Some(Token::Identifier(name)) => {
+                if let Some(value) = self.variables.get(name).cloned().or(expr::constant(name)) {
+                    self.pos += 1;
+                    return Ok(value);
+                }
+                let message = if name.contains('/') {
+                    format!(
+                        "undefined variable `{}` (put spaces around '/' to divide)",
+                        name
+                    )
+                } else if let Some(closest) =
+                    closest_name(name, self.variables.keys().map(String::as_str))
+                {
+                    format!("undefined variable `{}`, did you mean `{}`?", name, closest)
+                } else {
+                    return Err(self.expected("a number"));
+                };
+                Err(self.error_here(message))
+            }
+            _ => Err(self.expected("a number")),
         }
     }
 
     /// Parse ColorRBG
-    fn parse_color(&mut self) -> ColorRBG {
-        let data = self.parse_f64_array(3);
-        ColorRBG::new(data[0], data[1], data[2])
+    fn parse_color(&mut self) -> Result<ColorRBG, ParseError> {
+        let data = self.parse_f64_array(3)?;
+        Ok(ColorRBG::new(data[0], data[1], data[2]))
     }
 
     /// Parse Angle
-    fn parse_angle(&mut self) -> Angle {
-        Angle::from_deg(self.parse_number())
+    fn parse_angle(&mut self) -> Result<Angle, ParseError> {
+        Ok(Angle::from_deg(self.parse_number()?))
     }
 
-    /// Parse String
-    fn parse_string(&mut self) -> String {
-        match self.next() {
-            Some(Token::Identifier(name)) => String::from(name),
-            other => panic!("Expected material name, got {:?}", other),
+    /// Parse one or more names on a line, with their positions
+    fn parse_names(&mut self) -> Result<Vec<(String, usize)>, ParseError> {
+        let mut names = vec![(self.parse_string()?, self.pos - 1)];
+        while let Some(Token::Identifier(_) | Token::Str(_)) = self.peek() {
+            names.push((self.parse_string()?, self.pos - 1));
         }
+        Ok(names)
+    }
+
+    /// Parse String (a name, or a quoted string)
+    fn parse_string(&mut self) -> Result<String, ParseError> {
+        match self.peek() {
+            Some(Token::Identifier(name) | Token::Str(name)) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => Err(self.expected("a name")),
+        }
+    }
+
+    /// Parse interpolation name
+    fn parse_interpolation(&mut self) -> Result<Interpolation, ParseError> {
+        let name = self.parse_string()?;
+        Interpolation::from_name(&name)
+            .ok_or_else(|| self.error_at(self.pos - 1, format!("unknown interpolation `{}`", name)))
+    }
+
+    /// Parse name of a material defined before, giving the material
+    fn parse_material_name(&mut self) -> Result<Material, ParseError> {
+        let name = self.parse_string()?;
+        if let Some(material) = self.materials.iter_mut().find(|m| m.name == name) {
+            material.used = true;
+            return Ok(material.material);
+        }
+
+        let mut message = format!("undefined material `{}`", name);
+        if let Some(closest) = closest_name(&name, self.materials.iter().map(|m| m.name.as_str())) {
+            message += &format!(", did you mean `{}`?", closest);
+        }
+        let error = self.error_at(self.pos - 1, message);
+        if !self.lenient {
+            return Err(error);
+        }
+        self.warn(error);
+        Ok(Material::default())
     }
 
     // Helpers
 
-    /// Get material from its name
-    fn get_material(&self, material_name: String) -> Material {
-        if let Some(material) = self
-            .materials
-            .iter()
-            .find(|(name, _)| *name == *material_name)
-            .map(|(_, mat)| mat)
-        {
-            *material
+    /// Adds a warning, keeping the warnings of each file in line order
+    fn warn(&mut self, warning: ParseError) {
+        insert_in_order(&mut self.warnings, warning);
+    }
+
+    /// Adds a warning at a token, when linting
+    fn lint_at(&mut self, pos: usize, message: String) {
+        if self.lint {
+            self.warn(self.error_at(pos, message));
+        }
+    }
+
+    /// Adds a material block, warning if its name is already defined in the same file
+    /// (the first one being used)
+    ///
+    /// A material of another file is replaced, unless it comes from a file closer
+    /// to the scene file: the including file wins, then the last included file.
+    /// A material block of a repeat block replaces itself at each repetition
+    fn add_material(&mut self, mut material: NamedMaterial) {
+        let Some(index) = self.materials.iter().position(|m| m.name == material.name) else {
+            self.materials.push(material);
+            return;
+        };
+        let first = &self.materials[index];
+        if first.file == material.file && first.pos == material.pos {
+            material.used = first.used;
+            self.materials[index] = material;
+        } else if first.file == material.file {
+            let defined = match self.paths.get(first.pos) {
+                Some(path) => format!("at `{}`", path),
+                None => format!(
+                    "line {}",
+                    self.spans.get(first.pos).map_or(0, |span| span.line)
+                ),
+            };
+            self.warn(self.error_at(
+                material.pos,
+                format!(
+                    "material `{}` is already defined {}, this one is ignored",
+                    material.name, defined
+                ),
+            ));
+        } else if material.depth <= first.depth {
+            self.materials[index] = material;
+        }
+    }
+}
+
+/// Removes the errors found again after an index (such as the errors of each repetition)
+fn remove_repeated(errors: &mut Vec<ParseError>, start: usize) {
+    let mut index = start;
+    while index < errors.len() {
+        if errors[start..index].contains(&errors[index]) {
+            errors.remove(index);
         } else {
-            Material::default()
+            index += 1;
+        }
+    }
+}
+
+/// Inserts an error before the first error of its file found after it
+fn insert_in_order(errors: &mut Vec<ParseError>, error: ParseError) {
+    let index = errors
+        .iter()
+        .position(|e| e.file == error.file && (e.line, e.column) > (error.line, error.column))
+        .unwrap_or(errors.len());
+    errors.insert(index, error);
+}
+
+#[cfg(test)]
+mod tests_parser {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+        sync::Arc,
+    };
+
+    use super::{FileAccess, Parser};
+    use crate::mods::{
+        color::ColorRBG, material::Material, parse_error::ParseError, position::Vect3,
+        report::QuietReporter,
+    };
+
+    static CAMERA: &str = "camera {
+    position: 0.0 0.0 5.0
+    fov: 40.0
+}
+";
+
+    /// Parses a scene made of a camera and the given source, giving its errors
+    fn errors(source: &str) -> Vec<ParseError> {
+        let source = format!("{}{}", CAMERA, source);
+        match Parser::from_source(&source).parse_scene() {
+            Ok(_) => vec![],
+            Err(errors) => errors.0,
+        }
+    }
+
+    /// Line, column and message of an error
+    fn summary(error: &ParseError) -> (usize, usize, &str) {
+        (error.line, error.column, error.message.as_str())
+    }
+
+    #[test]
+    fn valid_scene() {
+        let scene = Parser::from_source(&format!(
+            "{}sphere {{\n    radius: 1.0 # comment\n}}\n",
+            CAMERA
+        ))
+        .parse_scene()
+        .unwrap();
+
+        assert_eq!(scene.spheres[0].radius, 1.0);
+    }
+
+    #[test]
+    fn unknown_character() {
+        let errors = errors("sphere {\n    radius: 1.0 %\n}\n");
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(summary(&errors[0]), (6, 17, "unexpected character `%`"));
+        assert_eq!(errors[0].file, "<source>");
+    }
+
+    #[test]
+    fn invalid_number() {
+        let errors = errors("sphere {\n    radius: 1.2.3\n}\n");
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(summary(&errors[0]), (6, 13, "invalid number `1.2.3`"));
+    }
+
+    #[test]
+    fn expected_number() {
+        let errors = errors("sphere {\n    position: 1.0 x 0.0\n}\n");
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(summary(&errors[0]), (6, 19, "expected a number, found `x`"));
+    }
+
+    #[test]
+    fn quoted_path() {
+        let errors = errors(
+            "mesh {\n    obj_file: \"my meshes/a \\\"b\\\".obj\"\n}\nsphere {\n    radius:   # missing\n}\n",
+        );
+
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0]
+            .message
+            .starts_with("cannot read mesh file: my meshes/a \"b\".obj:"));
+        assert_eq!(
+            summary(&errors[1]),
+            (9, 12, "expected a number, found end of line")
+        );
+    }
+
+    #[test]
+    fn missing_colon() {
+        let errors = errors("sphere {\n    radius 1.0\n}\n");
+
+        assert_eq!(summary(&errors[0]), (6, 12, "expected ':', found number 1"));
+    }
+
+    #[test]
+    fn unknown_property() {
+        let errors = errors("sphere {\n    radus: 1.0\n}\n");
+
+        assert_eq!(
+            summary(&errors[0]),
+            (6, 5, "unexpected `radus` in sphere block")
+        );
+    }
+
+    #[test]
+    fn expressions() {
+        let scene = Parser::from_source(&format!(
+            "{}let base = 2\nlet orange = [1.0, 0.5, 0.0]\nmaterial {{\n    name: warm\n    color: orange * 0.5\n}}\nsphere {{\n    position: base -1 (base + 1) * -2\n    radius: sqrt(base * 8) / 2\n    mat: warm\n}}\nsphere {{\n    position: [0, base, 0] - [1, 1, 1]\n    radius: base-1 + cos(rad(180))\n    mat: warm\n}}\n",
+            CAMERA
+        ))
+        .parse_scene()
+        .unwrap();
+
+        assert_eq!(
+            scene.spheres[0].transform.get_pos(),
+            Vect3::new(2.0, -1.0, -6.0)
+        );
+        assert_eq!(scene.spheres[0].radius, 2.0);
+        assert_eq!(
+            scene.spheres[0].get_mat().color,
+            ColorRBG::new(0.5, 0.25, 0.0)
+        );
+        assert_eq!(
+            scene.spheres[1].transform.get_pos(),
+            Vect3::new(-1.0, 1.0, -1.0)
+        );
+        assert_eq!(scene.spheres[1].radius, 0.0);
+    }
+
+    #[test]
+    fn division() {
+        let source = format!(
+            "{}let base = 6\nlet half = base / 2\nlet ratio = 2/3\nsphere {{\n    radius: half * ratio\n}}\n",
+            CAMERA
+        );
+        let scene = Parser::from_source(&source).parse_scene().unwrap();
+
+        assert_eq!(scene.spheres[0].radius, 2.0);
+        let errors = errors("let base = 6\nlet half = base/2\n");
+        assert_eq!(
+            summary(&errors[0]),
+            (
+                6,
+                12,
+                "undefined variable `base/2` (put spaces around '/' to divide)"
+            )
+        );
+    }
+
+    #[test]
+    fn expression_errors() {
+        let errors = errors(
+            "let base = 2\nsphere {\n    radius: bse\n    position: [1, 2]\n}\nplane {\n    normal: 0 base/2 0\n    point: 1 + [1, 1, 1]\n}\nlet sin = 1\n",
+        );
+
+        assert_eq!(errors.len(), 5);
+        assert_eq!(
+            summary(&errors[0]),
+            (7, 13, "undefined variable `bse`, did you mean `base`?")
+        );
+        assert_eq!(
+            summary(&errors[1]),
+            (8, 15, "expected 3 numbers, found a vector of 2")
+        );
+        assert_eq!(
+            summary(&errors[2]),
+            (
+                11,
+                15,
+                "undefined variable `base/2` (put spaces around '/' to divide)"
+            )
+        );
+        assert_eq!(
+            summary(&errors[3]),
+            (12, 14, "cannot add a number and a vector of 3")
+        );
+        assert_eq!(
+            summary(&errors[4]),
+            (14, 5, "expected a variable name, found `sin`")
+        );
+    }
+
+    #[test]
+    fn repeat() {
+        let mut parser = Parser::from_source(&format!(
+            "{}let i = 10\nrepeat i in 0..3 {{\n    material {{\n        name: sweep\n        smoothness: i / 2\n    }}\n    repeat j in 1..2.5 {{\n        sphere {{\n            position: i j 0\n            mat: sweep\n        }}\n    }}\n}}\nsphere {{\n    radius: i\n}}\n",
+            CAMERA
+        ));
+        parser.set_reporter(Arc::new(QuietReporter));
+        let scene = parser.parse_scene().unwrap();
+
+        let positions: Vec<(f64, f64)> = scene
+            .spheres
+            .iter()
+            .map(|s| (s.transform.get_pos().x(), s.transform.get_pos().y()))
+            .collect();
+        assert_eq!(
+            positions,
+            vec![
+                (0.0, 1.0),
+                (0.0, 2.0),
+                (1.0, 1.0),
+                (1.0, 2.0),
+                (2.0, 1.0),
+                (2.0, 2.0),
+                (0.0, 0.0)
+            ]
+        );
+        assert_eq!(scene.spheres[0].get_mat().smoothness, 0.0);
+        assert_eq!(scene.spheres[5].get_mat().smoothness, 1.0);
+        assert_eq!(scene.spheres[6].radius, 10.0);
+        assert!(parser.get_warnings().is_empty());
+    }
+
+    #[test]
+    fn repeat_errors() {
+        let errors = errors(
+            "repeat i in 0..4 {\n    sphere {\n        radius: size\n    }\n}\nrepeat i 0..4 {\n}\nrepeat i in 0..2 {\n    sphere {\n",
+        );
+
+        assert_eq!(errors.len(), 3);
+        assert_eq!(
+            summary(&errors[0]),
+            (7, 17, "expected a number, found `size`")
+        );
+        assert_eq!(
+            summary(&errors[1]),
+            (10, 10, "expected `in`, found number 0")
+        );
+        assert_eq!(
+            summary(&errors[2]),
+            (
+                14,
+                1,
+                "expected '}' closing the repeat block, found end of file"
+            )
+        );
+    }
+
+    #[test]
+    fn unknown_block() {
+        let errors = errors("sphre {\n    radius: 1.0\n}\nsphere {\n    radius: x\n}\n");
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            summary(&errors[0]),
+            (
+                5,
+                1,
+                "expected a let, an include, a repeat or a camera, material, sphere, plane, triangle, cube or mesh block, found `sphre`"
+            )
+        );
+        assert_eq!(errors[1].line, 9);
+    }
+
+    #[test]
+    fn missing_brace() {
+        let errors = errors("sphere {\n    radius: 1.0\n");
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            summary(&errors[0]),
+            (
+                7,
+                1,
+                "expected '}' closing the sphere block, found end of file"
+            )
+        );
+    }
+
+    #[test]
+    fn missing_camera() {
+        let Err(errors) = Parser::from_source("sphere {\n}\n").parse_scene() else {
+            panic!("scene without camera parsed");
+        };
+
+        assert_eq!(
+            errors.0[0].message,
+            "expected a camera block, found end of file"
+        );
+    }
+
+    #[test]
+    fn keyframe_errors() {
+        let errors = errors(
+            "sphere {\n    keyframe { frame: 1 interpolation: bouncy }\n    keyframe {\n        size: 2.0\n    }\n}\n",
+        );
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            summary(&errors[0]),
+            (6, 40, "unknown interpolation `bouncy`")
+        );
+        assert_eq!(
+            summary(&errors[1]),
+            (8, 9, "unexpected `size` in keyframe block")
+        );
+    }
+
+    #[test]
+    fn missing_mesh() {
+        let errors = errors("mesh {\n    obj_file: nowhere.obj\n}\n");
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].line, errors[0].column), (6, 15));
+        assert!(errors[0].message.starts_with("cannot read mesh file"));
+    }
+
+    #[test]
+    fn several_errors() {
+        let errors = errors(
+            "material {\n    name: red\n    color: 1.0 x 0.0 %\n    smoothness: 1\n}\nsphere {\n    radius: -\n    mat: red\n}\n",
+        );
+
+        let positions: Vec<(usize, usize)> = errors.iter().map(|e| (e.line, e.column)).collect();
+        assert_eq!(positions, vec![(7, 16), (7, 22), (11, 14)]);
+    }
+
+    static MATERIALS: &str = "material {
+    name: red
+    color: 1.0 0.0 0.0
+}
+material {
+    name: blue
+    color: 0.0 0.0 1.0
+}
+";
+
+    #[test]
+    fn undefined_material() {
+        let errors = errors(&format!(
+            "{}sphere {{\n    mat: bleu\n}}\nplane {{\n    mat: green\n}}\n",
+            MATERIALS
+        ));
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            summary(&errors[0]),
+            (14, 10, "undefined material `bleu`, did you mean `blue`?")
+        );
+        assert_eq!(summary(&errors[1]), (17, 10, "undefined material `green`"));
+    }
+
+    #[test]
+    fn lenient() {
+        let source = format!(
+            "{}{}sphere {{\n    mat: bleu\n}}\nsphere {{\n    mat: red\n}}\n",
+            CAMERA, MATERIALS
+        );
+        let mut parser = Parser::from_source(&source);
+        parser.set_reporter(Arc::new(QuietReporter));
+        parser.set_lenient(true);
+        let scene = parser.parse_scene().unwrap();
+
+        assert_eq!(*scene.spheres[0].get_mat(), Material::default());
+        assert_eq!(scene.spheres[1].get_mat().color, ColorRBG::RED);
+        let warnings: Vec<&str> = parser
+            .get_warnings()
+            .iter()
+            .map(|w| w.message.as_str())
+            .collect();
+        assert_eq!(
+            warnings,
+            vec![
+                "material `blue` is never used",
+                "undefined material `bleu`, did you mean `blue`?"
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_material() {
+        let source = format!(
+            "{}{}material {{\n    name: red\n    color: 0.0 1.0 0.0\n}}\ncube {{\n    mat: red\n}}\nplane {{\n    mat: blue\n}}\n",
+            CAMERA, MATERIALS
+        );
+        let mut parser = Parser::from_source(&source);
+        parser.set_reporter(Arc::new(QuietReporter));
+        let scene = parser.parse_scene().unwrap();
+
+        assert_eq!(scene.triangles[0].get_mat().color, ColorRBG::RED);
+        assert_eq!(parser.get_warnings().len(), 1);
+        assert_eq!(
+            summary(&parser.get_warnings()[0]),
+            (
+                14,
+                11,
+                "material `red` is already defined line 6, this one is ignored"
+            )
+        );
+    }
+
+    #[test]
+    fn unused_material() {
+        let source = format!("{}{}sphere {{\n    mat: red\n}}\n", CAMERA, MATERIALS);
+        let mut parser = Parser::from_source(&source);
+        parser.set_reporter(Arc::new(QuietReporter));
+        parser.parse_scene().unwrap();
+
+        assert_eq!(parser.get_warnings().len(), 1);
+        assert_eq!(
+            summary(&parser.get_warnings()[0]),
+            (10, 11, "material `blue` is never used")
+        );
+    }
+
+    /// Writes scene files in a new temporary directory
+    fn write_files(dir_name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(dir_name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for (name, source) in files {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, source).unwrap();
+        }
+        dir
+    }
+
+    /// Parses a scene file of a directory, without reporting its warnings
+    fn parse_file(dir: &Path, name: &str) -> Parser {
+        let mut parser = Parser::build(dir.join(name).to_str().unwrap()).unwrap();
+        parser.set_reporter(Arc::new(QuietReporter));
+        parser
+    }
+
+    #[test]
+    fn include() {
+        let dir = write_files(
+            "rbpt_tests_include",
+            &[
+                (
+                    "scene.rtp",
+                    &format!(
+                        "{}include \"lib/objects.rtp\"\nsphere {{\n    mat: red\n}}\n",
+                        CAMERA
+                    ),
+                ),
+                (
+                    "lib/objects.rtp",
+                    "include \"materials.rtp\"\ncube {\n    mat: blue\n}\n",
+                ),
+                ("lib/materials.rtp", MATERIALS),
+            ],
+        );
+        let mut parser = parse_file(&dir, "scene.rtp");
+        let scene = parser.parse_scene().unwrap();
+
+        assert_eq!(scene.spheres[0].get_mat().color, ColorRBG::RED);
+        assert_eq!(scene.triangles.len(), 12);
+        assert_eq!(scene.triangles[0].get_mat().color, ColorRBG::BLUE);
+        assert!(parser.get_warnings().is_empty());
+    }
+
+    #[test]
+    fn include_errors() {
+        let dir = write_files(
+            "rbpt_tests_include_errors",
+            &[
+                ("a.rtp", &format!("{}include \"b.rtp\"\n", CAMERA)),
+                (
+                    "b.rtp",
+                    "include a.rtp\ninclude \"nowhere.rtp\"\nsphere {\n    radius: x\n}\n",
+                ),
+            ],
+        );
+        let Err(errors) = parse_file(&dir, "a.rtp").parse_scene() else {
+            panic!("invalid includes parsed");
+        };
+        let errors: Vec<(&str, (usize, usize, &str))> = errors
+            .0
+            .iter()
+            .map(|e| (e.file.rsplit('/').next().unwrap(), summary(e)))
+            .collect();
+
+        assert_eq!(errors.len(), 3);
+        assert_eq!(
+            errors[0],
+            ("b.rtp", (1, 9, "include cycle: a.rtp -> b.rtp -> a.rtp"))
+        );
+        assert_eq!(errors[1].1 .0, 2);
+        assert!(errors[1].1 .2.starts_with("cannot read included file"));
+        assert_eq!(
+            errors[2],
+            ("b.rtp", (4, 13, "expected a number, found `x`"))
+        );
+    }
+
+    #[test]
+    fn mtl_materials() {
+        let dir = write_files(
+            "rbpt_tests_mtl_materials",
+            &[
+                (
+                    "meshes/a.obj",
+                    "mtllib a.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\nusemtl red\nf 1 2 3\nusemtl blue\nf 1 2 3\n",
+                ),
+                ("meshes/a.mtl", "newmtl red\nKd 1 0 0\n"),
+            ],
+        );
+        let obj_file = dir.join("meshes/a.obj");
+        let source = format!(
+            "{}mesh {{\n    obj_file: \"{}\"\n}}\n{}mesh {{\n    obj_file: \"{}\"\n    mat: green\n}}\n",
+            CAMERA,
+            obj_file.display(),
+            "material {\n    name: green\n    color: 0.0 1.0 0.0\n}\n",
+            obj_file.display()
+        );
+        let mut parser = Parser::from_source(&source);
+        parser.set_reporter(Arc::new(QuietReporter));
+        let scene = parser.parse_scene().unwrap();
+
+        let colors: Vec<ColorRBG> = scene.triangles.iter().map(|t| t.get_mat().color).collect();
+        assert_eq!(
+            colors,
+            vec![
+                ColorRBG::WHITE,
+                ColorRBG::RED,
+                ColorRBG::WHITE,
+                ColorRBG::GREEN,
+                ColorRBG::GREEN,
+                ColorRBG::GREEN
+            ]
+        );
+        assert_eq!(parser.get_warnings().len(), 1);
+        assert_eq!(
+            summary(&parser.get_warnings()[0]),
+            (
+                8,
+                1,
+                "material `blue` is not defined in the mesh's .mtl files, the default material is used"
+            )
+        );
+    }
+
+    #[test]
+    fn mesh_scale() {
+        let dir = write_files(
+            "rbpt_tests_mesh_scale",
+            &[(
+                "part.stl",
+                "solid part\nfacet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1000 0 0\nvertex 1000 1000 0\nendloop\nendfacet\nfacet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1000 0.5 0\nvertex 1000 1 0\nendloop\nendfacet\nendsolid part\n",
+            )],
+        );
+        let source = format!(
+            "{}mesh {{\n    stl_file: \"{}\"\n    scale: 0.001\n    weld: 1\n}}\n",
+            CAMERA,
+            dir.join("part.stl").display()
+        );
+        let scene = Parser::from_source(&source).parse_scene().unwrap();
+
+        // The second facet is collapsed by welding
+        assert_eq!(scene.triangles.len(), 1);
+        assert_eq!(scene.triangles[0].points()[2], Vect3::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn file_access() {
+        let dir = write_files(
+            "rbpt_tests_file_access",
+            &[
+                ("meshes/tri.obj", "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n"),
+                ("outside.obj", "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n"),
+                ("lib.rtp", MATERIALS),
+            ],
+        );
+        let parse = |block: &str, access: FileAccess| {
+            let mut parser = Parser::from_source(&format!("{}{}", CAMERA, block));
+            parser.set_file_access(access);
+            parser
+                .parse_scene()
+                .map(|scene| scene.triangles.len())
+                .map_err(|e| e.0[0].message.clone())
+        };
+        let mesh = |file: &str| format!("mesh {{\n    obj_file: \"{}\"\n}}\n", file);
+        let meshes = FileAccess::MeshDir(Some(dir.join("meshes")));
+
+        assert_eq!(parse(&mesh("tri.obj"), meshes.clone()), Ok(1));
+        assert_eq!(
+            parse(&mesh("../outside.obj"), meshes.clone()).unwrap_err(),
+            "cannot read mesh file: ../outside.obj: the file is outside of the mesh directory"
+        );
+        assert_eq!(
+            parse(&mesh("tri.obj"), FileAccess::MeshDir(None)).unwrap_err(),
+            "cannot read mesh file: tri.obj: mesh files are not allowed here"
+        );
+        let include = format!("include \"{}\"\n", dir.join("lib.rtp").display());
+        assert!(parse(&include, FileAccess::All).is_ok());
+        assert!(parse(&include, meshes)
+            .unwrap_err()
+            .ends_with("includes are not allowed here"));
+    }
+
+    #[test]
+    fn material_override() {
+        let green = "material {\n    name: red\n    color: 0.0 1.0 0.0\n}\n";
+        let dir = write_files(
+            "rbpt_tests_material_override",
+            &[
+                (
+                    "after.rtp",
+                    &format!(
+                        "{}include \"lib.rtp\"\n{}cube {{\n    mat: red\n}}\n",
+                        CAMERA, green
+                    ),
+                ),
+                (
+                    "before.rtp",
+                    &format!(
+                        "{}{}include \"lib.rtp\"\ncube {{\n    mat: red\n}}\n",
+                        CAMERA, green
+                    ),
+                ),
+                ("lib.rtp", MATERIALS),
+            ],
+        );
+
+        for file in ["after.rtp", "before.rtp"] {
+            let mut parser = parse_file(&dir, file);
+            let scene = parser.parse_scene().unwrap();
+
+            assert_eq!(scene.triangles[0].get_mat().color, ColorRBG::GREEN);
+            assert!(parser.get_warnings().is_empty());
         }
     }
 }
@@ -0,0 +1,668 @@
+use super::{
+    color::ColorRBG,
+    mesh::{triangulate, words, Face, Mesh},
+    parse_error::{ParseError, ParseErrors},
+    position::Vect3,
+};
+
+/// Type of a PLY value
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Scalar> {
+        match name {
+            "char" | "int8" => Some(Scalar::I8),
+            "uchar" | "uint8" => Some(Scalar::U8),
+            "short" | "int16" => Some(Scalar::I16),
+            "ushort" | "uint16" => Some(Scalar::U16),
+            "int" | "int32" => Some(Scalar::I32),
+            "uint" | "uint32" => Some(Scalar::U32),
+            "float" | "float32" => Some(Scalar::F32),
+            "double" | "float64" => Some(Scalar::F64),
+            _ => None,
+        }
+    }
+
+    /// Size in binary files
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    fn is_float(self) -> bool {
+        matches!(self, Scalar::F32 | Scalar::F64)
+    }
+
+    /// Value of a full intensity color component
+    fn color_scale(self) -> f64 {
+        match self {
+            Scalar::F32 | Scalar::F64 => 1.0,
+            Scalar::U16 => 65535.0,
+            _ => 255.0,
+        }
+    }
+}
+
+/// Property of a PLY element: a value, or a list of values with its length type
+#[derive(Debug, Clone)]
+struct Property {
+    name: String,
+    value: Scalar,
+    length: Option<Scalar>,
+}
+
+/// Element of a PLY header (vertex, face...)
+#[derive(Debug, Clone)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+    /// Line of its `element` statement
+    line: usize,
+}
+
+impl Element {
+    /// Index of the first property with one of the names
+    fn property(&self, names: &[&str]) -> Option<usize> {
+        self.properties
+            .iter()
+            .position(|p| names.contains(&p.name.as_str()))
+    }
+
+    /// Indices of the red, green and blue properties
+    fn color(&self) -> Option<[usize; 3]> {
+        Some([
+            self.property(&["red", "diffuse_red"])?,
+            self.property(&["green", "diffuse_green"])?,
+            self.property(&["blue", "diffuse_blue"])?,
+        ])
+    }
+}
+
+/// Encoding of the body of a PLY file
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encoding {
+    Ascii,
+    Binary { big_endian: bool },
+}
+
+/// Reads the body of a PLY file, value by value
+enum Body<'a> {
+    Ascii {
+        /// Words, with their line and column
+        words: Vec<(&'a str, usize, usize)>,
+        next: usize,
+    },
+    Binary {
+        bytes: &'a [u8],
+        pos: usize,
+        big_endian: bool,
+    },
+}
+
+impl Body<'_> {
+    fn value(&mut self, scalar: Scalar) -> Result<f64, String> {
+        match self {
+            Body::Ascii { words, next } => {
+                let Some(&(word, _, _)) = words.get(*next) else {
+                    return Err("unexpected end of file".to_string());
+                };
+                *next += 1;
+                let value = if scalar.is_float() {
+                    word.parse::<f64>().ok().filter(|v| v.is_finite())
+                } else {
+                    word.parse::<i64>().ok().map(|v| v as f64)
+                };
+                value.ok_or_else(|| {
+                    let kind = if scalar.is_float() {
+                        "a number"
+                    } else {
+                        "an integer"
+                    };
+                    format!("expected {}, found `{}`", kind, word)
+                })
+            }
+            Body::Binary {
+                bytes,
+                pos,
+                big_endian,
+            } => {
+                let size = scalar.size();
+                let Some(data) = bytes.get(*pos..*pos + size) else {
+                    return Err("unexpected end of file".to_string());
+                };
+                *pos += size;
+                let mut le = [0; 8];
+                le[..size].copy_from_slice(data);
+                if *big_endian {
+                    le[..size].reverse();
+                }
+                let value = match scalar {
+                    Scalar::I8 => le[0] as i8 as f64,
+                    Scalar::U8 => le[0] as f64,
+                    Scalar::I16 => i16::from_le_bytes([le[0], le[1]]) as f64,
+                    Scalar::U16 => u16::from_le_bytes([le[0], le[1]]) as f64,
+                    Scalar::I32 => i32::from_le_bytes([le[0], le[1], le[2], le[3]]) as f64,
+                    Scalar::U32 => u32::from_le_bytes([le[0], le[1], le[2], le[3]]) as f64,
+                    Scalar::F32 => f32::from_le_bytes([le[0], le[1], le[2], le[3]]) as f64,
+                    Scalar::F64 => f64::from_le_bytes(le),
+                };
+                if value.is_finite() {
+                    Ok(value)
+                } else {
+                    Err(format!("invalid number {}", value))
+                }
+            }
+        }
+    }
+
+    /// Line and column of the last value read (the whole file for binary files)
+    fn position(&self) -> (usize, usize) {
+        match self {
+            Body::Ascii { words, next } => words
+                .get(next.saturating_sub(1))
+                .or(words.last())
+                .map_or((0, 0), |&(_, line, column)| (line, column)),
+            Body::Binary { .. } => (0, 0),
+        }
+    }
+
+    /// Reads the values of an element's item, each property starting at an index of values
+    fn item(
+        &mut self,
+        element: &Element,
+        values: &mut Vec<f64>,
+        starts: &mut Vec<usize>,
+    ) -> Result<(), String> {
+        values.clear();
+        starts.clear();
+        for property in &element.properties {
+            starts.push(values.len());
+            let count = match property.length {
+                Some(length) => {
+                    let count = self.value(length)?;
+                    if count < 0.0 {
+                        return Err(format!("invalid list length {}", count));
+                    }
+                    count as usize
+                }
+                None => 1,
+            };
+            for _ in 0..count {
+                values.push(self.value(property.value)?);
+            }
+        }
+        starts.push(values.len());
+        Ok(())
+    }
+}
+
+/// Vertex of a PLY file
+struct Vertex {
+    position: Vect3,
+    color: Option<ColorRBG>,
+}
+
+/// Polygon of a PLY file, with the line it ends on (0 for binary files)
+struct Polygon {
+    /// Vertex indices, as read
+    vertices: Vec<f64>,
+    color: Option<ColorRBG>,
+    line: usize,
+    /// Index in the face element
+    index: usize,
+}
+
+/// Reads a PLY file (ASCII, or binary little or big endian)
+///
+/// Vertices have a position and may have a color, faces are polygons which may have
+/// a color. Other properties (normals, texture coordinates...) and elements are read
+/// but not used. Every error of the header is returned, but the body stops at its first
+/// error
+pub fn read_ply(bytes: &[u8], file_name: &str) -> Result<Mesh, ParseErrors> {
+    let error = |line: usize, column: usize, message: String| {
+        ParseErrors(vec![ParseError::new(file_name, line, column, message)])
+    };
+
+    // The header is text, up to its end_header line
+    let mut header = vec![];
+    let mut body_start = None;
+    let mut start = 0;
+    while start < bytes.len() {
+        let end = bytes[start..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(bytes.len(), |i| start + i + 1);
+        let line = String::from_utf8_lossy(&bytes[start..end]);
+        let line = line.trim_end_matches(['\n', '\r']).to_string();
+        start = end;
+        if line.trim() == "end_header" {
+            body_start = Some(start);
+            break;
+        }
+        header.push(line);
+    }
+    if header.first().map(|l| l.trim()) != Some("ply") {
+        return Err(error(1, 1, "not a PLY file (`ply` expected)".to_string()));
+    }
+    let Some(body_start) = body_start else {
+        return Err(error(
+            0,
+            0,
+            "the header has no `end_header` line".to_string(),
+        ));
+    };
+    let (encoding, elements) = read_header(&header, file_name)?;
+
+    // Words borrow the text of an ASCII body
+    let text = match encoding {
+        Encoding::Ascii => String::from_utf8_lossy(&bytes[body_start..]),
+        Encoding::Binary { .. } => "".into(),
+    };
+    let first_line = header.len() + 2;
+    let mut body = match encoding {
+        Encoding::Ascii => Body::Ascii {
+            words: text
+                .lines()
+                .enumerate()
+                .flat_map(|(i, line)| {
+                    words(line)
+                        .into_iter()
+                        .map(move |w| (w.text, first_line + i, w.column))
+                })
+                .collect(),
+            next: 0,
+        },
+        Encoding::Binary { big_endian } => Body::Binary {
+            bytes: &bytes[body_start..],
+            pos: 0,
+            big_endian,
+        },
+    };
+    read_body(&mut body, &elements, file_name)
+}
+
+/// Reads the format and elements of a PLY header (its lines before `end_header`)
+fn read_header(
+    header: &[String],
+    file_name: &str,
+) -> Result<(Encoding, Vec<Element>), ParseErrors> {
+    let mut encoding = None;
+    let mut elements: Vec<Element> = vec![];
+    let mut errors = vec![];
+    for (i, text) in header.iter().enumerate().skip(1) {
+        let line = i + 1;
+        let words = words(text);
+        let Some(keyword) = words.first() else {
+            continue;
+        };
+        let mut error = |column: usize, message: String| {
+            errors.push(ParseError::new(file_name, line, column, message))
+        };
+        match keyword.text {
+            "format" => match words.get(1) {
+                Some(w) if w.text == "ascii" => encoding = Some(Encoding::Ascii),
+                Some(w) if w.text == "binary_little_endian" => {
+                    encoding = Some(Encoding::Binary { big_endian: false })
+                }
+                Some(w) if w.text == "binary_big_endian" => {
+                    encoding = Some(Encoding::Binary { big_endian: true })
+                }
+                Some(w) => error(
+                    w.column,
+                    format!(
+                        "unknown format `{}` (expected ascii, binary_little_endian or binary_big_endian)",
+                        w.text
+                    ),
+                ),
+                None => error(keyword.column, "missing format".to_string()),
+            },
+            "element" => match (words.get(1), words.get(2).map(|w| w.text.parse::<usize>())) {
+                (Some(name), Some(Ok(count))) if words.len() == 3 => elements.push(Element {
+                    name: name.text.to_string(),
+                    count,
+                    properties: vec![],
+                    line,
+                }),
+                _ => error(
+                    keyword.column,
+                    "expected `element <name> <count>`".to_string(),
+                ),
+            },
+            "property" => {
+                let property = match &words[1..] {
+                    [value, name] => Scalar::parse(value.text)
+                        .map(|value| (value, None, name.text))
+                        .ok_or(value),
+                    [list, length, value, name] if list.text == "list" => {
+                        match (Scalar::parse(length.text), Scalar::parse(value.text)) {
+                            (Some(length), Some(value)) => Ok((value, Some(length), name.text)),
+                            (None, _) => Err(length),
+                            (_, None) => Err(value),
+                        }
+                    }
+                    _ => {
+                        error(
+                            keyword.column,
+                            "expected `property <type> <name>` or `property list <type> <type> <name>`"
+                                .to_string(),
+                        );
+                        continue;
+                    }
+                };
+                match (property, elements.last_mut()) {
+                    (Ok((value, length, name)), Some(element)) => {
+                        element.properties.push(Property {
+                            name: name.to_string(),
+                            value,
+                            length,
+                        })
+                    }
+                    (Ok(_), None) => error(
+                        keyword.column,
+                        "`property` before the first `element`".to_string(),
+                    ),
+                    (Err(kind), _) => error(
+                        kind.column,
+                        format!("unknown property type `{}`", kind.text),
+                    ),
+                }
+            }
+            "comment" | "obj_info" => {}
+            other => error(
+                keyword.column,
+                format!("unknown header statement `{}`", other),
+            ),
+        }
+    }
+    match encoding {
+        Some(encoding) if errors.is_empty() => Ok((encoding, elements)),
+        Some(_) => Err(ParseErrors(errors)),
+        None => {
+            errors.push(ParseError::new(
+                file_name,
+                0,
+                0,
+                "the header has no `format` line".to_string(),
+            ));
+            Err(ParseErrors(errors))
+        }
+    }
+}
+
+/// Reads the elements of a PLY body, building the mesh of its vertices and faces
+fn read_body(body: &mut Body, elements: &[Element], file_name: &str) -> Result<Mesh, ParseErrors> {
+    let error = |line: usize, column: usize, message: String| {
+        ParseErrors(vec![ParseError::new(file_name, line, column, message)])
+    };
+    let Some(vertex) = elements.iter().position(|e| e.name == "vertex") else {
+        return Err(error(0, 0, "the file has no `vertex` element".to_string()));
+    };
+    let mut position = [0; 3];
+    for (i, name) in ["x", "y", "z"].iter().enumerate() {
+        position[i] = elements[vertex].property(&[name]).ok_or_else(|| {
+            error(
+                elements[vertex].line,
+                1,
+                format!("the `vertex` element has no `{}` property", name),
+            )
+        })?;
+    }
+    let vertex_color = elements[vertex].color();
+    let face = elements.iter().position(|e| e.name == "face");
+    let mut indices = 0;
+    if let Some(face) = face {
+        indices = elements[face]
+            .property(&["vertex_indices", "vertex_index"])
+            .filter(|&p| elements[face].properties[p].length.is_some())
+            .ok_or_else(|| {
+                error(
+                    elements[face].line,
+                    1,
+                    "the `face` element has no `vertex_indices` list".to_string(),
+                )
+            })?;
+    }
+    let face_color = face.and_then(|face| elements[face].color());
+
+    let mut vertices = vec![];
+    let mut polygons = vec![];
+    let (mut values, mut starts) = (vec![], vec![]);
+    for (e, element) in elements.iter().enumerate() {
+        for i in 0..element.count {
+            body.item(element, &mut values, &mut starts)
+                .map_err(|message| {
+                    let (line, column) = body.position();
+                    match line {
+                        0 => error(
+                            0,
+                            0,
+                            format!(
+                                "{} (`{}` {} of {})",
+                                message,
+                                element.name,
+                                i + 1,
+                                element.count
+                            ),
+                        ),
+                        line => error(line, column, message),
+                    }
+                })?;
+            let color = |properties: Option<[usize; 3]>| {
+                properties.map(|properties| {
+                    let [r, g, b] = properties
+                        .map(|p| values[starts[p]] / element.properties[p].value.color_scale());
+                    ColorRBG::new(r, g, b)
+                })
+            };
+            if e == vertex {
+                let [x, y, z] = position.map(|p| values[starts[p]]);
+                vertices.push(Vertex {
+                    position: Vect3::new(x, y, z),
+                    color: color(vertex_color),
+                });
+            } else if Some(e) == face {
+                polygons.push(Polygon {
+                    vertices: values[starts[indices]..starts[indices + 1]].to_vec(),
+                    color: color(face_color),
+                    line: body.position().0,
+                    index: i,
+                });
+            }
+        }
+    }
+
+    let mut mesh = Mesh {
+        vertices: vertices.iter().map(|v| v.position).collect(),
+        group_sets: vec![vec![]],
+        ..Mesh::default()
+    };
+    if face.is_none() {
+        mesh.warnings.push(ParseError::new(
+            file_name,
+            0,
+            0,
+            "the file has no `face` element, the mesh is empty".to_string(),
+        ));
+    }
+    let mut errors = vec![];
+    for polygon in polygons {
+        let mut error = |message: String| {
+            let message = match polygon.line {
+                0 => format!("face {}: {}", polygon.index + 1, message),
+                _ => message,
+            };
+            errors.push(ParseError::new(file_name, polygon.line, 1, message));
+        };
+        if polygon.vertices.len() < 3 {
+            error(format!(
+                "face has {} vertices, at least 3 are needed",
+                polygon.vertices.len()
+            ));
+            continue;
+        }
+        let Some(outside) = polygon
+            .vertices
+            .iter()
+            .find(|&&v| v < 0.0 || v >= vertices.len() as f64)
+        else {
+            let polygon_vertices: Vec<usize> =
+                polygon.vertices.iter().map(|&v| v as usize).collect();
+            let points: Vec<Vect3> = polygon_vertices.iter().map(|&v| mesh.vertices[v]).collect();
+            for triangle in triangulate(&points) {
+                let triangle = triangle.map(|i| polygon_vertices[i]);
+                // Faces without a color of their own get the mean color of their vertices
+                let vertex_colors: Option<Vec<ColorRBG>> =
+                    triangle.iter().map(|&v| vertices[v].color).collect();
+                let color = polygon.color.or(vertex_colors.map(|colors| {
+                    colors
+                        .iter()
+                        .fold(ColorRBG::BLACK, |sum, &c| sum + (1.0 / 3.0) * c)
+                }));
+                mesh.faces.push(Face {
+                    vertices: triangle,
+                    object: None,
+                    groups: 0,
+                    material: None,
+                    color,
+                });
+            }
+            continue;
+        };
+        error(format!(
+            "face uses vertex {}, but {} vertices are defined",
+            outside,
+            vertices.len()
+        ));
+    }
+    if !errors.is_empty() {
+        return Err(ParseErrors(errors));
+    }
+    Ok(mesh)
+}
+
+#[cfg(test)]
+mod tests_ply {
+    use super::read_ply;
+    use crate::mods::{mesh::Mesh, position::Vect3};
+
+    /// Messages of the errors of a PLY file, with their line and column
+    fn errors(bytes: &[u8]) -> Vec<(usize, usize, String)> {
+        match read_ply(bytes, "a.ply") {
+            Ok(_) => vec![],
+            Err(errors) => errors
+                .0
+                .into_iter()
+                .map(|e| (e.line, e.column, e.message))
+                .collect(),
+        }
+    }
+
+    fn faces(mesh: &Mesh) -> Vec<[usize; 3]> {
+        mesh.faces.iter().map(|f| f.vertices).collect()
+    }
+
+    #[test]
+    fn ascii() {
+        let mesh = read_ply(
+            b"ply\nformat ascii 1.0\ncomment made by hand\nelement vertex 4\nproperty float x\nproperty float y\nproperty float z\nproperty float nx\nproperty float ny\nproperty float nz\nproperty uchar red\nproperty uchar green\nproperty uchar blue\nproperty float u\nproperty float v\nelement face 1\nproperty list uchar int vertex_indices\nelement edge 1\nproperty int vertex1\nproperty int vertex2\nend_header\n0 0 0 0 0 1 255 0 0 0 0\n1 0 0 0 0 1 255 0 0 1 0\n1 1 0 0 0 1 0 0 255 1 1\n0 1 0 0 0 1 0 0 255 0 1\n4 0 1 2 3\n0 1\n",
+            "a.ply",
+        )
+        .unwrap();
+
+        assert_eq!(mesh.vertices[2], Vect3::new(1.0, 1.0, 0.0));
+        assert_eq!(faces(&mesh), vec![[3, 0, 1], [1, 2, 3]]);
+        assert!(mesh.warnings.is_empty());
+        // Triangles get the mean color of their vertices
+        let (r, g, b) = mesh.triangles(&[], &[])[0].get_mat().color.get_value();
+        assert!((r - 2.0 / 3.0).abs() < 1e-12 && g == 0.0 && (b - 1.0 / 3.0).abs() < 1e-12);
+    }
+
+    /// Binary file of a triangle (x of vertices as doubles, faces with a short property)
+    fn binary(big_endian: bool) -> Vec<u8> {
+        let format = if big_endian { "big" } else { "little" };
+        let mut bytes = format!("ply\nformat binary_{}_endian 1.0\nelement vertex 3\nproperty double x\nproperty float y\nproperty float z\nelement face 1\nproperty list uchar uint vertex_indices\nproperty short flags\nend_header\n", format).into_bytes();
+        let mut push = |le: &[u8]| match big_endian {
+            true => bytes.extend(le.iter().rev()),
+            false => bytes.extend(le),
+        };
+        for (x, y, z) in [(0.0, 0.0, 0.0), (2.0, 0.0, 0.0), (0.0, 2.0, -1.5)] {
+            push(&f64::to_le_bytes(x));
+            push(&f32::to_le_bytes(y));
+            push(&f32::to_le_bytes(z));
+        }
+        push(&[3]);
+        for i in [0u32, 1, 2] {
+            push(&i.to_le_bytes());
+        }
+        push(&(-1i16).to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn binary_formats() {
+        for big_endian in [false, true] {
+            let mesh = read_ply(&binary(big_endian), "a.ply").unwrap();
+
+            assert_eq!(mesh.vertices[2], Vect3::new(0.0, 2.0, -1.5));
+            assert_eq!(faces(&mesh), vec![[0, 1, 2]]);
+        }
+
+        let mut truncated = binary(false);
+        truncated.pop();
+        assert_eq!(
+            read_ply(&truncated, "a.ply").unwrap_err().0[0].to_string(),
+            "a.ply: unexpected end of file (`face` 1 of 1)"
+        );
+    }
+
+    #[test]
+    fn ply_errors() {
+        assert_eq!(
+            errors(b"ply\nformat ascii 1.0\nproperty float w\nelement vertex x\nelement vertex 3\nproperty vec3 y\nend_header\n"),
+            vec![
+                (3, 1, "`property` before the first `element`".to_string()),
+                (4, 1, "expected `element <name> <count>`".to_string()),
+                (6, 10, "unknown property type `vec3`".to_string()),
+            ]
+        );
+        let header = "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\nelement face 2\nproperty list uchar int vertex_indices\nend_header\n";
+        assert_eq!(
+            errors(format!("{}0 0 0\n1 0 0\n0 1 0\n3 0 1 5\n2 0 1\n", header).as_bytes()),
+            vec![
+                (
+                    13,
+                    1,
+                    "face uses vertex 5, but 3 vertices are defined".to_string()
+                ),
+                (
+                    14,
+                    1,
+                    "face has 2 vertices, at least 3 are needed".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            errors(format!("{}0 0 0\n1 a 0\n", header).as_bytes()),
+            vec![(11, 3, "expected a number, found `a`".to_string())]
+        );
+        assert_eq!(
+            errors(b"format ascii 1.0\n"),
+            vec![(1, 1, "not a PLY file (`ply` expected)".to_string())]
+        );
+    }
+}
@@ -155,7 +155,7 @@ impl Add for Vect3 {
     }
 }
 
-impl<'a> Add<Vect3> for &'a Vect3 {
+impl Add<Vect3> for &Vect3 {
     type Output = Vect3;
 
     #[inline]
@@ -177,7 +177,7 @@ impl<'b> Add<&'b Vect3> for Vect3 {
     }
 }
 
-impl<'a, 'b> Add<&'b Vect3> for &'a Vect3 {
+impl<'b> Add<&'b Vect3> for &Vect3 {
     type Output = Vect3;
 
     #[inline]
@@ -352,6 +352,58 @@ impl Quat {
         }
     }
 
+    /// Quaternion length
+    #[inline]
+    pub fn norm(&self) -> f64 {
+        let squared = self.simd * self.simd;
+        (squared[0] + squared[1] + squared[2] + squared[3]).sqrt()
+    }
+
+    /// Dot product of 2 quaternions
+    #[inline]
+    pub fn dot(&self, other: &Self) -> f64 {
+        let product = self.simd * other.simd;
+        product[0] + product[1] + product[2] + product[3]
+    }
+
+    /// Spherical linear interpolation between 2 quaternions
+    ///
+    /// Rotations are interpolated on their normalized quaternions, the length
+    /// is interpolated linearly so that t = 0 and t = 1 give back self and other
+    pub fn slerp(self, other: Self, t: f64) -> Self {
+        let (norm_1, norm_2) = (self.norm(), other.norm());
+        if norm_1 == 0.0 || norm_2 == 0.0 {
+            return self;
+        }
+
+        let start = self.simd / f64x4::splat(norm_1);
+        let mut end = other.simd / f64x4::splat(norm_2);
+        let mut cos = (Quat { simd: start }).dot(&Quat { simd: end });
+
+        // Takes the shortest path
+        if cos < 0.0 {
+            cos = -cos;
+            end = -end;
+        }
+
+        let rotation = if cos > 0.9995 {
+            let simd = start + (end - start) * f64x4::splat(t);
+            Quat { simd }.normalize()
+        } else {
+            let theta = cos.acos();
+            let sin = theta.sin();
+            let a = ((1.0 - t) * theta).sin() / sin;
+            let b = (t * theta).sin() / sin;
+            Quat {
+                simd: start * f64x4::splat(a) + end * f64x4::splat(b),
+            }
+        };
+
+        Quat {
+            simd: rotation.simd * f64x4::splat(norm_1 + (norm_2 - norm_1) * t),
+        }
+    }
+
     /// Rotates a 3D vector from self
     #[inline]
     pub fn rotate(self, v: Vect3) -> Vect3 {
@@ -429,6 +481,20 @@ impl Transform {
         self.position
     }
 
+    /// Get transform rotation
+    #[inline]
+    pub fn get_rot(&self) -> Quat {
+        self.rotation
+    }
+
+    /// Interpolates between 2 transforms (lerp for position, slerp for rotation)
+    pub fn interpolate(&self, other: &Transform, t: f64) -> Transform {
+        Transform {
+            position: lerp(self.position, other.position, t),
+            rotation: self.rotation.slerp(other.rotation, t),
+        }
+    }
+
     /// Get transform X axis
     pub fn get_x_axis(&self) -> Vect3 {
         self.rotation.rotate(Vect3::RIGHT)
@@ -627,6 +693,38 @@ mod tests_quaternions {
         assert_abs_diff_eq!(transform.get_z_axis(), Vect3::FORWARD);
     }
 
+    #[test]
+    fn slerp() {
+        let start = Quat::identity();
+        let end = Quat::from_axis_angle_deg(Vect3::UP, 90.0);
+
+        assert_abs_diff_eq!(start.slerp(end, 0.0).rotate(Vect3::RIGHT), Vect3::RIGHT);
+        assert_abs_diff_eq!(
+            start.slerp(end, 1.0).rotate(Vect3::RIGHT),
+            end.rotate(Vect3::RIGHT)
+        );
+        assert_abs_diff_eq!(
+            start.slerp(end, 0.5).rotate(Vect3::RIGHT),
+            Quat::from_axis_angle_deg(Vect3::UP, 45.0).rotate(Vect3::RIGHT)
+        );
+    }
+
+    #[test]
+    fn transform_interpolation() {
+        let start = Transform::new(Vect3::ZERO, Quat::identity());
+        let end = Transform::new(
+            Vect3::new(2.0, 0.0, 0.0),
+            Quat::from_axis_angle_deg(Vect3::FORWARD, 90.0),
+        );
+        let middle = start.interpolate(&end, 0.5);
+
+        assert_abs_diff_eq!(middle.get_pos(), Vect3::new(1.0, 0.0, 0.0));
+        assert_abs_diff_eq!(
+            middle.get_x_axis(),
+            Vect3::new(0.5_f64.sqrt(), 0.5_f64.sqrt(), 0.0)
+        );
+    }
+
     #[test]
     fn rotate_around_all_axis() {
         let mut transform = Transform::new(Vect3::ZERO, Quat::identity());
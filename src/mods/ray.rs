@@ -6,12 +6,26 @@ use super::{material::Material, position::Vect3};
 pub struct Ray {
     start: Vect3,
     direction: Vect3,
+    time: f64,
 }
 
 impl Ray {
     /// New Ray constructor
     pub fn new(start: Vect3, direction: Vect3) -> Self {
-        Self { start, direction }
+        Self {
+            start,
+            direction,
+            time: 0.0,
+        }
+    }
+
+    /// New Ray constructor at a given time of the shutter interval
+    pub fn new_at_time(start: Vect3, direction: Vect3, time: f64) -> Self {
+        Self {
+            start,
+            direction,
+            time,
+        }
     }
 
     /// Get Ray start
@@ -25,6 +39,12 @@ impl Ray {
     pub fn get_dir(&self) -> Vect3 {
         self.direction
     }
+
+    /// Get Ray time
+    #[inline]
+    pub fn get_time(&self) -> f64 {
+        self.time
+    }
 }
 
 /// Intersection implementation
@@ -103,6 +123,15 @@ mod tests_rays {
         assert_eq!(ray.get_start(), Vect3::ZERO);
         assert_eq!(ray.get_dir(), Vect3::UP);
     }
+
+    #[test]
+    fn ray_time() {
+        assert_eq!(Ray::new(Vect3::ZERO, Vect3::UP).get_time(), 0.0);
+        assert_eq!(
+            Ray::new_at_time(Vect3::ZERO, Vect3::UP, 0.5).get_time(),
+            0.5
+        );
+    }
 }
 
 #[cfg(test)]
@@ -1,30 +1,81 @@
 use core::f64;
-use std::error::Error;
+use std::{
+    error::Error,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use console::style;
-use indicatif::{ProgressBar, ProgressStyle};
 use rayon::prelude::*;
 
 use crate::mods::funcs::s_to_hms;
 
 use super::{
-    color::{ColorRBG, ColorRBGOF},
-    objs::{Camera, Plane, Sphere, Triangle},
+    buffer::AccBuffer,
+    checkpoint::{save_checkpoint, Checkpoint, CheckpointInfo},
+    color::ColorRBG,
+    config::{AdaptiveSampling, ProgressiveMode},
+    image::ppm_filename,
+    objs::{Camera, Plane, Sphere, Triangle, TriangleGroup},
     position::lerp,
     random::LCG,
     ray::{Intersection, Ray},
+    report::{estimate_eta, Progress, QuietReporter, RenderSettings, Reporter, Stage},
+    tile::{spiral_tiles, DEFAULT_TILE_SIZE},
 };
 
 static BIAS: f64 = 1e-5;
 
+/// Base seed of the pixel randomizers
+pub static SEED: u64 = 123456789;
+
+/// Minimum number of iterations before trusting the noise estimate
+static MIN_NOISE_ITERATIONS: usize = 4;
+
+/// Progressive output: the current image is written to a file during the render
+#[derive(Debug, Clone)]
+pub struct Progressive {
+    filename: String,
+    mode: ProgressiveMode,
+}
+
+impl Progressive {
+    /// New Progressive constructor
+    pub fn new(filename: &str, mode: ProgressiveMode) -> Progressive {
+        Progressive {
+            filename: filename.to_string(),
+            mode,
+        }
+    }
+
+    /// Is an image due, given the time since the last one
+    fn is_due(&self, since_last: f64) -> bool {
+        match self.mode {
+            ProgressiveMode::EachIteration => true,
+            ProgressiveMode::Every(interval) => since_last >= interval.as_secs_f64(),
+        }
+    }
+}
+
 /// Scene implementation
 pub struct Scene {
     pub camera: Camera,
     pub spheres: Vec<Sphere>,
     pub planes: Vec<Plane>,
     pub triangles: Vec<Triangle>,
+    /// Cube and mesh blocks of the parsed scene file, with their triangles
+    pub triangle_groups: Vec<TriangleGroup>,
     render_iterations: usize,
     max_bounces: u32,
+    progressive: Option<Progressive>,
+    time_limit: Option<Duration>,
+    target_noise: Option<f64>,
+    adaptive: Option<AdaptiveSampling>,
+    acc_buffer: Option<AccBuffer>,
+    checkpoint: Option<Checkpoint>,
+    resume: Option<(CheckpointInfo, AccBuffer)>,
+    first_iteration: usize,
+    tile_size: usize,
+    reporter: Arc<dyn Reporter>,
 }
 
 impl Scene {
@@ -40,82 +91,248 @@ impl Scene {
             spheres,
             planes,
             triangles,
+            triangle_groups: vec![],
             render_iterations: 10,
             max_bounces: 10,
+            progressive: None,
+            time_limit: None,
+            target_noise: None,
+            adaptive: None,
+            acc_buffer: None,
+            checkpoint: None,
+            resume: None,
+            first_iteration: 0,
+            tile_size: DEFAULT_TILE_SIZE,
+            reporter: Arc::new(QuietReporter),
         }
     }
 
+    /// Set progressive output
+    pub fn set_progressive(&mut self, progressive: Option<Progressive>) {
+        self.progressive = progressive;
+    }
+
+    /// Set render time budget: no iteration is started once it is used up
+    pub fn set_time_limit(&mut self, time_limit: Option<Duration>) {
+        self.time_limit = time_limit;
+    }
+
+    /// Set noise threshold: the render stops once the estimated noise is below it
+    pub fn set_target_noise(&mut self, target_noise: Option<f64>) {
+        self.target_noise = target_noise;
+    }
+
+    /// Set adaptive sampling
+    pub fn set_adaptive(&mut self, adaptive: Option<AdaptiveSampling>) {
+        self.adaptive = adaptive;
+    }
+
+    /// Set reporter, told about the render (nothing is reported by default)
+    pub fn set_reporter(&mut self, reporter: Arc<dyn Reporter>) {
+        self.reporter = reporter;
+    }
+
+    /// Set side of the square tiles the image is split into for rendering
+    pub fn set_tile_size(&mut self, tile_size: usize) {
+        self.tile_size = tile_size.max(1);
+    }
+
+    /// Set periodic checkpoints
+    pub fn set_checkpoint(&mut self, checkpoint: Option<Checkpoint>) {
+        self.checkpoint = checkpoint;
+    }
+
+    /// Set index of the first iteration: rendering the iterations from it to render_iterations
+    /// gives samples independent of other iteration ranges
+    pub fn set_first_iteration(&mut self, first_iteration: usize) {
+        self.first_iteration = first_iteration;
+    }
+
+    /// Continue the next render from a checkpoint (validated by the caller)
+    pub fn resume_from(&mut self, info: CheckpointInfo, acc_buffer: AccBuffer) {
+        self.resume = Some((info, acc_buffer));
+    }
+
+    /// Get accumulated samples of the last render
+    pub fn get_acc_buffer(&self) -> Option<&AccBuffer> {
+        self.acc_buffer.as_ref()
+    }
+
     /// Render Scene
     pub fn render(&mut self, render_iterations: usize, max_bounces: u32, resolution: (u32, u32)) {
-        print_render_info(render_iterations, max_bounces, resolution.0, resolution.1);
+        let range_start = match &self.resume {
+            Some((info, _)) => info.first_iteration,
+            None => self.first_iteration,
+        };
+        self.reporter.stage_started(Stage::Rendering, "");
+        self.reporter.render_started(&RenderSettings {
+            first_iteration: range_start,
+            render_iterations,
+            max_bounces,
+            resolution,
+        });
         self.render_iterations = render_iterations;
         self.max_bounces = max_bounces;
         self.camera.set_image_resolution(resolution.0, resolution.1);
 
-        let camera_pos = self.camera.transform.get_pos();
+        // A moving camera with an instantaneous shutter is seen when the shutter opens
+        let camera_transform = self.camera.transform_at(self.camera.get_shutter().0);
+        let camera_pos = camera_transform.get_pos();
         let camera_axis = (
-            self.camera.transform.get_x_axis(),
-            self.camera.transform.get_y_axis(),
-            self.camera.transform.get_z_axis(),
+            camera_transform.get_x_axis(),
+            camera_transform.get_y_axis(),
+            camera_transform.get_z_axis(),
         );
+        let camera_moving = self.camera.is_moving();
 
         let width = self.camera.image.get_width();
         let height = self.camera.image.get_height();
-        let mut acc_buffer = vec![ColorRBGOF::BLACK; width * height];
+        let (first_iteration, mut acc_buffer) = match self.resume.take() {
+            Some((info, acc_buffer)) => (info.next_iteration, acc_buffer),
+            None => (range_start, AccBuffer::new(width, height)),
+        };
 
         let scene = &self;
-        let bar = ProgressBar::new(self.render_iterations as u64);
-        bar.set_style(ProgressStyle::default_bar().template(
-            "[{elapsed_precise}] {bar:70.cyan/blue} {pos:>7}/{len:7} ({percent}%) | ETA: {eta}",
-        ).unwrap().progress_chars("█░"));
-        bar.inc(0);
-
-        for f in 0..self.render_iterations {
-            let all_pixels: Vec<(usize, usize)> = (0..width)
-                .flat_map(|x| (0..height).map(move |y| (x, y)))
-                .collect();
-
-            let frame_results: Vec<(usize, usize, ColorRBG)> = all_pixels
-                .into_par_iter()
-                .map(|(x, y)| {
-                    let pixel_seed = 123456789_u64
-                        .wrapping_add(f as u64 * 0xA24BAED4)
-                        .wrapping_add((y * width + x) as u64 * 0x9E3779B9)
-                        .wrapping_mul(74747_u64);
-                    let mut local_randomizer = LCG::new(pixel_seed);
-
-                    let ray = Ray::new(
-                        camera_pos,
-                        scene.camera.get_ray_direction(camera_axis, x, y),
-                    );
-                    let color = scene.trace(&ray, &mut local_randomizer, 0);
-
-                    (x, y, color)
-                })
-                .collect();
-            for (x, y, color) in frame_results {
-                let idx = y * width + x;
-                acc_buffer[idx] = acc_buffer[idx] + color;
+        let reporter = scene.reporter.clone();
+        let start = Instant::now();
+        if first_iteration > range_start {
+            reporter.info(&format!("Resuming from iteration {}", first_iteration));
+            reporter.iteration_done(&Progress {
+                iteration: first_iteration,
+                render_iterations,
+                elapsed: Duration::ZERO,
+                eta: None,
+            });
+        }
+        let mut done = first_iteration;
+        let mut last_write = Instant::now();
+        let mut stop_reason = None;
+
+        let sample_pixel = |f: usize, x: usize, y: usize| {
+            let mut local_randomizer = LCG::new(pixel_seed(f, y * width + x));
+
+            let time = scene.camera.sample_time(&mut local_randomizer);
+            let ray = if camera_moving {
+                let transform = scene.camera.transform_at(time);
+                let axis = (
+                    transform.get_x_axis(),
+                    transform.get_y_axis(),
+                    transform.get_z_axis(),
+                );
+                Ray::new_at_time(
+                    transform.get_pos(),
+                    scene.camera.get_ray_direction(axis, x, y),
+                    time,
+                )
+            } else {
+                Ray::new_at_time(
+                    camera_pos,
+                    scene.camera.get_ray_direction(camera_axis, x, y),
+                    time,
+                )
+            };
+            scene.trace(&ray, &mut local_randomizer, 0)
+        };
+
+        // Each tile is rendered by a single task into its own buffer, reused between iterations
+        let tiles = spiral_tiles(width, height, scene.tile_size);
+        let mut tile_buffers: Vec<Vec<Option<ColorRBG>>> =
+            tiles.iter().map(|tile| vec![None; tile.area()]).collect();
+
+        for f in first_iteration..self.render_iterations {
+            let adaptive = scene.adaptive.filter(|a| f - range_start >= a.warmup);
+            let acc = &acc_buffer;
+            tiles
+                .par_iter()
+                .zip(tile_buffers.par_iter_mut())
+                .for_each(|(tile, buffer)| {
+                    for ((x, y), sample) in tile.pixels().zip(buffer.iter_mut()) {
+                        *sample = match adaptive {
+                            Some(a) if acc.get(x, y).relative_error() <= a.threshold => None,
+                            _ => Some(sample_pixel(f, x, y)),
+                        };
+                    }
+                });
+
+            let mut sampled = 0;
+            for (tile, buffer) in tiles.iter().zip(&tile_buffers) {
+                for ((x, y), sample) in tile.pixels().zip(buffer) {
+                    if let Some(color) = sample {
+                        acc_buffer.add_sample(x, y, *color);
+                        sampled += 1;
+                    }
+                }
+            }
+            if sampled == 0 {
+                stop_reason = Some(String::from("all pixels converged"));
+                break;
+            }
+            done = f + 1;
+            let elapsed = start.elapsed();
+            reporter.iteration_done(&Progress {
+                iteration: done,
+                render_iterations,
+                elapsed,
+                eta: estimate_eta(elapsed, done - first_iteration, render_iterations - done),
+            });
+
+            if let Some(limit) = scene.time_limit {
+                if elapsed >= limit && done < scene.render_iterations {
+                    stop_reason = Some(format!("time limit of {}", s_to_hms(limit.as_secs_f64())));
+                }
+            }
+            if let Some(target) = scene.target_noise {
+                if done - range_start >= MIN_NOISE_ITERATIONS
+                    && done < scene.render_iterations
+                    && acc_buffer.noise() <= target
+                {
+                    stop_reason = Some(format!("noise below {}", target));
+                }
+            }
+            let is_last = done == scene.render_iterations || stop_reason.is_some();
+
+            if let Some(progressive) = &scene.progressive {
+                if !is_last && progressive.is_due(last_write.elapsed().as_secs_f64()) {
+                    let image = acc_buffer.to_image();
+                    if let Err(e) = image.write_ppm_atomic(&progressive.filename) {
+                        reporter.warning(&format!("Could not write progressive image : {e}"));
+                    }
+                    last_write = Instant::now();
+                }
             }
 
-            bar.inc(1);
-        }
+            if let Some(checkpoint) = &scene.checkpoint {
+                if is_last || done % checkpoint.every == 0 {
+                    let info = CheckpointInfo {
+                        scene_hash: checkpoint.scene_hash,
+                        seed: SEED,
+                        first_iteration: range_start,
+                        max_bounces: scene.max_bounces,
+                        next_iteration: done,
+                    };
+                    if let Err(e) = save_checkpoint(&checkpoint.filename, &info, &acc_buffer) {
+                        reporter.warning(&format!("Could not write checkpoint : {e}"));
+                    }
+                }
+            }
 
-        let coeff = 1.0 / self.render_iterations as f64;
-        for y in 0..height {
-            for x in 0..width {
-                let idx = y * width + x;
-                let avg_color = (coeff * acc_buffer[idx]).to_rgb();
-                self.camera.image.set_pixel(x, y, avg_color.rgb());
+            if is_last {
+                break;
             }
         }
 
-        let render_time = bar.elapsed();
-        bar.finish_and_clear();
-        println!(
-            "      Rendered scene in {}",
-            style(s_to_hms(render_time.as_secs_f64())).bold().white()
-        );
+        self.camera.image = acc_buffer.to_image();
+
+        if let Some(reason) = stop_reason {
+            reporter.info(&format!(
+                "Stopped after {} iterations ({}), estimated noise : {:.4}",
+                done,
+                reason,
+                acc_buffer.noise()
+            ));
+        }
+        reporter.stage_finished(Stage::Rendering, start.elapsed());
+        self.acc_buffer = Some(acc_buffer);
     }
 
     /// Trace ray
@@ -143,7 +360,7 @@ impl Scene {
                 };
 
                 let ray_origin = inter.point + inter.normal * BIAS;
-                let new_ray = Ray::new(ray_origin, ray_dir);
+                let new_ray = Ray::new_at_time(ray_origin, ray_dir, ray.get_time());
 
                 let emitted = inter.material.get_emited_light();
 
@@ -198,41 +415,162 @@ impl Scene {
         closest_intersection
     }
 
-    /// Prints Scene information
+    /// Reports Scene information
     pub fn get_info(&self) {
-        println!("      Parsed scene containing :");
-        println!(
-            "        - {} speres",
-            style(self.spheres.len()).bold().blue()
-        );
-        println!(
-            "        - {} planes",
-            style(self.planes.len()).bold().blue()
-        );
-        println!(
-            "        - {} triangles",
-            style(self.triangles.len()).bold().blue()
-        );
+        self.reporter
+            .scene_parsed(self.spheres.len(), self.planes.len(), self.triangles.len());
     }
 
     /// Save Scene image to ppm file
     pub fn save_image(&mut self, filename: &str) -> Result<(), Box<dyn Error>> {
-        self.camera.image.save_as_ppm(filename)?;
+        let filename = ppm_filename(filename);
+        let start = Instant::now();
+        self.reporter.stage_started(Stage::Saving, &filename);
+        self.camera.image.write_ppm_atomic(&filename)?;
+        self.reporter.stage_finished(Stage::Saving, start.elapsed());
         Ok(())
     }
 }
 
-/// Prints render information
-fn print_render_info(ri: usize, mb: u32, w: u32, h: u32) {
-    println!(
-        "{} Rendering scene with these parameters :",
-        style("[2/3]").bold().green()
-    );
-    println!("        - Iterations : {}", style(ri).bold().blue());
-    println!("        - Max bounces : {}", style(mb).bold().blue());
-    println!(
-        "        - Image resolution : {} x {} pixels",
-        style(w).bold().blue(),
-        style(h).bold().blue()
-    );
+/// Seed of the randomizer of a pixel (by row-major index) for a given iteration
+#[inline]
+fn pixel_seed(iteration: usize, index: usize) -> u64 {
+    SEED.wrapping_add(iteration as u64 * 0xA24BAED4)
+        .wrapping_add(index as u64 * 0x9E3779B9)
+        .wrapping_mul(74747_u64)
+}
+
+#[cfg(test)]
+mod tests_render {
+    use std::sync::{Arc, Mutex};
+
+    use super::Scene;
+    use crate::mods::{config::AdaptiveSampling, parser::Parser, report::Reporter};
+
+    /// A light seen by the camera: its pixels and the black background have no noise
+    static LIGHT: &str = "camera {
+    position: 0.0 0.0 5.0
+    rotation: 0.0 0.0 1.0 0.0
+    focal_length: 5.0
+    fov: 40.0
+}
+material {
+    name: light
+    color: 0.0 0.0 0.0
+    emission_color: 1.0 1.0 1.0
+    specular_color: 0.0 0.0 0.0
+    emission_strength: 1.0
+    smoothness: 0.0
+    specular_prob: 0.0
+}
+sphere {
+    position: -1.0 0.0 0.0
+    radius: 0.8
+    mat: light
+}
+";
+
+    /// A white diffuse sphere lit by the light, noisy
+    static LIT: &str = "material {
+    name: white
+    color: 1.0 1.0 1.0
+    emission_color: 0.0 0.0 0.0
+    specular_color: 0.0 0.0 0.0
+    emission_strength: 0.0
+    smoothness: 0.0
+    specular_prob: 0.0
+}
+sphere {
+    position: 1.0 0.0 0.0
+    radius: 0.8
+    mat: white
+}
+";
+
+    /// Keeps the info messages
+    #[derive(Default)]
+    struct Messages(Mutex<Vec<String>>);
+
+    impl Reporter for Messages {
+        fn info(&self, message: &str) {
+            self.0.lock().unwrap().push(message.to_string());
+        }
+    }
+
+    fn parse(source: &str) -> (Scene, Arc<Messages>) {
+        let mut scene = Parser::from_source(source).parse_scene().unwrap();
+        let messages = Arc::new(Messages::default());
+        scene.set_reporter(messages.clone());
+        (scene, messages)
+    }
+
+    #[test]
+    fn adaptive() {
+        let (mut scene, _) = parse(&format!("{}{}", LIGHT, LIT));
+        let adaptive = AdaptiveSampling {
+            warmup: 16,
+            threshold: 0.05,
+        };
+        scene.set_adaptive(Some(adaptive));
+        scene.render(40, 4, (16, 12));
+        let acc_buffer = scene.get_acc_buffer().unwrap();
+
+        let mut converged = 0;
+        let mut noisy = 0;
+        for y in 0..12 {
+            for x in 0..16 {
+                let pixel = acc_buffer.get(x, y);
+                assert!(pixel.samples >= 16);
+                if pixel.samples < 40 {
+                    converged += 1;
+                    assert!(pixel.relative_error() <= adaptive.threshold);
+                } else if pixel.relative_error() > adaptive.threshold {
+                    noisy += 1;
+                }
+            }
+        }
+        // The background and the light stop after the warmup, the lit sphere keeps being sampled
+        assert_eq!(acc_buffer.get(0, 0).samples, 16);
+        assert_eq!(acc_buffer.get(3, 6).samples, 16);
+        assert_eq!(acc_buffer.get(10, 6).samples, 40);
+        assert!(converged > 0);
+        assert!(noisy > 0);
+    }
+
+    #[test]
+    fn all_converged() {
+        let (mut scene, messages) = parse(LIGHT);
+        scene.set_adaptive(Some(AdaptiveSampling {
+            warmup: 3,
+            threshold: 0.05,
+        }));
+        scene.render(40, 4, (16, 12));
+        let acc_buffer = scene.get_acc_buffer().unwrap();
+
+        assert!((0..12).all(|y| (0..16).all(|x| acc_buffer.get(x, y).samples == 3)));
+        assert_eq!(
+            messages.0.lock().unwrap().last().unwrap(),
+            "Stopped after 3 iterations (all pixels converged), estimated noise : 0.0000"
+        );
+    }
+
+    #[test]
+    fn target_noise() {
+        let (mut scene, messages) = parse(LIGHT);
+        scene.set_target_noise(Some(0.01));
+        scene.render(40, 4, (16, 12));
+
+        assert_eq!(scene.get_acc_buffer().unwrap().get(8, 6).samples, 4);
+        assert_eq!(
+            messages.0.lock().unwrap().last().unwrap(),
+            "Stopped after 4 iterations (noise below 0.01), estimated noise : 0.0000"
+        );
+
+        let (mut scene, messages) = parse(&format!("{}{}", LIGHT, LIT));
+        scene.set_target_noise(Some(0.01));
+        scene.render(10, 4, (16, 12));
+
+        assert_eq!(scene.get_acc_buffer().unwrap().get(8, 6).samples, 10);
+        assert!(messages.0.lock().unwrap().is_empty());
+    }
 }
@@ -0,0 +1,340 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use console::style;
+use indicatif::{ProgressBar, ProgressStyle};
+use serde_json::json;
+
+use super::funcs::s_to_hms;
+
+/// Stage of a render
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stage {
+    Parsing,
+    Rendering,
+    Saving,
+}
+
+impl Stage {
+    /// Get stage name
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::Parsing => "parsing",
+            Stage::Rendering => "rendering",
+            Stage::Saving => "saving",
+        }
+    }
+}
+
+/// Render settings, given when the rendering stage starts
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderSettings {
+    pub first_iteration: usize,
+    pub render_iterations: usize,
+    pub max_bounces: u32,
+    pub resolution: (u32, u32),
+}
+
+/// Render progress, given after each iteration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    pub iteration: usize,
+    pub render_iterations: usize,
+    pub elapsed: Duration,
+    pub eta: Option<Duration>,
+}
+
+/// Receives what the library is doing, instead of it printing to the console
+///
+/// Every method does nothing by default
+pub trait Reporter: Send + Sync {
+    /// A stage started, working on a file (scene file, mesh file or image file)
+    fn stage_started(&self, _stage: Stage, _file: &str) {}
+
+    /// A mesh file is being parsed
+    fn mesh_parsing(&self, _file: &str) {}
+
+    /// The scene was parsed
+    fn scene_parsed(&self, _spheres: usize, _planes: usize, _triangles: usize) {}
+
+    /// The rendering started
+    fn render_started(&self, _settings: &RenderSettings) {}
+
+    /// An iteration was rendered
+    fn iteration_done(&self, _progress: &Progress) {}
+
+    /// A frame of an image sequence started
+    fn frame_started(&self, _frame: u32, _index: usize, _frame_count: usize) {}
+
+    /// A stage finished
+    fn stage_finished(&self, _stage: Stage, _elapsed: Duration) {}
+
+    /// Information on the render (early stop, resumed render...)
+    fn info(&self, _message: &str) {}
+
+    /// Something failed without stopping the render
+    fn warning(&self, _message: &str) {}
+
+    /// The render failed
+    fn error(&self, _message: &str) {}
+}
+
+/// Reporter printing nothing
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QuietReporter;
+
+impl Reporter for QuietReporter {}
+
+/// Reporter printing colored text and a progress bar
+#[derive(Debug, Default)]
+pub struct ConsoleReporter {
+    bar: Mutex<Option<ProgressBar>>,
+}
+
+impl ConsoleReporter {
+    /// New ConsoleReporter constructor
+    pub fn new() -> ConsoleReporter {
+        ConsoleReporter::default()
+    }
+
+    /// Prints a line above the progress bar, if any
+    fn println(&self, line: String) {
+        match self.bar.lock().unwrap().as_ref() {
+            Some(bar) => bar.println(line),
+            None => println!("{}", line),
+        }
+    }
+}
+
+impl Reporter for ConsoleReporter {
+    fn stage_started(&self, stage: Stage, file: &str) {
+        match stage {
+            Stage::Parsing => println!(
+                "{} Parsing scene file : {}",
+                style("[1/3]").bold().green(),
+                style(file).italic().dim()
+            ),
+            Stage::Rendering => {}
+            Stage::Saving => println!(
+                "{} Saving image to : {}",
+                style("[3/3]").bold().green(),
+                style(file).italic().dim()
+            ),
+        }
+    }
+
+    fn mesh_parsing(&self, file: &str) {
+        println!(
+            "        - Parsing mesh file : {}",
+            style(file).italic().dim()
+        );
+    }
+
+    fn scene_parsed(&self, spheres: usize, planes: usize, triangles: usize) {
+        println!("      Parsed scene containing :");
+        println!("        - {} speres", style(spheres).bold().blue());
+        println!("        - {} planes", style(planes).bold().blue());
+        println!("        - {} triangles", style(triangles).bold().blue());
+    }
+
+    fn render_started(&self, settings: &RenderSettings) {
+        println!(
+            "{} Rendering scene with these parameters :",
+            style("[2/3]").bold().green()
+        );
+        if settings.first_iteration == 0 {
+            println!(
+                "        - Iterations : {}",
+                style(settings.render_iterations).bold().blue()
+            );
+        } else {
+            println!(
+                "        - Iterations : {}..{}",
+                style(settings.first_iteration).bold().blue(),
+                style(settings.render_iterations).bold().blue()
+            );
+        }
+        println!(
+            "        - Max bounces : {}",
+            style(settings.max_bounces).bold().blue()
+        );
+        println!(
+            "        - Image resolution : {} x {} pixels",
+            style(settings.resolution.0).bold().blue(),
+            style(settings.resolution.1).bold().blue()
+        );
+
+        let bar = ProgressBar::new(settings.render_iterations as u64);
+        bar.set_style(ProgressStyle::default_bar().template(
+            "[{elapsed_precise}] {bar:70.cyan/blue} {pos:>7}/{len:7} ({percent}%) | ETA: {eta}",
+        ).unwrap().progress_chars("█░"));
+        bar.set_position(settings.first_iteration as u64);
+        bar.reset_eta();
+        *self.bar.lock().unwrap() = Some(bar);
+    }
+
+    fn iteration_done(&self, progress: &Progress) {
+        if let Some(bar) = self.bar.lock().unwrap().as_ref() {
+            bar.set_position(progress.iteration as u64);
+        }
+    }
+
+    fn frame_started(&self, frame: u32, index: usize, frame_count: usize) {
+        println!(
+            "{} Frame {} ({}/{})",
+            style("[frame]").bold().magenta(),
+            style(frame).bold().blue(),
+            index + 1,
+            frame_count
+        );
+    }
+
+    fn stage_finished(&self, stage: Stage, elapsed: Duration) {
+        match stage {
+            Stage::Parsing => {}
+            Stage::Rendering => {
+                if let Some(bar) = self.bar.lock().unwrap().take() {
+                    bar.finish_and_clear();
+                }
+                println!(
+                    "      Rendered scene in {}",
+                    style(s_to_hms(elapsed.as_secs_f64())).bold().white()
+                );
+            }
+            Stage::Saving => println!("      Saved image to ppm file"),
+        }
+    }
+
+    fn info(&self, message: &str) {
+        self.println(format!("      {}", message));
+    }
+
+    fn warning(&self, message: &str) {
+        self.println(format!("      {}", style(message).yellow()));
+    }
+
+    fn error(&self, message: &str) {
+        println!("Erreur : {message}");
+    }
+}
+
+/// Reporter printing one JSON object per line ("event" field giving its kind)
+#[derive(Debug)]
+pub struct JsonReporter {
+    start: Instant,
+}
+
+impl JsonReporter {
+    /// New JsonReporter constructor
+    pub fn new() -> JsonReporter {
+        JsonReporter {
+            start: Instant::now(),
+        }
+    }
+
+    fn emit(&self, mut event: serde_json::Value) {
+        event["time"] = json!(self.start.elapsed().as_secs_f64());
+        println!("{}", event);
+    }
+}
+
+impl Default for JsonReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Reporter for JsonReporter {
+    fn stage_started(&self, stage: Stage, file: &str) {
+        self.emit(json!({"event": "stage_started", "stage": stage.name(), "file": file}));
+    }
+
+    fn mesh_parsing(&self, file: &str) {
+        self.emit(json!({"event": "mesh_parsing", "file": file}));
+    }
+
+    fn scene_parsed(&self, spheres: usize, planes: usize, triangles: usize) {
+        self.emit(json!({
+            "event": "scene_parsed",
+            "spheres": spheres,
+            "planes": planes,
+            "triangles": triangles,
+        }));
+    }
+
+    fn render_started(&self, settings: &RenderSettings) {
+        self.emit(json!({
+            "event": "render_started",
+            "first_iteration": settings.first_iteration,
+            "iterations": settings.render_iterations,
+            "max_bounces": settings.max_bounces,
+            "width": settings.resolution.0,
+            "height": settings.resolution.1,
+        }));
+    }
+
+    fn iteration_done(&self, progress: &Progress) {
+        self.emit(json!({
+            "event": "iteration_done",
+            "iteration": progress.iteration,
+            "iterations": progress.render_iterations,
+            "elapsed": progress.elapsed.as_secs_f64(),
+            "eta": progress.eta.map(|eta| eta.as_secs_f64()),
+        }));
+    }
+
+    fn frame_started(&self, frame: u32, index: usize, frame_count: usize) {
+        self.emit(json!({
+            "event": "frame_started",
+            "frame": frame,
+            "index": index,
+            "frames": frame_count,
+        }));
+    }
+
+    fn stage_finished(&self, stage: Stage, elapsed: Duration) {
+        self.emit(json!({
+            "event": "stage_finished",
+            "stage": stage.name(),
+            "elapsed": elapsed.as_secs_f64(),
+        }));
+    }
+
+    fn info(&self, message: &str) {
+        self.emit(json!({"event": "info", "message": message}));
+    }
+
+    fn warning(&self, message: &str) {
+        self.emit(json!({"event": "warning", "message": message}));
+    }
+
+    fn error(&self, message: &str) {
+        self.emit(json!({"event": "error", "message": message}));
+    }
+}
+
+/// Estimated time left, from the time spent on the iterations done so far
+pub fn estimate_eta(elapsed: Duration, done: usize, left: usize) -> Option<Duration> {
+    if done == 0 {
+        return None;
+    }
+    Some(elapsed.mul_f64(left as f64 / done as f64))
+}
+
+#[cfg(test)]
+mod tests_report {
+    use std::time::Duration;
+
+    use super::estimate_eta;
+
+    #[test]
+    fn eta() {
+        assert_eq!(estimate_eta(Duration::from_secs(10), 0, 5), None);
+        assert_eq!(
+            estimate_eta(Duration::from_secs(10), 2, 6),
+            Some(Duration::from_secs(30))
+        );
+    }
+}
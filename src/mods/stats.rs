@@ -0,0 +1,368 @@
+use std::{
+    f64::consts::PI,
+    mem::size_of,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use serde_json::{json, Value};
+
+use super::{
+    buffer::PixelStats,
+    color::ColorRBG,
+    config::StatsFormat,
+    funcs::s_to_hms,
+    objs::{Plane, Sphere, Triangle},
+    position::Vect3,
+    render::Scene,
+    report::QuietReporter,
+};
+
+/// Width of the image rendered to estimate the render time
+static SAMPLE_WIDTH: u32 = 64;
+
+/// Statistics of a parsed scene, and of its render at given settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneStats {
+    pub spheres: usize,
+    pub planes: usize,
+    pub triangles: usize,
+    /// .obj file and triangle count of each mesh
+    pub meshes: Vec<(String, usize)>,
+    /// Bounds of the finite objects at the start and the end of the frame (planes being infinite)
+    pub bounding_box: Option<(Vect3, Vect3)>,
+    /// Memory used by the objects and the image buffers, in bytes
+    pub memory: usize,
+    /// Finite objects emitting light
+    pub emitters: usize,
+    /// Light power emitted by the finite objects (emitted luminance × π × area)
+    pub emitted_power: f64,
+    /// Infinite planes emitting light, their power being unbounded
+    pub emitting_planes: usize,
+    pub render_iterations: usize,
+    pub max_bounces: u32,
+    pub resolution: (u32, u32),
+    /// Maximum number of ray-object intersection tests (every ray being tested against every object)
+    pub intersection_tests: f64,
+    /// Render time measured on a small image, then scaled
+    pub estimated_time: Option<Duration>,
+}
+
+impl SceneStats {
+    /// New SceneStats constructor
+    pub fn new(
+        scene: &Scene,
+        meshes: &[(String, usize)],
+        render_iterations: usize,
+        max_bounces: u32,
+        resolution: (u32, u32),
+    ) -> SceneStats {
+        let emitting = |color: ColorRBG| color != ColorRBG::BLACK;
+        let sphere_power = scene
+            .spheres
+            .iter()
+            .filter(|s| emitting(s.get_mat().get_emited_light()))
+            .map(|s| {
+                emitted_power(
+                    s.get_mat().get_emited_light(),
+                    4.0 * PI * s.radius * s.radius,
+                )
+            });
+        let triangle_power = scene
+            .triangles
+            .iter()
+            .filter(|t| emitting(t.get_mat().get_emited_light()))
+            .map(|t| emitted_power(t.get_mat().get_emited_light(), t.area()));
+        let emitting_planes = scene
+            .planes
+            .iter()
+            .filter(|p| emitting(p.get_mat().get_emited_light()))
+            .count();
+        let (emitters, emitted) = sphere_power
+            .chain(triangle_power)
+            .fold((0, 0.0), |(count, power), p| (count + 1, power + p));
+
+        let objects = scene.spheres.len() + scene.planes.len() + scene.triangles.len();
+        let pixels = resolution.0 as usize * resolution.1 as usize;
+        let memory = scene.spheres.len() * size_of::<Sphere>()
+            + scene.planes.len() * size_of::<Plane>()
+            + scene.triangles.len() * size_of::<Triangle>()
+            + pixels * (size_of::<(u8, u8, u8)>() + size_of::<PixelStats>());
+
+        SceneStats {
+            spheres: scene.spheres.len(),
+            planes: scene.planes.len(),
+            triangles: scene.triangles.len(),
+            meshes: meshes.to_vec(),
+            bounding_box: bounding_box(&scene.spheres, &scene.triangles),
+            memory,
+            emitters,
+            emitted_power: emitted,
+            emitting_planes,
+            render_iterations,
+            max_bounces,
+            resolution,
+            intersection_tests: pixels as f64
+                * render_iterations as f64
+                * (max_bounces as f64 + 1.0)
+                * objects as f64,
+            estimated_time: None,
+        }
+    }
+
+    /// Estimates the render time by rendering a small image with the same aspect ratio
+    /// (the scene's image being replaced)
+    pub fn estimate_time(&mut self, scene: &mut Scene) {
+        let (width, height) = self.resolution;
+        let sample = (
+            SAMPLE_WIDTH.min(width),
+            ((SAMPLE_WIDTH.min(width) * height) / width.max(1)).max(1),
+        );
+        let iterations = self.render_iterations.min(2);
+        if iterations == 0 {
+            return;
+        }
+
+        scene.set_reporter(Arc::new(QuietReporter));
+        scene.set_tile_size(8);
+        let start = Instant::now();
+        scene.render(iterations, self.max_bounces, sample);
+        let sampled = sample.0 as f64 * sample.1 as f64 * iterations as f64;
+        let total = width as f64 * height as f64 * self.render_iterations as f64;
+        self.estimated_time = Some(start.elapsed().mul_f64(total / sampled));
+    }
+
+    /// Human readable statistics
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![String::from("Scene statistics :")];
+        lines.push(format!(
+            "        - Objects : {} spheres, {} planes, {} triangles",
+            self.spheres, self.planes, self.triangles
+        ));
+        for (file, triangles) in &self.meshes {
+            lines.push(format!(
+                "          - Mesh {} : {} triangles",
+                file, triangles
+            ));
+        }
+        match self.bounding_box {
+            Some((min, max)) => lines.push(format!(
+                "        - Bounding box : ({:.2}, {:.2}, {:.2}) to ({:.2}, {:.2}, {:.2})",
+                min.x(),
+                min.y(),
+                min.z(),
+                max.x(),
+                max.y(),
+                max.z()
+            )),
+            None => lines.push(String::from("        - Bounding box : none")),
+        }
+        lines.push(format!(
+            "        - Memory : {:.1} MiB",
+            self.memory as f64 / (1024.0 * 1024.0)
+        ));
+        lines.push(String::from(
+            "        - BVH : none, every ray is tested against every object",
+        ));
+        lines.push(format!(
+            "        - Emitters : {} (power {:.2})",
+            self.emitters, self.emitted_power
+        ));
+        if self.emitting_planes > 0 {
+            lines.push(format!(
+                "        - Emitting planes : {} (unbounded power, not in the emitters)",
+                self.emitting_planes
+            ));
+        }
+        lines.push(format!(
+            "        - Render : {} iterations, {} max bounces, {} x {} pixels",
+            self.render_iterations, self.max_bounces, self.resolution.0, self.resolution.1
+        ));
+        lines.push(format!(
+            "        - Intersection tests : {:.3e} at most",
+            self.intersection_tests
+        ));
+        if let Some(time) = self.estimated_time {
+            lines.push(format!(
+                "        - Estimated render time : {}",
+                s_to_hms(time.as_secs_f64())
+            ));
+        }
+        lines.join("\n")
+    }
+
+    /// Report printed by --stats, as human readable text or as a JSON object
+    pub fn report(&self, format: StatsFormat) -> String {
+        match format {
+            StatsFormat::Text => self.to_text(),
+            StatsFormat::Json => self.to_json().to_string(),
+        }
+    }
+
+    /// Statistics as a JSON object
+    pub fn to_json(&self) -> Value {
+        let vect = |v: Vect3| json!([v.x(), v.y(), v.z()]);
+        json!({
+            "spheres": self.spheres,
+            "planes": self.planes,
+            "triangles": self.triangles,
+            "meshes": self
+                .meshes
+                .iter()
+                .map(|(file, triangles)| json!({"file": file, "triangles": triangles}))
+                .collect::<Vec<Value>>(),
+            "bounding_box": self
+                .bounding_box
+                .map(|(min, max)| json!({"min": vect(min), "max": vect(max)})),
+            "memory": self.memory,
+            "bvh": null,
+            "emitters": self.emitters,
+            "emitted_power": self.emitted_power,
+            "emitting_planes": self.emitting_planes,
+            "iterations": self.render_iterations,
+            "max_bounces": self.max_bounces,
+            "width": self.resolution.0,
+            "height": self.resolution.1,
+            "intersection_tests": self.intersection_tests,
+            "estimated_time": self.estimated_time.map(|t| t.as_secs_f64()),
+        })
+    }
+}
+
+/// Power emitted by a lambertian surface
+fn emitted_power(emitted_light: ColorRBG, area: f64) -> f64 {
+    emitted_light.luminance() * PI * area
+}
+
+/// Bounds of spheres and triangles, moving ones at the start and the end of the frame
+fn bounding_box(spheres: &[Sphere], triangles: &[Triangle]) -> Option<(Vect3, Vect3)> {
+    let frame = [0.0, 1.0];
+    let sphere_bounds = spheres.iter().flat_map(|s| {
+        let radius = Vect3::new(s.radius, s.radius, s.radius);
+        frame.map(|time| {
+            let center = s.position_at(time);
+            (center - radius, center + radius)
+        })
+    });
+    let triangle_bounds = triangles.iter().flat_map(|t| {
+        frame.map(|time| {
+            let [a, b, c] = t.points_at(time);
+            (min(min(a, b), c), max(max(a, b), c))
+        })
+    });
+
+    sphere_bounds
+        .chain(triangle_bounds)
+        .reduce(|(min_1, max_1), (min_2, max_2)| (min(min_1, min_2), max(max_1, max_2)))
+}
+
+fn min(a: Vect3, b: Vect3) -> Vect3 {
+    Vect3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()))
+}
+
+fn max(a: Vect3, b: Vect3) -> Vect3 {
+    Vect3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()))
+}
+
+#[cfg(test)]
+mod tests_stats {
+    use std::f64::consts::PI;
+
+    use approx::assert_abs_diff_eq;
+
+    use super::SceneStats;
+    use crate::mods::{
+        builder::{MaterialBuilder, SceneBuilder},
+        color::ColorRBG,
+        config::StatsFormat,
+        objs::Motion,
+        position::{Quat, Transform, Vect3},
+    };
+
+    #[test]
+    fn stats() {
+        let mut builder = SceneBuilder::new();
+        let light = builder.add_material(
+            MaterialBuilder::new()
+                .emission(ColorRBG::WHITE, 1.0)
+                .build(),
+        );
+        let wall = builder.add_material(MaterialBuilder::new().build());
+        builder
+            .add_sphere(Vect3::new(0.0, 5.0, 0.0), 1.0, light)
+            .unwrap();
+        builder
+            .add_cube(Vect3::new(-2.0, -1.0, -3.0), Quat::identity(), 2.0, wall)
+            .unwrap();
+        builder.add_plane(Vect3::ZERO, Vect3::UP, light).unwrap();
+        let scene = builder.build();
+
+        let stats = SceneStats::new(&scene, &[], 10, 4, (20, 10));
+        assert_eq!((stats.spheres, stats.planes, stats.triangles), (1, 1, 12));
+        assert_eq!((stats.emitters, stats.emitting_planes), (1, 1));
+        assert_abs_diff_eq!(stats.emitted_power, 4.0 * PI * PI, epsilon = 1e-9);
+        assert_eq!(
+            stats.bounding_box,
+            Some((Vect3::new(-2.0, -1.0, -3.0), Vect3::new(1.0, 6.0, 1.0)))
+        );
+        assert_eq!(stats.intersection_tests, 200.0 * 10.0 * 5.0 * 14.0);
+        assert_eq!(stats.to_json()["meshes"], serde_json::json!([]));
+
+        let report: serde_json::Value =
+            serde_json::from_str(&stats.report(StatsFormat::Json)).unwrap();
+        assert_eq!(report["triangles"], 12);
+        assert_eq!(report["emitting_planes"], 1);
+        assert_eq!(
+            report["bounding_box"]["max"],
+            serde_json::json!([1.0, 6.0, 1.0])
+        );
+        let text = stats.report(StatsFormat::Text);
+        assert!(text.starts_with("Scene statistics :"));
+        assert!(text.contains("- Emitting planes : 1 (unbounded power, not in the emitters)"));
+    }
+
+    #[test]
+    fn moving_bounding_box() {
+        let mut builder = SceneBuilder::new();
+        let wall = builder.add_material(MaterialBuilder::new().build());
+        let sphere = builder.add_sphere(Vect3::ZERO, 1.0, wall).unwrap();
+        builder
+            .sphere_mut(sphere)
+            .unwrap()
+            .set_motion(Transform::new(Vect3::new(4.0, 0.0, 0.0), Quat::identity()));
+        let triangles = builder
+            .add_triangle((Vect3::ZERO, Vect3::RIGHT, Vect3::UP), wall)
+            .unwrap();
+        let motion = Motion::new(
+            Transform::new(Vect3::ZERO, Quat::identity()),
+            Transform::new(Vect3::new(0.0, 0.0, -3.0), Quat::identity()),
+        );
+        builder.triangles_mut(&triangles).unwrap()[0].set_motion(motion);
+        let scene = builder.build();
+
+        let stats = SceneStats::new(&scene, &[], 10, 4, (20, 10));
+        assert_eq!(
+            stats.bounding_box,
+            Some((Vect3::new(-1.0, -1.0, -3.0), Vect3::new(5.0, 1.0, 1.0)))
+        );
+    }
+
+    #[test]
+    fn estimated_time() {
+        let mut builder = SceneBuilder::new();
+        let light = builder.add_material(
+            MaterialBuilder::new()
+                .emission(ColorRBG::WHITE, 1.0)
+                .build(),
+        );
+        builder
+            .add_sphere(10.0 * Vect3::FORWARD, 3.0, light)
+            .unwrap();
+        let mut scene = builder.build();
+
+        let mut stats = SceneStats::new(&scene, &[], 4, 2, (16, 8));
+        stats.estimate_time(&mut scene);
+        assert!(stats.estimated_time.is_some());
+        assert_eq!(scene.camera.image.get_width(), 16);
+    }
+}
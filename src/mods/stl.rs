@@ -0,0 +1,334 @@
+use super::{
+    mesh::{triangulate, words, Face, Mesh},
+    parse_error::{ParseError, ParseErrors},
+    position::Vect3,
+};
+
+/// Size of the header and triangle count of a binary STL file
+const BINARY_HEADER: usize = 84;
+
+/// Size of a triangle of a binary STL file (normal, 3 vertices and attribute)
+const BINARY_TRIANGLE: usize = 50;
+
+/// Reads an STL file (ASCII or binary)
+///
+/// Each triangle gets its own vertices, which `Mesh::weld` can merge. The solids of an
+/// ASCII file are the objects of the mesh
+pub fn read_stl(bytes: &[u8], file_name: &str) -> Result<Mesh, ParseErrors> {
+    let error = |line: usize, column: usize, message: String| {
+        ParseErrors(vec![ParseError::new(file_name, line, column, message)])
+    };
+
+    // Binary files may start with "solid" too, but their size is given by their count, and
+    // they have zero bytes
+    let count = bytes
+        .get(80..BINARY_HEADER)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize);
+    let binary_size = count.map(|count| BINARY_HEADER + BINARY_TRIANGLE * count);
+    let ascii = bytes
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .is_some_and(|start| bytes[start..].starts_with(b"solid"))
+        && !bytes.contains(&0);
+    if ascii && binary_size != Some(bytes.len()) {
+        return read_ascii(&String::from_utf8_lossy(bytes))
+            .map_err(|(line, column, message)| error(line, column, message));
+    }
+    let (Some(count), Some(binary_size)) = (count, binary_size) else {
+        return Err(error(
+            0,
+            0,
+            format!(
+                "not an STL file ({} bytes, a binary file has at least {})",
+                bytes.len(),
+                BINARY_HEADER
+            ),
+        ));
+    };
+    if bytes.len() != binary_size {
+        return Err(error(
+            0,
+            0,
+            format!(
+                "a binary STL file of {} triangles has {} bytes, found {}",
+                count,
+                binary_size,
+                bytes.len()
+            ),
+        ));
+    }
+
+    let mut mesh = Mesh {
+        group_sets: vec![vec![]],
+        ..Mesh::default()
+    };
+    for (i, triangle) in bytes[BINARY_HEADER..]
+        .chunks_exact(BINARY_TRIANGLE)
+        .enumerate()
+    {
+        // The normal (first 3 numbers) is given by the winding
+        let number = |n: usize| {
+            let b = &triangle[4 * n..4 * n + 4];
+            f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64
+        };
+        let points = [3, 6, 9].map(|n| Vect3::new(number(n), number(n + 1), number(n + 2)));
+        if points
+            .iter()
+            .any(|p| !p.to_arr().iter().all(|c| c.is_finite()))
+        {
+            return Err(error(
+                0,
+                0,
+                format!("triangle {} has an invalid coordinate", i + 1),
+            ));
+        }
+        add_polygon(&mut mesh, &points, None);
+    }
+    Ok(mesh)
+}
+
+/// Adds a polygon to a mesh, with new vertices
+fn add_polygon(mesh: &mut Mesh, points: &[Vect3], object: Option<usize>) {
+    let first = mesh.vertices.len();
+    mesh.vertices.extend(points);
+    for triangle in triangulate(points) {
+        mesh.faces.push(Face {
+            vertices: triangle.map(|i| first + i),
+            object,
+            groups: 0,
+            material: None,
+            color: None,
+        });
+    }
+}
+
+/// Line, column and message of an error
+type StlError = (usize, usize, String);
+
+/// Words of an ASCII STL file, with their line and column
+struct Words<'a> {
+    words: Vec<(&'a str, usize, usize)>,
+    next: usize,
+    /// Line of the end of the file
+    last_line: usize,
+}
+
+impl<'a> Words<'a> {
+    fn next(&mut self, expected: &str) -> Result<(&'a str, usize, usize), StlError> {
+        let word = self.words.get(self.next).copied().ok_or_else(|| {
+            (
+                self.last_line,
+                1,
+                format!("expected {}, found end of file", expected),
+            )
+        })?;
+        self.next += 1;
+        Ok(word)
+    }
+
+    fn expect(&mut self, keyword: &str) -> Result<(), StlError> {
+        let expected = format!("`{}`", keyword);
+        match self.next(&expected)? {
+            (word, _, _) if word == keyword => Ok(()),
+            (word, line, column) => Err((
+                line,
+                column,
+                format!("expected {}, found `{}`", expected, word),
+            )),
+        }
+    }
+
+    fn number(&mut self) -> Result<f64, StlError> {
+        let (word, line, column) = self.next("a number")?;
+        word.parse::<f64>()
+            .ok()
+            .filter(|n| n.is_finite())
+            .ok_or_else(|| (line, column, format!("expected a number, found `{}`", word)))
+    }
+
+    /// Words until the end of a line
+    fn rest_of_line(&mut self, line: usize) -> Vec<&'a str> {
+        let mut rest = vec![];
+        while let Some(&(word, _, _)) = self.words.get(self.next).filter(|w| w.1 == line) {
+            rest.push(word);
+            self.next += 1;
+        }
+        rest
+    }
+}
+
+/// Reads an ASCII STL file, giving the line, column and message of its first error
+fn read_ascii(source: &str) -> Result<Mesh, StlError> {
+    let mut words = Words {
+        words: source
+            .lines()
+            .enumerate()
+            .flat_map(|(i, line)| {
+                words(line)
+                    .into_iter()
+                    .map(move |w| (w.text, i + 1, w.column))
+            })
+            .collect(),
+        next: 0,
+        last_line: source.lines().count().max(1),
+    };
+    let mut mesh = Mesh {
+        group_sets: vec![vec![]],
+        ..Mesh::default()
+    };
+
+    // Files may have several solids
+    while words.next < words.words.len() {
+        let (word, line, column) = words.next("`solid`")?;
+        if word != "solid" {
+            return Err((line, column, format!("expected `solid`, found `{}`", word)));
+        }
+        let name = words.rest_of_line(line).join(" ");
+        let object = match mesh.objects.iter().position(|o| *o == name) {
+            Some(index) => index,
+            None => {
+                mesh.objects.push(name);
+                mesh.objects.len() - 1
+            }
+        };
+        loop {
+            let (word, line, column) = words.next("`facet` or `endsolid`")?;
+            match word {
+                "facet" => {
+                    // The normal is given by the winding
+                    words.expect("normal")?;
+                    for _ in 0..3 {
+                        words.number()?;
+                    }
+                    words.expect("outer")?;
+                    words.expect("loop")?;
+                    let mut points = vec![];
+                    loop {
+                        match words.next("`vertex` or `endloop`")? {
+                            ("vertex", _, _) => points.push(Vect3::new(
+                                words.number()?,
+                                words.number()?,
+                                words.number()?,
+                            )),
+                            ("endloop", line, column) if points.len() < 3 => {
+                                return Err((
+                                    line,
+                                    column,
+                                    format!(
+                                        "facet has {} vertices, at least 3 are needed",
+                                        points.len()
+                                    ),
+                                ))
+                            }
+                            ("endloop", _, _) => break,
+                            (word, line, column) => {
+                                return Err((
+                                    line,
+                                    column,
+                                    format!("expected `vertex` or `endloop`, found `{}`", word),
+                                ))
+                            }
+                        }
+                    }
+                    words.expect("endfacet")?;
+                    add_polygon(&mut mesh, &points, Some(object));
+                }
+                "endsolid" => {
+                    // Its name is not checked, some exporters not repeating it
+                    words.rest_of_line(line);
+                    break;
+                }
+                word => {
+                    return Err((
+                        line,
+                        column,
+                        format!("expected `facet` or `endsolid`, found `{}`", word),
+                    ))
+                }
+            }
+        }
+    }
+    Ok(mesh)
+}
+
+#[cfg(test)]
+mod tests_stl {
+    use super::read_stl;
+    use crate::mods::position::Vect3;
+
+    /// Binary file of triangles, with a header starting like an ASCII file
+    fn binary(triangles: &[[(f32, f32, f32); 3]]) -> Vec<u8> {
+        let mut bytes = b"solid exported by a CAD tool".to_vec();
+        bytes.resize(80, b' ');
+        bytes.extend((triangles.len() as u32).to_le_bytes());
+        for triangle in triangles {
+            bytes.extend([0.0f32; 3].iter().flat_map(|n| n.to_le_bytes()));
+            for (x, y, z) in triangle {
+                bytes.extend([x, y, z].iter().flat_map(|n| n.to_le_bytes()));
+            }
+            bytes.extend([0, 0]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn ascii() {
+        let mesh = read_stl(
+            b"solid part one\n  facet normal 0 0 1\n    outer loop\n      vertex 0 0 0\n      vertex 1 0 0\n      vertex 1 1 0\n    endloop\n  endfacet\nendsolid part one\nsolid two\nfacet normal 0 0 0\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nvertex 1 1 0\nvertex 0 1 0\nendloop\nendfacet\nendsolid\n",
+            "a.stl",
+        )
+        .unwrap();
+
+        assert_eq!(mesh.objects, vec!["part one", "two"]);
+        assert_eq!(mesh.vertices.len(), 7);
+        assert_eq!(mesh.vertices[2], Vect3::new(1.0, 1.0, 0.0));
+        assert_eq!(mesh.triangles(&[1], &[]).len(), 2);
+    }
+
+    #[test]
+    fn binary_and_weld() {
+        let square = [
+            [(0.0, 0.0, 0.0), (1.0, 0.0, 0.0), (1.0, 1.0, 0.0)],
+            [(0.0, 0.0, 0.0), (1.0, 1.0, 0.0), (0.0, 1.0, 0.0)],
+            // A sliver, collapsed by a tolerance of 0.01
+            [(0.0, 1.0, 0.0), (1.0, 1.0, 0.0), (1.0, 1.001, 0.0)],
+        ];
+        let mut mesh = read_stl(&binary(&square), "a.stl").unwrap();
+        assert_eq!(mesh.vertices.len(), 9);
+        assert_eq!(mesh.faces.len(), 3);
+
+        mesh.weld(0.0);
+        assert_eq!(mesh.vertices.len(), 5);
+        assert_eq!(mesh.faces.len(), 3);
+        mesh.weld(0.01);
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.faces.len(), 2);
+        assert_eq!(mesh.faces[1].vertices, [0, 2, 3]);
+    }
+
+    #[test]
+    fn stl_errors() {
+        let error = |bytes: &[u8]| read_stl(bytes, "a.stl").unwrap_err().0[0].to_string();
+
+        assert_eq!(
+            error(b"solid a\nfacet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0\nendloop\n"),
+            "a.stl:6:1: expected a number, found `endloop`"
+        );
+        assert_eq!(
+            error(
+                b"solid a\nfacet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nendloop\n"
+            ),
+            "a.stl:6:1: facet has 2 vertices, at least 3 are needed"
+        );
+        assert_eq!(
+            error(b"solid a\nfacet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nvertex 1 1 0\nendloop\nendfacet\n"),
+            "a.stl:8:1: expected `facet` or `endsolid`, found end of file"
+        );
+        let mut truncated = binary(&[[(0.0, 0.0, 0.0); 3]]);
+        truncated.pop();
+        assert_eq!(
+            error(&truncated),
+            "a.stl: a binary STL file of 1 triangles has 134 bytes, found 133"
+        );
+    }
+}
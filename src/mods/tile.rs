@@ -0,0 +1,98 @@
+use std::f64::consts::PI;
+
+/// Default tile side, in pixels
+pub static DEFAULT_TILE_SIZE: usize = 32;
+
+/// Rectangular block of pixels rendered by a single task
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Tile {
+    /// Get number of pixels
+    #[inline]
+    pub fn area(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// Get image coordinates of the pixels, row by row
+    #[inline]
+    pub fn pixels(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (self.y..self.y + self.height)
+            .flat_map(move |y| (self.x..self.x + self.width).map(move |x| (x, y)))
+    }
+}
+
+/// Splits an image into tiles (smaller on the right and bottom edges),
+/// ordered in a spiral going out from the center so that the middle of the image converges first
+pub fn spiral_tiles(width: usize, height: usize, size: usize) -> Vec<Tile> {
+    let size = size.max(1);
+    let columns = width.div_ceil(size);
+    let rows = height.div_ceil(size);
+
+    let mut tiles: Vec<Tile> = (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (column, row)))
+        .map(|(column, row)| Tile {
+            x: column * size,
+            y: row * size,
+            width: size.min(width - column * size),
+            height: size.min(height - row * size),
+        })
+        .collect();
+
+    // Ring around the center tile, then angle inside the ring
+    let center = ((columns as f64 - 1.0) / 2.0, (rows as f64 - 1.0) / 2.0);
+    let spiral_key = |tile: &Tile| {
+        let dx = (tile.x / size) as f64 - center.0;
+        let dy = (tile.y / size) as f64 - center.1;
+        let ring = dx.abs().max(dy.abs()).ceil();
+        let angle = dy.atan2(dx).rem_euclid(2.0 * PI);
+        (ring, angle)
+    };
+    tiles.sort_by(|a, b| spiral_key(a).partial_cmp(&spiral_key(b)).unwrap());
+    tiles
+}
+
+#[cfg(test)]
+mod tests_tile {
+    use super::{spiral_tiles, Tile};
+
+    #[test]
+    fn cover() {
+        let (width, height) = (70, 45);
+        let tiles = spiral_tiles(width, height, 16);
+        let mut covered = vec![0; width * height];
+        for tile in &tiles {
+            for (x, y) in tile.pixels() {
+                covered[y * width + x] += 1;
+            }
+        }
+
+        assert_eq!(tiles.len(), 5 * 3);
+        assert!(covered.iter().all(|&c| c == 1));
+        assert_eq!(
+            tiles.iter().map(|t| t.area()).sum::<usize>(),
+            width * height
+        );
+    }
+
+    #[test]
+    fn spiral_order() {
+        let tiles = spiral_tiles(48, 48, 16);
+
+        assert_eq!(
+            tiles[0],
+            Tile {
+                x: 16,
+                y: 16,
+                width: 16,
+                height: 16
+            }
+        );
+        assert!(tiles[1..].iter().all(|t| t.x != 16 || t.y != 16));
+    }
+}
@@ -0,0 +1,524 @@
+use std::error::Error;
+
+use serde_json::{json, Map, Value};
+
+use super::{
+    color::ColorRBG,
+    material::Material,
+    objs::TriangleBlock,
+    position::{Angle, Quat, Vect3},
+    render::Scene,
+};
+
+/// Value of a block property
+enum Property {
+    Number(f64),
+    Numbers(Vec<f64>),
+    Name(String),
+    Names(Vec<String>),
+}
+
+/// Block of a scene file, with its properties in writing order
+struct Block {
+    kind: &'static str,
+    properties: Vec<(&'static str, Property)>,
+}
+
+impl Block {
+    fn new(kind: &'static str) -> Block {
+        Block {
+            kind,
+            properties: vec![],
+        }
+    }
+
+    fn number(mut self, name: &'static str, value: f64) -> Block {
+        self.properties.push((name, Property::Number(value)));
+        self
+    }
+
+    fn numbers(mut self, name: &'static str, values: Vec<f64>) -> Block {
+        self.properties.push((name, Property::Numbers(values)));
+        self
+    }
+
+    fn vect3(self, name: &'static str, v: Vect3) -> Block {
+        self.numbers(name, v.to_arr().to_vec())
+    }
+
+    fn quat(self, name: &'static str, q: Quat) -> Block {
+        let v = q.v();
+        self.numbers(name, vec![q.w(), v.x(), v.y(), v.z()])
+    }
+
+    fn color(self, name: &'static str, color: ColorRBG) -> Block {
+        let (r, g, b) = color.get_value();
+        self.numbers(name, vec![r, g, b])
+    }
+
+    fn name(mut self, name: &'static str, value: String) -> Block {
+        self.properties.push((name, Property::Name(value)));
+        self
+    }
+
+    /// Adds names, unless there are none
+    fn names(mut self, name: &'static str, values: &[String]) -> Block {
+        if !values.is_empty() {
+            self.properties
+                .push((name, Property::Names(values.to_vec())));
+        }
+        self
+    }
+}
+
+/// Materials of a scene, de-duplicated and named mat_1, mat_2… in order of first use
+#[derive(Default)]
+struct Materials(Vec<Material>);
+
+impl Materials {
+    /// Name of a material, added if it was not used yet
+    fn name(&mut self, material: &Material) -> String {
+        let index = match self.0.iter().position(|m| m == material) {
+            Some(index) => index,
+            None => {
+                self.0.push(*material);
+                self.0.len() - 1
+            }
+        };
+        format!("mat_{}", index + 1)
+    }
+
+    fn blocks(&self) -> Vec<Block> {
+        self.0
+            .iter()
+            .enumerate()
+            .map(|(i, material)| {
+                Block::new("material")
+                    .name("name", format!("mat_{}", i + 1))
+                    .color("color", material.color)
+                    .color("emission_color", material.emission_color)
+                    .color("specular_color", material.specular_color)
+                    .number("emission_strength", material.emission_strengh)
+                    .number("smoothness", material.smoothness)
+                    .number("specular_prob", material.specular_prob)
+            })
+            .collect()
+    }
+}
+
+/// Blocks of a scene: camera, materials, spheres, planes, triangles, cubes and meshes
+/// (triangles created by a cube or mesh block being written as that block)
+///
+/// Fails on a moving triangle outside of a mesh block, triangle blocks having no motion
+fn scene_blocks(scene: &Scene) -> Result<Vec<Block>, Box<dyn Error>> {
+    let mut materials = Materials::default();
+    let mut objects = vec![];
+
+    for sphere in &scene.spheres {
+        let mut block = Block::new("sphere")
+            .vect3("position", sphere.transform.get_pos())
+            .number("radius", sphere.radius);
+        if let Some(end) = sphere.end_transform {
+            block = block.vect3("end_position", end.get_pos());
+        }
+        objects.push(block.name("mat", materials.name(sphere.get_mat())));
+    }
+
+    for plane in &scene.planes {
+        objects.push(
+            Block::new("plane")
+                .vect3("point", plane.get_point())
+                .vect3("normal", plane.get_normal())
+                .name("mat", materials.name(plane.get_mat())),
+        );
+    }
+
+    // A group is only written as its block if its triangles still have its material (the
+    // materials of a mesh file cannot be checked)
+    let groups: Vec<_> = scene
+        .triangle_groups
+        .iter()
+        .filter(|group| {
+            group.material.map_or(true, |material| {
+                scene.triangles[group.triangles.clone()]
+                    .iter()
+                    .all(|t| *t.get_mat() == material)
+            })
+        })
+        .collect();
+    for (i, triangle) in scene.triangles.iter().enumerate() {
+        if groups.iter().any(|group| group.triangles.contains(&i)) {
+            continue;
+        }
+        if triangle.is_moving() {
+            return Err(format!(
+                "triangle {} moves, which can only be written in a mesh block",
+                i + 1
+            )
+            .into());
+        }
+        let [point_1, point_2, point_3] = triangle.points();
+        objects.push(
+            Block::new("triangle")
+                .vect3("point_1", point_1)
+                .vect3("point_2", point_2)
+                .vect3("point_3", point_3)
+                .name("mat", materials.name(triangle.get_mat())),
+        );
+    }
+
+    let (cubes, meshes): (Vec<_>, Vec<_>) = groups
+        .into_iter()
+        .partition(|group| matches!(group.block, TriangleBlock::Cube { .. }));
+    for group in cubes.into_iter().chain(meshes) {
+        let block = match &group.block {
+            TriangleBlock::Cube {
+                position,
+                rotation,
+                size,
+            } => Block::new("cube")
+                .vect3("position", *position)
+                .quat("rotation", *rotation)
+                .number("size", *size),
+            TriangleBlock::Mesh {
+                file,
+                format,
+                objects,
+                groups,
+                scale,
+                weld,
+                position,
+                rotation,
+                motion,
+            } => {
+                let mut block = Block::new("mesh")
+                    .name(format.key(), file.clone())
+                    .names("objects", objects)
+                    .names("groups", groups);
+                if *scale != 1.0 {
+                    block = block.number("scale", *scale);
+                }
+                if let Some(weld) = weld {
+                    block = block.number("weld", *weld);
+                }
+                block = block
+                    .vect3("position", *position)
+                    .quat("rotation", *rotation);
+                if let Some((end_position, end_rotation)) = motion {
+                    block = block
+                        .vect3("end_position", *end_position)
+                        .quat("end_rotation", *end_rotation);
+                }
+                block
+            }
+        };
+        objects.push(match &group.material {
+            Some(material) => block.name("mat", materials.name(material)),
+            None => block,
+        });
+    }
+
+    let camera = &scene.camera;
+    let mut camera_block = Block::new("camera")
+        .vect3("position", camera.transform.get_pos())
+        .quat("rotation", camera.transform.get_rot())
+        .number("focal_length", camera.get_focal())
+        .number("fov", fov_degrees(camera.get_fov()));
+    if let Some(end) = camera.end_transform {
+        camera_block = camera_block
+            .vect3("end_position", end.get_pos())
+            .quat("end_rotation", end.get_rot());
+    }
+    let (open, close) = camera.get_shutter();
+    if (open, close) != (0.0, 0.0) {
+        camera_block = camera_block.numbers("shutter", vec![open, close]);
+    }
+
+    let mut blocks = vec![camera_block];
+    blocks.extend(materials.blocks());
+    blocks.extend(objects);
+    Ok(blocks)
+}
+
+/// Field of view in degrees, with the fewest digits giving back the same angle
+fn fov_degrees(fov: Angle) -> f64 {
+    let degrees = fov.get().to_degrees();
+    (0..17)
+        .filter_map(|digits| format!("{:.*e}", digits, degrees).parse::<f64>().ok())
+        .find(|d| Angle::from_deg(*d).get() == fov.get())
+        .unwrap_or(degrees)
+}
+
+/// Name written as is when the lexer reads it back as a name, quoted otherwise
+fn format_name(name: &str) -> String {
+    let mut chars = name.chars();
+    let plain = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || "_./".contains(c))
+        && !name.contains("..")
+        && name != "true"
+        && name != "false";
+    if plain {
+        return name.to_string();
+    }
+    let escaped = name
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t");
+    format!("\"{}\"", escaped)
+}
+
+/// Writes a scene as a canonical .rtp file: camera, materials (de-duplicated), spheres,
+/// planes, triangles, cubes and meshes (referenced by their .obj file), failing on moving
+/// triangles outside of a mesh block
+pub fn scene_to_rtp(scene: &Scene) -> Result<String, Box<dyn Error>> {
+    let blocks: Vec<String> = scene_blocks(scene)?
+        .iter()
+        .map(|block| {
+            let mut text = format!("{} {{\n", block.kind);
+            for (name, property) in &block.properties {
+                let value = match property {
+                    Property::Number(n) => n.to_string(),
+                    Property::Numbers(values) => values
+                        .iter()
+                        .map(f64::to_string)
+                        .collect::<Vec<String>>()
+                        .join(" "),
+                    Property::Name(value) => format_name(value),
+                    Property::Names(values) => values
+                        .iter()
+                        .map(|value| format_name(value))
+                        .collect::<Vec<String>>()
+                        .join(" "),
+                };
+                text += &format!("    {}: {}\n", name, value);
+            }
+            text + "}\n"
+        })
+        .collect();
+    Ok(blocks.join("\n"))
+}
+
+/// Writes a scene as JSON: a camera object, and arrays of materials, spheres, planes,
+/// triangles, cubes and meshes, with the properties of the .rtp blocks (failing like
+/// scene_to_rtp)
+pub fn scene_to_json(scene: &Scene) -> Result<Value, Box<dyn Error>> {
+    let mut root = Map::new();
+    root.insert(String::from("camera"), Value::Null);
+    for kind in [
+        "materials",
+        "spheres",
+        "planes",
+        "triangles",
+        "cubes",
+        "meshes",
+    ] {
+        root.insert(kind.to_string(), json!([]));
+    }
+
+    for block in scene_blocks(scene)? {
+        let object: Map<String, Value> = block
+            .properties
+            .into_iter()
+            .map(|(name, property)| {
+                let value = match property {
+                    Property::Number(n) => json!(n),
+                    Property::Numbers(values) => json!(values),
+                    Property::Name(value) => json!(value),
+                    Property::Names(values) => json!(values),
+                };
+                (name.to_string(), value)
+            })
+            .collect();
+        match block.kind {
+            "camera" => {
+                root.insert(String::from("camera"), Value::Object(object));
+            }
+            kind => {
+                let key = match kind {
+                    "mesh" => String::from("meshes"),
+                    kind => format!("{}s", kind),
+                };
+                if let Some(Value::Array(array)) = root.get_mut(&key) {
+                    array.push(Value::Object(object));
+                }
+            }
+        }
+    }
+    Ok(Value::Object(root))
+}
+
+#[cfg(test)]
+mod tests_writer {
+    use std::fs;
+
+    use approx::assert_abs_diff_eq;
+    use serde_json::Value;
+
+    use super::{format_name, scene_to_json, scene_to_rtp};
+    use crate::mods::{
+        builder::{MaterialBuilder, SceneBuilder},
+        color::ColorRBG,
+        objs::Motion,
+        parser::Parser,
+        position::{Quat, Transform, Vect3},
+        render::Scene,
+    };
+
+    fn parse(source: &str, file_name: &str) -> Scene {
+        Parser::new(source, file_name)
+            .parse_scene()
+            .unwrap_or_else(|e| panic!("{}: {}", file_name, e))
+    }
+
+    /// Whether two JSON values are equal, numbers being compared with a tolerance
+    fn same(a: &Value, b: &Value) -> bool {
+        match (a, b) {
+            (Value::Number(a), Value::Number(b)) => {
+                let (a, b) = (a.as_f64().unwrap(), b.as_f64().unwrap());
+                (a - b).abs() <= 1e-9 * a.abs().max(1.0)
+            }
+            (Value::Array(a), Value::Array(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same(a, b))
+            }
+            (Value::Object(a), Value::Object(b)) => {
+                a.len() == b.len() && a.iter().all(|(k, v)| b.get(k).is_some_and(|w| same(v, w)))
+            }
+            (a, b) => a == b,
+        }
+    }
+
+    /// Asserts that two scenes have the same objects, at the start and the end of the frame
+    fn assert_same_objects(a: &Scene, b: &Scene, name: &str) {
+        let transforms = |scene: &Scene| {
+            [0.0, 1.0].map(|time| {
+                let camera = scene.camera.transform_at(time);
+                (camera.get_pos(), camera.get_rot().v(), camera.get_rot().w())
+            })
+        };
+        for (a, b) in transforms(a).iter().zip(transforms(b)) {
+            assert_abs_diff_eq!(a.0, b.0, epsilon = 1e-9);
+            assert_abs_diff_eq!(a.1, b.1, epsilon = 1e-9);
+            assert_abs_diff_eq!(a.2, b.2, epsilon = 1e-9);
+        }
+
+        assert_eq!(a.spheres.len(), b.spheres.len(), "{}", name);
+        for (a, b) in a.spheres.iter().zip(&b.spheres) {
+            assert_eq!(a.radius, b.radius, "{}", name);
+            assert_eq!(a.get_mat(), b.get_mat(), "{}", name);
+            for time in [0.0, 1.0] {
+                assert_abs_diff_eq!(a.position_at(time), b.position_at(time), epsilon = 1e-9);
+            }
+        }
+
+        assert_eq!(a.planes.len(), b.planes.len(), "{}", name);
+        for (a, b) in a.planes.iter().zip(&b.planes) {
+            assert_abs_diff_eq!(a.get_point(), b.get_point(), epsilon = 1e-9);
+            assert_abs_diff_eq!(a.get_normal(), b.get_normal(), epsilon = 1e-9);
+            assert_eq!(a.get_mat(), b.get_mat(), "{}", name);
+        }
+
+        assert_eq!(a.triangles.len(), b.triangles.len(), "{}", name);
+        for (a, b) in a.triangles.iter().zip(&b.triangles) {
+            assert_eq!(a.get_mat(), b.get_mat(), "{}", name);
+            assert_eq!(a.is_moving(), b.is_moving(), "{}", name);
+            for time in [0.0, 1.0] {
+                for (p, q) in a.points_at(time).iter().zip(b.points_at(time)) {
+                    assert_abs_diff_eq!(*p, q, epsilon = 1e-9);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut files: Vec<_> = fs::read_dir("scenes")
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|e| e == "rtp"))
+            .collect();
+        files.sort();
+        assert!(!files.is_empty());
+
+        for file in files {
+            let name = file.to_str().unwrap();
+            let scene = parse(&fs::read_to_string(&file).unwrap(), name);
+            let written = scene_to_rtp(&scene).unwrap();
+            let parsed = parse(&written, name);
+
+            assert!(
+                same(
+                    &scene_to_json(&scene).unwrap(),
+                    &scene_to_json(&parsed).unwrap()
+                ),
+                "{} changed when written back:\n{}",
+                name,
+                written
+            );
+            assert_same_objects(&scene, &parsed, name);
+            assert_eq!(scene_to_rtp(&parsed).unwrap(), written, "{}", name);
+
+            let json = scene_to_json(&scene).unwrap();
+            let from_json = parse(&json.to_string(), &name.replace(".rtp", ".json"));
+            assert!(same(&scene_to_json(&from_json).unwrap(), &json), "{}", name);
+            assert_same_objects(&scene, &from_json, name);
+        }
+    }
+
+    #[test]
+    fn canonical() {
+        let mut builder = SceneBuilder::new();
+        let red = builder.add_material(MaterialBuilder::new().color(ColorRBG::RED).build());
+        let same_red = builder.add_material(MaterialBuilder::new().color(ColorRBG::RED).build());
+        builder
+            .add_sphere(Vect3::new(0.0, 1.0, -2.5), 1.0, red)
+            .unwrap();
+        builder
+            .add_cube(Vect3::ZERO, Quat::identity(), 2.0, same_red)
+            .unwrap();
+        let scene = builder.build();
+
+        let written = scene_to_rtp(&scene).unwrap();
+        assert_eq!(written.matches("material {").count(), 1);
+        assert!(written.contains(
+            "cube {\n    position: 0 0 0\n    rotation: 1 0 0 0\n    size: 2\n    mat: mat_1\n}"
+        ));
+        assert!(written.contains("    position: 0 1 -2.5\n"));
+        assert!(!written.contains("triangle"));
+
+        let json = scene_to_json(&scene).unwrap();
+        assert_eq!(json["spheres"][0]["mat"], "mat_1");
+        assert_eq!(json["cubes"][0]["size"], 2.0);
+        assert_eq!(
+            json["materials"][0]["color"],
+            serde_json::json!([1.0, 0.0, 0.0])
+        );
+    }
+
+    #[test]
+    fn moving_triangle() {
+        let mut builder = SceneBuilder::new();
+        let red = builder.add_material(MaterialBuilder::new().color(ColorRBG::RED).build());
+        let triangles = builder
+            .add_triangle((Vect3::ZERO, Vect3::RIGHT, Vect3::UP), red)
+            .unwrap();
+        let end = Transform::new(Vect3::UP, Quat::identity());
+        builder.triangles_mut(&triangles).unwrap()[0].set_motion(Motion::new(
+            Transform::new(Vect3::ZERO, Quat::identity()),
+            end,
+        ));
+        let scene = builder.build();
+
+        let error = "triangle 1 moves, which can only be written in a mesh block";
+        assert_eq!(scene_to_rtp(&scene).unwrap_err().to_string(), error);
+        assert_eq!(scene_to_json(&scene).unwrap_err().to_string(), error);
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(format_name("obj/monkey.obj"), "obj/monkey.obj");
+        assert_eq!(format_name("my mesh.obj"), "\"my mesh.obj\"");
+        assert_eq!(format_name("true"), "\"true\"");
+        assert_eq!(format_name("1.obj"), "\"1.obj\"");
+    }
+}